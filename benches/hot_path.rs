@@ -0,0 +1,160 @@
+// times the innermost loop of the renderer, intersecting and scattering rays in a scene of
+// shared materials, and counts the heap allocations it makes along the way, which should be
+// none. run with `cargo bench`, it exits with an error when the hot path allocates
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::process::ExitCode;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use ray_tracing::background::Background;
+use ray_tracing::bvh::{BvhNode, SplitHeuristic};
+use ray_tracing::hittable::{HitRecord, Hittable};
+use ray_tracing::hittable_list::HittableList;
+use ray_tracing::integrator::{Integrator, MisPathTracer, PathTracer};
+use ray_tracing::material::{Conductor, Dielectric, DiffuseLight, Lambertian, Material, Metal};
+use ray_tracing::principled::Principled;
+use ray_tracing::ray::Ray;
+use ray_tracing::sphere::Sphere;
+use ray_tracing::utils::{random, seed_random};
+use ray_tracing::vec3::Vec3;
+
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+  unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+    ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+    System.alloc(layout)
+  }
+  unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+    System.dealloc(ptr, layout)
+  }
+  unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+    ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+    System.realloc(ptr, layout, new_size)
+  }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+const RAYS: usize = 200_000;
+const RUNS: usize = 3;
+
+// a ground, a light and a grid of small spheres sharing a handful of materials
+fn scene() -> (BvhNode, HittableList) {
+  let materials: Vec<Arc<dyn Material>> = vec![
+    Arc::new(Lambertian::new(Vec3(0.7, 0.3, 0.3))),
+    Arc::new(Metal::new(Vec3(0.8, 0.8, 0.8)).set_fuzz(0.1)),
+    Arc::new(Dielectric::new(1.5)),
+    Arc::new(Conductor::gold(0.3)),
+    Arc::new(Principled::new(Vec3(0.2, 0.4, 0.8))),
+  ];
+
+  let mut list = HittableList::new();
+  let mut ground = Sphere::new(Vec3(0.0, -1000.0, 0.0), 1000.0);
+  ground.set_material(Arc::new(Lambertian::new(Vec3::fill(0.5))));
+  list.add(Box::new(ground));
+
+  for a in -10..10 {
+    for b in -10..10 {
+      let center = Vec3(a as f64 + 0.9 * random(), 0.2, b as f64 + 0.9 * random());
+      let mut sphere = Sphere::new(center, 0.2);
+      sphere.set_material(Arc::clone(&materials[(a + b + 20) as usize % materials.len()]));
+      list.add(Box::new(sphere));
+    }
+  }
+
+  let mut light = Sphere::new(Vec3(0.0, 6.0, 0.0), 1.5);
+  light.set_material(Arc::new(DiffuseLight::new(Vec3::fill(8.0))));
+  list.add(Box::new(light));
+
+  let mut lights = HittableList::new();
+  lights.add(Box::new(Sphere::new(Vec3(0.0, 6.0, 0.0), 1.5)));
+
+  (BvhNode::new(list, 0.0, 1.0, SplitHeuristic::Sah), lights)
+}
+
+// rays from above the grid looking down into it
+fn rays() -> Vec<Ray> {
+  (0..RAYS)
+    .map(|_| {
+      let origin = Vec3(0.0, 3.0, 12.0);
+      let target = Vec3(20.0 * random() - 10.0, 0.0, 20.0 * random() - 10.0);
+      Ray::new(origin, target - origin)
+    })
+    .collect()
+}
+
+// the fastest of a few runs, with the allocations of the last one per ray
+fn measure<F: FnMut(&Ray) -> f64>(name: &str, rays: &[Ray], mut trace: F) -> usize {
+  let mut fastest = Duration::MAX;
+  let mut allocations = 0;
+  let mut checksum = 0.0;
+
+  for _ in 0..RUNS {
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    let start = Instant::now();
+    for ray in rays {
+      checksum += trace(ray);
+    }
+    fastest = fastest.min(start.elapsed());
+    allocations = ALLOCATIONS.load(Ordering::Relaxed) - before;
+  }
+
+  println!(
+    "{:<14} {:>8.2} Mrays/s  {:>6.3} allocations/ray  (checksum {:.3})",
+    name,
+    rays.len() as f64 / fastest.as_secs_f64() / 1.0e6,
+    allocations as f64 / rays.len() as f64,
+    checksum / (RUNS * rays.len()) as f64
+  );
+  allocations
+}
+
+fn main() -> ExitCode {
+  seed_random(0, 0);
+  let (world, lights) = scene();
+  let rays = rays();
+  let background = Background::sky();
+
+  let mut allocations = 0;
+
+  allocations += measure("hit", &rays, |ray| {
+    let mut record = HitRecord::new();
+    if world.hit(ray, 0.001, f64::INFINITY, &mut record) {
+      record.t
+    } else {
+      0.0
+    }
+  });
+
+  allocations += measure("hit + scatter", &rays, |ray| {
+    let mut record = HitRecord::new();
+    if !world.hit(ray, 0.001, f64::INFINITY, &mut record) {
+      return 0.0;
+    }
+    match record.material.scatter(ray, &record) {
+      Some(scatter) => scatter.attenuation.x(),
+      None => 0.0,
+    }
+  });
+
+  let path_tracer = PathTracer::new();
+  allocations += measure("path", &rays, |ray| {
+    path_tracer.ray_color(ray, &world, &background, 8).x()
+  });
+
+  let mis_path_tracer = MisPathTracer::new(lights);
+  allocations += measure("mis path", &rays, |ray| {
+    mis_path_tracer.ray_color(ray, &world, &background, 8).x()
+  });
+
+  if allocations > 0 {
+    eprintln!("error: Expected no allocations while tracing, but counted {}", allocations);
+    return ExitCode::FAILURE;
+  }
+  ExitCode::SUCCESS
+}
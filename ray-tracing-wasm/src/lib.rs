@@ -1,3 +1,5 @@
+use std::sync::Arc;
+
 use wasm_bindgen::prelude::*;
 
 // When the `wee_alloc` feature is enabled, use `wee_alloc` as the global
@@ -28,10 +30,12 @@ extern "C" {
 extern crate ray_tracing;
 
 use ray_tracing::camera::ExposureCamera;
-use ray_tracing::hittable::{HitRecord, Hittable};
+use ray_tracing::denoise::{Denoiser, FeatureBuffers};
+use ray_tracing::hittable::Hittable;
 use ray_tracing::hittable_list::HittableList;
 use ray_tracing::material::{Dielectric, Lambertian, Material, Metal};
-use ray_tracing::ray::Ray;
+use ray_tracing::progressive::ProgressiveRenderer;
+use ray_tracing::renderer::Renderer;
 use ray_tracing::sphere::{MovingSphere, Sphere};
 use ray_tracing::utils::random;
 use ray_tracing::utils::random_in;
@@ -42,37 +46,6 @@ pub fn test_random() -> f64 {
     random()
 }
 
-fn ray_color<W>(ray: &Ray, world: &W, depth: usize) -> Vec3
-where
-    W: Hittable,
-{
-    if depth <= 0 {
-        return Vec3::fill(0.0);
-    }
-
-    let mut record = HitRecord::new();
-
-    if world.hit(&ray, 0.001, f64::INFINITY, &mut record) {
-        let mut scattered = Ray::new(Vec3::fill(0.0), Vec3::fill(0.0));
-        let mut attenuation = Vec3::fill(0.0);
-        let mut material = record.material.box_clone();
-
-        if material.scatter(&ray, &mut record, &mut attenuation, &mut scattered) {
-            return attenuation * ray_color(&scattered, world, depth - 1);
-        } else {
-            return Vec3::fill(0.0);
-        }
-    }
-
-    let unit_direction = ray.direction.unit_vector();
-    let t = 0.5 * (unit_direction.y() + 1.0);
-
-    let from = Vec3(1.0, 1.0, 1.0);
-    let to = Vec3(0.5, 0.7, 1.0);
-
-    Vec3::lerp(t, from, to)
-}
-
 fn random_scene(small_sphere_counts: i32) -> HittableList {
     let count = ((small_sphere_counts as f64).sqrt() / 2.0) as i32;
     let start = -count;
@@ -80,9 +53,9 @@ fn random_scene(small_sphere_counts: i32) -> HittableList {
     let mut world = HittableList::new();
     let mut ground = Sphere::new(Vec3(0.0, -1000.0, 0.0), 1000.0);
     let ground_material = Lambertian::new(Vec3(0.5, 0.5, 0.5));
-    ground.set_material(Box::new(ground_material));
+    ground.set_material(Arc::new(ground_material));
     world.add(Box::new(ground));
-    let create_sphere = |center: Vec3, material: Box<dyn Material>| -> Box<dyn Hittable + Send> {
+    let create_sphere = |center: Vec3, material: Arc<dyn Material>| -> Box<dyn Hittable + Send> {
         if random() < 0.7 {
             let mut sphere = Sphere::new(center, 0.2);
             sphere.set_material(material);
@@ -112,19 +85,19 @@ fn random_scene(small_sphere_counts: i32) -> HittableList {
                     // diffuse
                     let albedo = Vec3::random() * Vec3::random();
                     let material = Lambertian::new(albedo);
-                    let sphere = create_sphere(center, Box::new(material));
+                    let sphere = create_sphere(center, Arc::new(material));
                     world.add(sphere);
                 } else if choose_material < 0.95 {
                     // metal
                     let albedo = Vec3::random();
                     let fuzz = random_in(0.0, 0.5);
                     let material = Metal::new(albedo).set_fuzz(fuzz);
-                    let sphere = create_sphere(center, Box::new(material));
+                    let sphere = create_sphere(center, Arc::new(material));
                     world.add(sphere);
                 } else {
                     // glass
                     let material = Dielectric::new(1.5);
-                    let sphere = create_sphere(center, Box::new(material));
+                    let sphere = create_sphere(center, Arc::new(material));
                     world.add(sphere);
                 }
             }
@@ -132,26 +105,27 @@ fn random_scene(small_sphere_counts: i32) -> HittableList {
     }
     let material = Dielectric::new(1.5);
     let mut sphere = Sphere::new(Vec3(0.0, 1.0, 0.0), 1.0);
-    sphere.set_material(Box::new(material));
+    sphere.set_material(Arc::new(material));
     world.add(Box::new(sphere));
     let material = Lambertian::new(Vec3(0.4, 0.2, 0.1));
     let mut sphere = Sphere::new(Vec3(-4.0, 1.0, 0.0), 1.0);
-    sphere.set_material(Box::new(material));
+    sphere.set_material(Arc::new(material));
     world.add(Box::new(sphere));
     let material = Metal::new(Vec3(0.7, 0.6, 0.5)).set_fuzz(0.0);
     let mut sphere = Sphere::new(Vec3(4.0, 1.0, 0.0), 1.0);
-    sphere.set_material(Box::new(material));
+    sphere.set_material(Arc::new(material));
     world.add(Box::new(sphere));
     world
 }
 
 #[wasm_bindgen]
 pub struct Scene {
-    image_width: usize,
-    image_height: usize,
-    max_depth: usize,
+    progressive: ProgressiveRenderer,
     camera: ExposureCamera,
     world: HittableList,
+    denoise: bool,
+    // rendered with the first denoised pass after every reset
+    features: Option<FeatureBuffers>,
 }
 
 #[wasm_bindgen]
@@ -185,70 +159,83 @@ impl Scene {
 
         let world = HittableList::new();
 
+        let mut renderer = Renderer::new(image_width, image_height);
+        renderer.set_max_depth(max_depth as u64);
+
         Scene {
-            image_width,
-            image_height,
-            max_depth,
+            progressive: ProgressiveRenderer::new(renderer),
             camera,
             world,
+            denoise: false,
+            features: None,
         }
     }
 
     pub fn process_keyboard(&mut self, direction: u8) {
         self.camera.process_keyboard(direction);
+        self.progressive.reset();
+        self.features = None;
     }
 
     pub fn random_scene(&mut self, small_sphere_counts: i32) {
         self.world = random_scene(small_sphere_counts);
+        self.progressive.reset();
+        self.features = None;
     }
 
     pub fn render_by_position(&self, x: usize, y: usize) -> Vec<f64> {
-        if x > self.image_width - 1 {
+        let renderer = self.progressive.renderer();
+        let image_width = renderer.image_width;
+        let image_height = renderer.image_height;
+
+        if x > image_width - 1 {
             panic!(
                 "x = {} is out of scope, max number is {}",
                 x,
-                self.image_width - 1
+                image_width - 1
             )
         }
 
-        if y > self.image_height - 1 {
+        if y > image_height - 1 {
             panic!(
                 "y = {} is out of scope, max number is {}",
                 y,
-                self.image_height - 1
+                image_height - 1
             )
         }
 
-        let image_width = self.image_width;
-        let image_height = self.image_height;
-        let camera = &self.camera;
-        let world = &self.world;
-        let max_depth = self.max_depth;
-
-        let x = x as f64;
-        let y = y as f64;
-        let w = image_width as f64;
-        let h = image_height as f64;
-        let u = (x + random()) / (w - 1.0);
-        let v = (y + random()) / (h - 1.0);
-        let ray = camera.get_ray(u, v);
-        let color = ray_color(&ray, world, max_depth);
+        let color = renderer.sample_pixel(&self.world, &self.camera, x, y);
 
         vec![color.r(), color.g(), color.b()]
     }
 
-    pub fn render(&self) -> Vec<f64> {
-        let image_width = self.image_width;
-        let image_height = self.image_height;
-        let mut pixels = vec![];
+    pub fn set_denoise(&mut self, denoise: bool) {
+        self.denoise = denoise;
+    }
 
-        for j in (0..image_height).rev() {
-            for i in 0..image_width {
-                let mut rgb = self.render_by_position(i, j);
-                pixels.append(&mut rgb);
-            }
+    // renders one more pass and returns the average of all passes since the last reset,
+    // denoised when set_denoise asks for it
+    pub fn render(&mut self) -> Vec<f64> {
+        self.progressive.pass(&self.world, &self.camera);
+        let mut framebuffer = self.progressive.image();
+
+        if self.denoise {
+            let renderer = self.progressive.renderer();
+            let (world, camera) = (&self.world, &self.camera);
+            let features = self
+                .features
+                .get_or_insert_with(|| renderer.render_features(world, camera));
+            framebuffer = Denoiser::new().denoise(&framebuffer, features);
         }
 
-        pixels
+        framebuffer
+            .pixels
+            .iter()
+            .flat_map(|color| vec![color.r(), color.g(), color.b()])
+            .collect()
+    }
+
+    pub fn passes(&self) -> usize {
+        self.progressive.passes()
     }
 }
@@ -29,6 +29,8 @@ export default React.lazy(async () => {
     let [time, setTime] = useState(0);
     let ref = useRef<HTMLCanvasElement | null>(null);
     let deubgRef = useRef<HTMLCanvasElement | null>(null);
+    let sceneRef = useRef<InstanceType<typeof Scene> | null>(null);
+    let [denoise, setDenoise] = useState(false);
     let width = 200;
     let height = 100;
 
@@ -45,10 +47,8 @@ export default React.lazy(async () => {
 
       let imageData = ctx.createImageData(width, height);
       let ray = new Scene(width, height, 50);
-      let data = new Float32Array(width * height * 4);
-      let prevImageData = new Float32Array(width * height * 4);
-      let currImageData = new Float32Array(width * height * 4);
-      let renderCount = new Float32Array(width * height * 4);
+      ray.set_denoise(denoise);
+      sceneRef.current = ray;
       let innerCount = 1;
       let innerTime = 0;
       let tid: any = null;
@@ -59,10 +59,6 @@ export default React.lazy(async () => {
       let reset = () => {
         if (!ctx) return;
         imageData = ctx.createImageData(width, height);
-        data = new Float32Array(width * height * 4);
-        prevImageData = new Float32Array(width * height * 4);
-        currImageData = new Float32Array(width * height * 4);
-        renderCount = new Float32Array(width * height * 4);
         innerCount += 1;
         innerTime = 0;
         tid = null;
@@ -83,45 +79,13 @@ export default React.lazy(async () => {
         let duration = 0;
         let list = ray.render();
 
+        // the scene keeps the running average, every call adds one pass
         for (let i = 0; i < list.length; ) {
           let offset = (i / 3) * 4;
-          let r = list[i++];
-          let g = list[i++];
-          let b = list[i++];
-          let a = 1;
-
-          data[offset + 0] += r;
-          data[offset + 1] += g;
-          data[offset + 2] += b;
-          data[offset + 3] += a;
-
-          renderCount[offset + 0] += 1;
-          renderCount[offset + 1] += 1;
-          renderCount[offset + 2] += 1;
-          renderCount[offset + 3] += 1;
-
-          prevImageData[offset + 0] = imageData.data[offset + 0];
-          prevImageData[offset + 1] = imageData.data[offset + 1];
-          prevImageData[offset + 2] = imageData.data[offset + 2];
-          prevImageData[offset + 3] = imageData.data[offset + 3];
-
-          imageData.data[offset + 0] = toColor(
-            data[offset + 0] / renderCount[offset + 0]
-          );
-          imageData.data[offset + 1] = toColor(
-            data[offset + 1] / renderCount[offset + 1]
-          );
-          imageData.data[offset + 2] = toColor(
-            data[offset + 2] / renderCount[offset + 2]
-          );
-          imageData.data[offset + 3] = toColor(
-            data[offset + 3] / renderCount[offset + 3]
-          );
-
-          currImageData[offset + 0] = imageData.data[offset + 0];
-          currImageData[offset + 1] = imageData.data[offset + 1];
-          currImageData[offset + 2] = imageData.data[offset + 2];
-          currImageData[offset + 3] = imageData.data[offset + 3];
+          imageData.data[offset + 0] = toColor(list[i++]);
+          imageData.data[offset + 1] = toColor(list[i++]);
+          imageData.data[offset + 2] = toColor(list[i++]);
+          imageData.data[offset + 3] = 255;
         }
 
         if (over) return
@@ -183,6 +147,17 @@ export default React.lazy(async () => {
         <h2>Ray tracing via Rust</h2>
         <h3>Time: {(time / 1000).toFixed(2)}s</h3>
         <div>Press arrow keys or w/s/a/d to change the viewpoint</div>
+        <label>
+          <input
+            type="checkbox"
+            checked={denoise}
+            onChange={(event) => {
+              setDenoise(event.target.checked);
+              sceneRef.current?.set_denoise(event.target.checked);
+            }}
+          />
+          Denoise
+        </label>
         <div
           style={{
             height: 400,
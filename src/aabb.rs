@@ -0,0 +1,99 @@
+use crate::ray::Ray;
+use crate::vec3::Vec3;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Aabb {
+  pub minimum: Vec3,
+  pub maximum: Vec3,
+}
+
+impl Aabb {
+  pub fn new(minimum: Vec3, maximum: Vec3) -> Aabb {
+    Aabb { minimum, maximum }
+  }
+
+  // an inverted box which surrounds nothing, so any union with it yields the other box
+  pub fn empty() -> Aabb {
+    Aabb {
+      minimum: Vec3::fill(f64::INFINITY),
+      maximum: Vec3::fill(f64::NEG_INFINITY),
+    }
+  }
+
+  pub fn surrounding_box(box0: Aabb, box1: Aabb) -> Aabb {
+    let small = Vec3(
+      box0.minimum.x().min(box1.minimum.x()),
+      box0.minimum.y().min(box1.minimum.y()),
+      box0.minimum.z().min(box1.minimum.z()),
+    );
+    let big = Vec3(
+      box0.maximum.x().max(box1.maximum.x()),
+      box0.maximum.y().max(box1.maximum.y()),
+      box0.maximum.z().max(box1.maximum.z()),
+    );
+    Aabb::new(small, big)
+  }
+
+  // widens any axis thinner than delta, flat primitives would otherwise have boxes no ray can hit
+  pub fn padded(&self, delta: f64) -> Aabb {
+    let mut minimum = self.minimum;
+    let mut maximum = self.maximum;
+
+    for axis in 0..3 {
+      if maximum[axis] - minimum[axis] < delta {
+        let center = 0.5 * (minimum[axis] + maximum[axis]);
+        minimum[axis] = center - 0.5 * delta;
+        maximum[axis] = center + 0.5 * delta;
+      }
+    }
+
+    Aabb::new(minimum, maximum)
+  }
+
+  pub fn centroid(&self) -> Vec3 {
+    0.5 * (self.minimum + self.maximum)
+  }
+
+  pub fn surface_area(&self) -> f64 {
+    let Vec3(dx, dy, dz) = self.maximum - self.minimum;
+    if dx < 0.0 || dy < 0.0 || dz < 0.0 {
+      return 0.0;
+    }
+    2.0 * (dx * dy + dy * dz + dz * dx)
+  }
+
+  pub fn longest_axis(&self) -> usize {
+    let Vec3(dx, dy, dz) = self.maximum - self.minimum;
+    if dx > dy && dx > dz {
+      0
+    } else if dy > dz {
+      1
+    } else {
+      2
+    }
+  }
+
+  pub fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> bool {
+    let mut t_min = t_min;
+    let mut t_max = t_max;
+
+    for axis in 0..3 {
+      let inv_d = 1.0 / ray.direction[axis];
+      let mut t0 = (self.minimum[axis] - ray.origin[axis]) * inv_d;
+      let mut t1 = (self.maximum[axis] - ray.origin[axis]) * inv_d;
+
+      if inv_d < 0.0 {
+        std::mem::swap(&mut t0, &mut t1);
+      }
+
+      t_min = if t0 > t_min { t0 } else { t_min };
+      t_max = if t1 < t_max { t1 } else { t_max };
+
+      if t_max <= t_min {
+        return false;
+      }
+    }
+
+    true
+  }
+}
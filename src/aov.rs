@@ -0,0 +1,197 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::denoise::FeatureBuffers;
+use crate::framebuffer::Framebuffer;
+use crate::vec3::Vec3;
+
+// the auxiliary buffers (AOVs) of what the camera sees first in every pixel, row by row from
+// the top like a framebuffer, for compositing and as denoiser inputs
+#[derive(Debug, Clone)]
+pub struct Aovs {
+  pub width: usize,
+  pub height: usize,
+  // the albedo of the first hit, the background where the camera sees none
+  pub albedo: Framebuffer,
+  // the unit shading normal of the first hit, facing the camera
+  pub normal: Framebuffer,
+  // the ray parameter t of the first hit, infinite where every sample missed
+  pub depth: Vec<f64>,
+  // the world position of the first hit
+  pub position: Framebuffer,
+  // numbered from 1 in the order they show up from the top-left pixel, 0 for the background.
+  // they come from the middle of the pixel since ids cannot be averaged
+  pub material_id: Vec<usize>,
+  pub object_id: Vec<usize>,
+}
+
+impl Aovs {
+  pub fn new(width: usize, height: usize) -> Aovs {
+    Aovs {
+      width,
+      height,
+      albedo: Framebuffer::new(width, height),
+      normal: Framebuffer::new(width, height),
+      depth: vec![f64::INFINITY; width * height],
+      position: Framebuffer::new(width, height),
+      material_id: vec![0; width * height],
+      object_id: vec![0; width * height],
+    }
+  }
+
+  // every buffer as an image named after it, depth and ids fill all three channels
+  pub fn layers(&self) -> Vec<(&'static str, Framebuffer)> {
+    let gray = |values: Vec<f64>| {
+      let mut framebuffer = Framebuffer::new(self.width, self.height);
+      for (pixel, value) in framebuffer.pixels.iter_mut().zip(values) {
+        *pixel = Vec3::fill(value);
+      }
+      framebuffer
+    };
+
+    vec![
+      ("albedo", self.albedo.clone()),
+      ("normal", self.normal.clone()),
+      ("depth", gray(self.depth.clone())),
+      ("position", self.position.clone()),
+      ("material_id", gray(self.material_id.iter().map(|&id| id as f64).collect())),
+      ("object_id", gray(self.object_id.iter().map(|&id| id as f64).collect())),
+    ]
+  }
+
+  // writes every layer to <directory>/<name>.pfm, whose floats keep the values as they are
+  pub fn save<P: AsRef<Path>>(&self, directory: P) -> io::Result<()> {
+    let directory = directory.as_ref();
+    fs::create_dir_all(directory)?;
+    for (name, framebuffer) in self.layers() {
+      framebuffer.save(directory.join(format!("{}.pfm", name)), false)?;
+    }
+    Ok(())
+  }
+
+  // the features Denoiser::denoise takes, from the first hit.
+  // Renderer::render_features looks through mirrors and glass instead
+  pub fn features(&self) -> FeatureBuffers {
+    FeatureBuffers {
+      albedo: self.albedo.clone(),
+      normal: self.normal.clone(),
+      depth: self.depth.clone(),
+    }
+  }
+}
+
+// numbers the addresses from 1 in the order they first show up, keeping 0 for none, so the
+// ids are the same from run to run
+pub(crate) fn number_ids(addresses: &[usize]) -> Vec<usize> {
+  let mut ids: HashMap<usize, usize> = HashMap::new();
+  addresses
+    .iter()
+    .map(|&address| {
+      if address == 0 {
+        return 0;
+      }
+      let next = ids.len() + 1;
+      *ids.entry(address).or_insert(next)
+    })
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+
+  use std::sync::Arc;
+
+  use super::*;
+  use crate::bvh::{BvhNode, SplitHeuristic};
+  use crate::camera::PositionalCamera;
+  use crate::hittable::Hittable;
+  use crate::hittable_list::HittableList;
+  use crate::material::{Lambertian, Material};
+  use crate::renderer::Renderer;
+  use crate::sphere::Sphere;
+
+  // a ground and two spheres sharing one material
+  fn world() -> HittableList {
+    let red: Arc<dyn Material> = Arc::new(Lambertian::new(Vec3(0.7, 0.3, 0.3)));
+    let mut ground = Sphere::new(Vec3(0.0, -100.5, -1.0), 100.0);
+    ground.set_material(Arc::new(Lambertian::new(Vec3(0.5, 0.6, 0.3))));
+    let mut left = Sphere::new(Vec3(-0.6, 0.0, -1.0), 0.5);
+    left.set_material(red.clone());
+    let mut right = Sphere::new(Vec3(0.6, 0.0, -1.0), 0.5);
+    right.set_material(red);
+
+    let mut world = HittableList::new();
+    world.add(Box::new(ground));
+    world.add(Box::new(left));
+    world.add(Box::new(right));
+    world
+  }
+
+  fn render(world: &dyn Hittable, threads: usize) -> Aovs {
+    let camera = PositionalCamera::new(
+      Vec3(0.0, 0.5, 1.0),
+      Vec3(0.0, 0.0, -1.0),
+      Vec3(0.0, 1.0, 0.0),
+      70.0,
+      1.5,
+    );
+    let mut renderer = Renderer::new(36, 24);
+    renderer.set_samples_per_pixel(4);
+    renderer.set_antialias(false);
+    renderer.set_threads(threads);
+    renderer.render_aovs(world, &camera)
+  }
+
+  fn distinct(ids: &[usize]) -> usize {
+    let mut ids = ids.to_vec();
+    ids.sort_unstable();
+    ids.dedup();
+    ids.len()
+  }
+
+  #[test]
+  fn test_number_ids() {
+    assert_eq!(number_ids(&[0, 70, 30, 70, 0, 50]), vec![0, 1, 2, 1, 0, 3]);
+  }
+
+  #[test]
+  fn test_aovs_describe_the_first_hit() {
+    let aovs = render(&world(), 1);
+
+    // the sky, the ground and both spheres, which share a material
+    assert_eq!(distinct(&aovs.object_id), 4);
+    assert_eq!(distinct(&aovs.material_id), 3);
+    assert_eq!(aovs.object_id[0], 0);
+
+    for index in 0..aovs.width * aovs.height {
+      if aovs.object_id[index] == 0 {
+        assert!(aovs.depth[index].is_infinite());
+        continue;
+      }
+      assert!(aovs.depth[index].is_finite());
+      assert!((aovs.normal.pixels[index].length_squared() - 1.0).abs() < 1.0e-9);
+      // every hit lies on the surface of one of the spheres
+      let position = aovs.position.pixels[index];
+      let on_sphere =
+        |center: Vec3, radius: f64| ((position - center).length_squared().sqrt() - radius).abs() < 1.0e-6;
+      assert!(
+        on_sphere(Vec3(0.0, -100.5, -1.0), 100.0)
+          || on_sphere(Vec3(-0.6, 0.0, -1.0), 0.5)
+          || on_sphere(Vec3(0.6, 0.0, -1.0), 0.5)
+      );
+    }
+  }
+
+  #[test]
+  fn test_ids_do_not_depend_on_threads_or_the_bvh() {
+    let aovs = render(&world(), 1);
+    let threaded = render(&world(), 3);
+    let bvh = render(&BvhNode::new(world(), 0.0, 1.0, SplitHeuristic::Sah), 1);
+
+    assert_eq!(aovs.object_id, threaded.object_id);
+    assert_eq!(aovs.object_id, bvh.object_id);
+    assert_eq!(aovs.material_id, bvh.material_id);
+  }
+}
@@ -0,0 +1,52 @@
+use std::sync::Arc;
+
+use crate::environment::EnvironmentMap;
+use crate::ray::Ray;
+use crate::vec3::Vec3;
+
+#[derive(Debug, Clone)]
+pub enum Background {
+  Color(Vec3),
+  // blends from the first color at the bottom to the second color at the top
+  Gradient(Vec3, Vec3),
+  // an image of the surroundings, which lights the scene like any other light
+  Environment(Arc<EnvironmentMap>),
+}
+
+impl Background {
+  pub fn sky() -> Background {
+    Background::Gradient(Vec3(1.0, 1.0, 1.0), Vec3(0.5, 0.7, 1.0))
+  }
+
+  pub fn value(&self, ray: &Ray) -> Vec3 {
+    match self {
+      Background::Color(color) => *color,
+      Background::Gradient(from, to) => {
+        let unit_direction = ray.direction.unit_vector();
+        let t = 0.5 * (unit_direction.y() + 1.0);
+        Vec3::lerp(t, *from, *to)
+      }
+      Background::Environment(environment) => environment.value(ray.direction),
+    }
+  }
+
+  // whether random and pdf_value can be used to send shadow rays toward the background
+  pub fn is_sampled(&self) -> bool {
+    matches!(self, Background::Environment(_))
+  }
+
+  // density over solid angle of random picking direction
+  pub fn pdf_value(&self, direction: Vec3) -> f64 {
+    match self {
+      Background::Environment(environment) => environment.pdf_value(direction),
+      _ => 0.0,
+    }
+  }
+
+  pub fn random(&self) -> Vec3 {
+    match self {
+      Background::Environment(environment) => environment.random(),
+      _ => Vec3(1.0, 0.0, 0.0),
+    }
+  }
+}
@@ -0,0 +1,102 @@
+use std::sync::Arc;
+
+use crate::aabb::Aabb;
+use crate::hittable::{HitRecord, Hittable};
+use crate::material::Material;
+use crate::quad::Quad;
+use crate::ray::Ray;
+use crate::vec3::Vec3;
+
+// an axis aligned box made of six quads facing outwards
+#[derive(Debug)]
+pub struct BoxShape {
+  pub minimum: Vec3,
+  pub maximum: Vec3,
+  sides: Vec<Quad>,
+}
+
+impl BoxShape {
+  // any two opposite corners
+  pub fn new(a: Vec3, b: Vec3) -> BoxShape {
+    let minimum = Vec3(a.x().min(b.x()), a.y().min(b.y()), a.z().min(b.z()));
+    let maximum = Vec3(a.x().max(b.x()), a.y().max(b.y()), a.z().max(b.z()));
+
+    let dx = Vec3(maximum.x() - minimum.x(), 0.0, 0.0);
+    let dy = Vec3(0.0, maximum.y() - minimum.y(), 0.0);
+    let dz = Vec3(0.0, 0.0, maximum.z() - minimum.z());
+
+    let Vec3(x0, y0, z0) = minimum;
+    let Vec3(x1, y1, z1) = maximum;
+
+    // front, right, back, left, top, bottom
+    let sides = vec![
+      Quad::new(Vec3(x0, y0, z1), dx, dy),
+      Quad::new(Vec3(x1, y0, z1), -dz, dy),
+      Quad::new(Vec3(x1, y0, z0), -dx, dy),
+      Quad::new(Vec3(x0, y0, z0), dz, dy),
+      Quad::new(Vec3(x0, y1, z1), dx, -dz),
+      Quad::new(Vec3(x0, y0, z0), dx, dz),
+    ];
+
+    BoxShape {
+      minimum,
+      maximum,
+      sides,
+    }
+  }
+
+  pub fn set_material(&mut self, material: Arc<dyn Material>) {
+    for side in self.sides.iter_mut() {
+      side.set_material(Arc::clone(&material));
+    }
+  }
+}
+
+impl Hittable for BoxShape {
+  fn hit<'a>(&'a self, ray: &Ray, t_min: f64, t_max: f64, record: &mut HitRecord<'a>) -> bool {
+    let mut hit_anything = false;
+    let mut closest_so_far = t_max;
+
+    for side in self.sides.iter() {
+      if side.hit(ray, t_min, closest_so_far, record) {
+        hit_anything = true;
+        closest_so_far = record.t;
+      }
+    }
+
+    hit_anything
+  }
+
+  fn bounding_box(&self, _time0: f64, _time1: f64, output_box: &mut Aabb) -> bool {
+    *output_box = Aabb::new(self.minimum, self.maximum);
+    true
+  }
+}
+
+#[cfg(test)]
+mod tests {
+
+  use super::*;
+
+  #[test]
+  fn test_box_normals_point_outwards() {
+    let shape = BoxShape::new(Vec3(1.0, 1.0, 1.0), Vec3(-1.0, -1.0, -1.0));
+    let mut record = HitRecord::new();
+
+    let axes = [
+      Vec3(1.0, 0.0, 0.0),
+      Vec3(0.0, 1.0, 0.0),
+      Vec3(0.0, 0.0, 1.0),
+    ];
+    for axis in axes.iter() {
+      for sign in [-1.0, 1.0] {
+        let outside = 3.0 * sign * *axis + Vec3(0.1, 0.2, 0.3);
+        let ray = Ray::new(outside, -sign * *axis);
+        assert!(shape.hit(&ray, 0.001, f64::INFINITY, &mut record));
+        assert!((record.point.dot(*axis) - sign).abs() < 1.0e-9);
+        assert!(record.front_face);
+        assert!((record.normal - sign * *axis).len() < 1.0e-9);
+      }
+    }
+  }
+}
@@ -0,0 +1,339 @@
+use crate::aabb::Aabb;
+use crate::hittable::{hit_object, HitRecord, Hittable};
+use crate::hittable_list::HittableList;
+use crate::ray::Ray;
+
+// number of centroid buckets evaluated by the surface area heuristic
+const SAH_BUCKETS: usize = 12;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SplitHeuristic {
+  // split at the middle of the centroid bounds along the longest axis
+  Midpoint,
+  // split where the surface area heuristic estimates the cheapest traversal
+  Sah,
+}
+
+pub struct BvhNode {
+  // none only for a BVH without bounded objects
+  left: Option<Box<dyn Hittable>>,
+  right: Option<Box<dyn Hittable>>,
+  bounding_box: Aabb,
+  // objects without a bounding box, like planes, tested on every ray next to the tree
+  unbounded: Vec<Box<dyn Hittable>>,
+}
+
+struct Primitive {
+  object: Box<dyn Hittable>,
+  bounding_box: Aabb,
+  centroid_box: Aabb,
+}
+
+impl BvhNode {
+  // an empty list gives a BVH that nothing hits
+  pub fn new(list: HittableList, time0: f64, time1: f64, heuristic: SplitHeuristic) -> BvhNode {
+    let mut primitives = vec![];
+    let mut unbounded = vec![];
+    for object in list.objects {
+      let mut bounding_box = Aabb::empty();
+      if !object.bounding_box(time0, time1, &mut bounding_box) {
+        unbounded.push(object);
+        continue;
+      }
+      let centroid = bounding_box.centroid();
+      primitives.push(Primitive {
+        object,
+        bounding_box,
+        centroid_box: Aabb::new(centroid, centroid),
+      });
+    }
+
+    if primitives.is_empty() {
+      return BvhNode {
+        left: None,
+        right: None,
+        bounding_box: Aabb::empty(),
+        unbounded,
+      };
+    }
+
+    let mut root = BvhNode::build(primitives, heuristic);
+    root.unbounded = unbounded;
+    root
+  }
+
+  fn build(mut primitives: Vec<Primitive>, heuristic: SplitHeuristic) -> BvhNode {
+    let bounding_box = primitives
+      .iter()
+      .fold(Aabb::empty(), |acc, p| Aabb::surrounding_box(acc, p.bounding_box));
+
+    if primitives.len() == 1 {
+      let left = primitives.pop().unwrap().object;
+      return BvhNode {
+        left: Some(left),
+        right: None,
+        bounding_box,
+        unbounded: vec![],
+      };
+    }
+
+    if primitives.len() == 2 {
+      let right = primitives.pop().unwrap().object;
+      let left = primitives.pop().unwrap().object;
+      return BvhNode {
+        left: Some(left),
+        right: Some(right),
+        bounding_box,
+        unbounded: vec![],
+      };
+    }
+
+    let centroid_bounds = primitives
+      .iter()
+      .fold(Aabb::empty(), |acc, p| Aabb::surrounding_box(acc, p.centroid_box));
+    let axis = centroid_bounds.longest_axis();
+
+    let mid = match heuristic {
+      SplitHeuristic::Midpoint => partition_midpoint(&mut primitives, &centroid_bounds, axis),
+      SplitHeuristic::Sah => partition_sah(&mut primitives, &centroid_bounds, axis),
+    };
+
+    // fall back to an even split when every centroid lands on one side
+    let mid = if mid == 0 || mid == primitives.len() {
+      partition_median(&mut primitives, axis)
+    } else {
+      mid
+    };
+
+    let right = primitives.split_off(mid);
+    let left = primitives;
+
+    BvhNode {
+      left: Some(Box::new(BvhNode::build(left, heuristic))),
+      right: Some(Box::new(BvhNode::build(right, heuristic))),
+      bounding_box,
+      unbounded: vec![],
+    }
+  }
+}
+
+fn centroid(primitive: &Primitive, axis: usize) -> f64 {
+  primitive.centroid_box.minimum[axis]
+}
+
+fn partition_by<F>(primitives: &mut [Primitive], goes_left: F) -> usize
+where
+  F: Fn(&Primitive) -> bool,
+{
+  let mut mid = 0;
+  for i in 0..primitives.len() {
+    if goes_left(&primitives[i]) {
+      primitives.swap(i, mid);
+      mid += 1;
+    }
+  }
+  mid
+}
+
+fn partition_median(primitives: &mut [Primitive], axis: usize) -> usize {
+  primitives.sort_by(|a, b| {
+    centroid(a, axis)
+      .partial_cmp(&centroid(b, axis))
+      .expect("Expected bounding boxes without NaN")
+  });
+  primitives.len() / 2
+}
+
+fn partition_midpoint(primitives: &mut [Primitive], centroid_bounds: &Aabb, axis: usize) -> usize {
+  let split = 0.5 * (centroid_bounds.minimum[axis] + centroid_bounds.maximum[axis]);
+  partition_by(primitives, |p| centroid(p, axis) < split)
+}
+
+fn partition_sah(primitives: &mut [Primitive], centroid_bounds: &Aabb, axis: usize) -> usize {
+  let min = centroid_bounds.minimum[axis];
+  let extent = centroid_bounds.maximum[axis] - min;
+
+  if extent <= 0.0 {
+    return 0;
+  }
+
+  let bucket_of = |p: &Primitive| -> usize {
+    let b = (SAH_BUCKETS as f64 * (centroid(p, axis) - min) / extent) as usize;
+    b.min(SAH_BUCKETS - 1)
+  };
+
+  let mut counts = [0usize; SAH_BUCKETS];
+  let mut boxes = [Aabb::empty(); SAH_BUCKETS];
+
+  for p in primitives.iter() {
+    let b = bucket_of(p);
+    counts[b] += 1;
+    boxes[b] = Aabb::surrounding_box(boxes[b], p.bounding_box);
+  }
+
+  // cost of splitting after bucket i, relative to the parent's surface area
+  let mut best_cost = f64::INFINITY;
+  let mut best_bucket = 0;
+
+  for i in 0..SAH_BUCKETS - 1 {
+    let (left_count, left_box) = (0..=i).fold((0, Aabb::empty()), |(n, b), j| {
+      (n + counts[j], Aabb::surrounding_box(b, boxes[j]))
+    });
+    let (right_count, right_box) = (i + 1..SAH_BUCKETS).fold((0, Aabb::empty()), |(n, b), j| {
+      (n + counts[j], Aabb::surrounding_box(b, boxes[j]))
+    });
+
+    if left_count == 0 || right_count == 0 {
+      continue;
+    }
+
+    let cost = left_count as f64 * left_box.surface_area() + right_count as f64 * right_box.surface_area();
+
+    if cost < best_cost {
+      best_cost = cost;
+      best_bucket = i;
+    }
+  }
+
+  partition_by(primitives, |p| bucket_of(p) <= best_bucket)
+}
+
+impl Hittable for BvhNode {
+  fn hit<'a>(&'a self, ray: &Ray, t_min: f64, t_max: f64, record: &mut HitRecord<'a>) -> bool {
+    let mut hit_anything = false;
+    let mut t_max = t_max;
+    for object in &self.unbounded {
+      if hit_object(object.as_ref(), ray, t_min, t_max, record) {
+        hit_anything = true;
+        t_max = record.t;
+      }
+    }
+
+    let left = match &self.left {
+      Some(left) if self.bounding_box.hit(ray, t_min, t_max) => left,
+      _ => return hit_anything,
+    };
+
+    let hit_left = hit_object(left.as_ref(), ray, t_min, t_max, record);
+    let t_max = if hit_left { record.t } else { t_max };
+    let hit_right = match &self.right {
+      Some(right) => hit_object(right.as_ref(), ray, t_min, t_max, record),
+      None => false,
+    };
+
+    hit_anything || hit_left || hit_right
+  }
+
+  // false when the BVH is empty or holds unbounded objects
+  fn bounding_box(&self, _time0: f64, _time1: f64, output_box: &mut Aabb) -> bool {
+    if self.left.is_none() || !self.unbounded.is_empty() {
+      return false;
+    }
+    *output_box = self.bounding_box;
+    true
+  }
+}
+
+#[cfg(test)]
+mod tests {
+
+  use super::*;
+  use crate::plane::Plane;
+  use crate::sphere::Sphere;
+  use crate::utils::random_in;
+  use crate::vec3::Vec3;
+
+  fn random_spheres(count: usize) -> HittableList {
+    let mut list = HittableList::new();
+    for _ in 0..count {
+      let center = Vec3::random_in(-10.0, 10.0);
+      list.add(Box::new(Sphere::new(center, random_in(0.1, 1.0))));
+    }
+    list
+  }
+
+  fn assert_same_hits(list: &HittableList, bvh: &BvhNode) {
+    for _ in 0..2000 {
+      let origin = Vec3::random_in(-15.0, 15.0);
+      let direction = Vec3::random_unit_vector();
+      let ray = Ray::new(origin, direction);
+
+      let mut expected = HitRecord::new();
+      let mut actual = HitRecord::new();
+      let list_hit = list.hit(&ray, 0.001, f64::INFINITY, &mut expected);
+      let bvh_hit = bvh.hit(&ray, 0.001, f64::INFINITY, &mut actual);
+
+      assert_eq!(list_hit, bvh_hit);
+      if list_hit {
+        assert!((expected.t - actual.t).abs() < 1.0e-9);
+      }
+    }
+  }
+
+  fn sphere_centers(list: &HittableList) -> Vec<(Vec3, f64)> {
+    list
+      .objects
+      .iter()
+      .map(|object| {
+        let mut bounding_box = Aabb::empty();
+        assert!(object.bounding_box(0.0, 1.0, &mut bounding_box));
+        let radius = 0.5 * (bounding_box.maximum.x() - bounding_box.minimum.x());
+        (bounding_box.centroid(), radius)
+      })
+      .collect()
+  }
+
+  fn clone_spheres(list: &HittableList, centers: &[(Vec3, f64)]) -> HittableList {
+    let mut cloned = HittableList::new();
+    for (center, radius) in centers {
+      cloned.add(Box::new(Sphere::new(*center, *radius)));
+    }
+    assert_eq!(cloned.objects.len(), list.objects.len());
+    cloned
+  }
+
+  #[test]
+  fn test_bvh_matches_linear_list() {
+    for heuristic in [SplitHeuristic::Midpoint, SplitHeuristic::Sah].iter() {
+      let list = random_spheres(200);
+      let bvh = BvhNode::new(clone_spheres(&list, &sphere_centers(&list)), 0.0, 1.0, *heuristic);
+
+      assert_same_hits(&list, &bvh);
+    }
+  }
+
+  #[test]
+  fn test_bvh_keeps_unbounded_objects() {
+    let mut linear = random_spheres(20);
+    let mut list = clone_spheres(&linear, &sphere_centers(&linear));
+    let floor = || Box::new(Plane::new(Vec3(0.0, -12.0, 0.0), Vec3(0.0, 1.0, 0.0)));
+    linear.add(floor());
+    list.add(floor());
+
+    let bvh = BvhNode::new(list, 0.0, 1.0, SplitHeuristic::Sah);
+    assert!(!bvh.bounding_box(0.0, 1.0, &mut Aabb::empty()));
+    assert_same_hits(&linear, &bvh);
+  }
+
+  #[test]
+  fn test_empty_bvh_hits_nothing() {
+    let bvh = BvhNode::new(HittableList::new(), 0.0, 1.0, SplitHeuristic::Sah);
+    let ray = Ray::new(Vec3::fill(0.0), Vec3(0.0, 0.0, -1.0));
+    assert!(!bvh.hit(&ray, 0.001, f64::INFINITY, &mut HitRecord::new()));
+    assert!(!bvh.bounding_box(0.0, 1.0, &mut Aabb::empty()));
+  }
+
+  #[test]
+  fn test_bvh_bounding_box_encloses_objects() {
+    let list = random_spheres(50);
+    let mut expected = Aabb::empty();
+    assert!(list.bounding_box(0.0, 1.0, &mut expected));
+
+    let bvh = BvhNode::new(list, 0.0, 1.0, SplitHeuristic::Sah);
+    let mut actual = Aabb::empty();
+    assert!(bvh.bounding_box(0.0, 1.0, &mut actual));
+
+    assert!(expected.minimum.eq(actual.minimum));
+    assert!(expected.maximum.eq(actual.maximum));
+  }
+}
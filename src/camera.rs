@@ -1,26 +1,49 @@
 #![allow(dead_code)]
 use crate::ray::Ray;
-use crate::utils::random;
+use crate::sampler::sample_1d;
 use crate::vec3::Vec3;
 
-pub struct Camera {
+pub trait Camera: Send + Sync {
+  // (s, t) are viewport coordinates in [0, 1], measured from the lower left corner
+  fn get_ray(&self, s: f64, t: f64) -> Ray;
+  fn aspect_ratio(&self) -> f64;
+  fn position(&self) -> Vec3;
+  // the interval the generated rays' time is spread over, [0, 0] for cameras without motion blur
+  fn shutter_interval(&self) -> (f64, f64) {
+    (0.0, 0.0)
+  }
+}
+
+fn viewport_aspect_ratio(horizontal: Vec3, vertical: Vec3) -> f64 {
+  horizontal.len() / vertical.len()
+}
+
+pub struct ViewportCamera {
   pub origin: Vec3,
   pub lower_left_corner: Vec3,
   pub horizontal: Vec3,
   pub vertical: Vec3,
 }
 
-impl Camera {
-  pub fn new(origin: Vec3, lower_left_corner: Vec3, horizontal: Vec3, vertical: Vec3) -> Camera {
-    Camera {
+impl ViewportCamera {
+  pub fn new(
+    origin: Vec3,
+    lower_left_corner: Vec3,
+    horizontal: Vec3,
+    vertical: Vec3,
+  ) -> ViewportCamera {
+    ViewportCamera {
       origin,
       lower_left_corner,
       horizontal,
       vertical,
     }
   }
-  pub fn get_ray(&self, u: f64, v: f64) -> Ray {
-    let Camera {
+}
+
+impl Camera for ViewportCamera {
+  fn get_ray(&self, u: f64, v: f64) -> Ray {
+    let ViewportCamera {
       origin,
       lower_left_corner,
       horizontal,
@@ -31,6 +54,14 @@ impl Camera {
 
     Ray::new(origin, direction)
   }
+
+  fn aspect_ratio(&self) -> f64 {
+    viewport_aspect_ratio(self.horizontal, self.vertical)
+  }
+
+  fn position(&self) -> Vec3 {
+    self.origin
+  }
 }
 
 pub struct PositionalCamera {
@@ -69,8 +100,10 @@ impl PositionalCamera {
       lower_left_corner,
     }
   }
+}
 
-  pub fn get_ray(&self, s: f64, t: f64) -> Ray {
+impl Camera for PositionalCamera {
+  fn get_ray(&self, s: f64, t: f64) -> Ray {
     let PositionalCamera {
       origin,
       lower_left_corner,
@@ -82,6 +115,14 @@ impl PositionalCamera {
 
     Ray::new(origin, direction)
   }
+
+  fn aspect_ratio(&self) -> f64 {
+    viewport_aspect_ratio(self.horizontal, self.vertical)
+  }
+
+  fn position(&self) -> Vec3 {
+    self.origin
+  }
 }
 
 pub struct LensCamera {
@@ -131,8 +172,10 @@ impl LensCamera {
       lens_radius,
     }
   }
+}
 
-  pub fn get_ray(&self, s: f64, t: f64) -> Ray {
+impl Camera for LensCamera {
+  fn get_ray(&self, s: f64, t: f64) -> Ray {
     let LensCamera {
       origin,
       lower_left_corner,
@@ -151,6 +194,14 @@ impl LensCamera {
 
     Ray::new(origin + offset, direction)
   }
+
+  fn aspect_ratio(&self) -> f64 {
+    viewport_aspect_ratio(self.horizontal, self.vertical)
+  }
+
+  fn position(&self) -> Vec3 {
+    self.origin
+  }
 }
 
 pub struct ExposureCamera {
@@ -167,6 +218,7 @@ pub struct ExposureCamera {
 }
 
 impl ExposureCamera {
+  #[allow(clippy::too_many_arguments)]
   pub fn new(
     look_from: Vec3,
     look_at: Vec3,
@@ -202,18 +254,6 @@ impl ExposureCamera {
     }
   }
 
-  pub fn get_ray(&self, s: f64, t: f64) -> Ray {
-    let lens_camera = &self.lens_camera;
-    let exposure_start_time = self.exposure_start_time;
-    let exposure_end_time = self.exposure_end_time;
-
-    let mut ray = lens_camera.get_ray(s, t);
-
-    ray.time = exposure_start_time + random() * (exposure_end_time - exposure_start_time);
-
-    ray
-  }
-
   pub fn process_keyboard(&mut self, direction: u8) {
     let right = self.lens_camera.u;
     let front = (self.look_from - self.look_at).unit_vector();
@@ -259,3 +299,29 @@ impl ExposureCamera {
     );
   }
 }
+
+impl Camera for ExposureCamera {
+  fn get_ray(&self, s: f64, t: f64) -> Ray {
+    let lens_camera = &self.lens_camera;
+    let exposure_start_time = self.exposure_start_time;
+    let exposure_end_time = self.exposure_end_time;
+
+    let mut ray = lens_camera.get_ray(s, t);
+
+    ray.time = exposure_start_time + sample_1d() * (exposure_end_time - exposure_start_time);
+
+    ray
+  }
+
+  fn aspect_ratio(&self) -> f64 {
+    self.aspect_ratio
+  }
+
+  fn position(&self) -> Vec3 {
+    self.look_from
+  }
+
+  fn shutter_interval(&self) -> (f64, f64) {
+    (self.exposure_start_time, self.exposure_end_time)
+  }
+}
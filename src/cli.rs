@@ -0,0 +1,333 @@
+use std::io;
+use std::path::Path;
+
+use ray_tracing::camera::Camera;
+use ray_tracing::denoise::Denoiser;
+use ray_tracing::framebuffer::Framebuffer;
+use ray_tracing::hittable::Hittable;
+use ray_tracing::image::ImageFormat;
+use ray_tracing::renderer::Renderer;
+use ray_tracing::sampler::SamplerKind;
+
+pub static USAGE: &str = "Usage:
+  ray_tracing render <scene> [options]             render a built-in scene, a .scene file or `all`
+  ray_tracing bench <scene> [--runs <n>] [options] time repeated renders of a scene
+  ray_tracing list                                 list the built-in scenes
+  ray_tracing <index> | *                          shorthand for `render <index>` and `render all`
+
+A built-in scene is picked by its index or name, see `ray_tracing list`.
+
+Options:
+  --width <pixels>      image width, the height follows the scene's aspect ratio unless given
+  --height <pixels>     image height
+  --spp <n>             samples per pixel
+  --adaptive <error>    keep sampling noisy pixels until their relative error is below <error>, e.g. 0.02
+  --max-spp <n>         samples per pixel adaptive sampling stops at, defaults to 8 times --spp
+  --heatmap <path>      also write an image of how many samples every pixel took
+  --denoise             filter the noise out guided by the albedo, normals and depth of the scene
+  --aovs <directory>    also write the albedo, normal, depth, position and ids of the first hits as .pfm
+  --max-depth <n>       maximum number of bounces
+  --threads <n>         number of render threads, defaults to one per core
+  --seed <n>            seed for scene generation and sampling, defaults to 0
+  --sampler <sampler>   sample sequence: independent, stratified, halton or sobol, defaults to sobol
+  -o, --output <path>   output file, .ppm, .png, .hdr or .pfm, defaults to the scene's file under dist/
+  --format <format>     output format: ppm, png, png16, hdr or pfm
+  --help                show this message";
+
+// overrides for the settings each scene hard-codes
+#[derive(Debug, Clone, Default)]
+pub struct Options {
+  pub width: Option<usize>,
+  pub height: Option<usize>,
+  pub samples_per_pixel: Option<usize>,
+  pub error_threshold: Option<f64>,
+  pub max_samples_per_pixel: Option<usize>,
+  pub heatmap: Option<String>,
+  pub denoise: bool,
+  pub aovs: Option<String>,
+  pub max_depth: Option<u64>,
+  pub threads: Option<usize>,
+  pub seed: Option<u64>,
+  pub sampler: Option<SamplerKind>,
+  pub output: Option<String>,
+  pub format: Option<ImageFormat>,
+}
+
+impl Options {
+  // a missing side is derived from the given one and the scene's aspect ratio
+  pub fn image_size(&self, width: usize, aspect_ratio: f64) -> (usize, usize) {
+    match (self.width, self.height) {
+      (Some(width), Some(height)) => (width, height),
+      (Some(width), None) => (width, ((width as f64 / aspect_ratio) as usize).max(1)),
+      (None, Some(height)) => (((height as f64 * aspect_ratio) as usize).max(1), height),
+      (None, None) => (width, ((width as f64 / aspect_ratio) as usize).max(1)),
+    }
+  }
+
+  pub fn configure(&self, renderer: &mut Renderer) {
+    if let Some(samples_per_pixel) = self.samples_per_pixel {
+      renderer.set_samples_per_pixel(samples_per_pixel);
+    }
+    if let Some(error_threshold) = self.error_threshold {
+      renderer.set_error_threshold(error_threshold);
+    }
+    if let Some(max_samples_per_pixel) = self.max_samples_per_pixel {
+      renderer.set_max_samples_per_pixel(max_samples_per_pixel);
+    }
+    if let Some(max_depth) = self.max_depth {
+      renderer.set_max_depth(max_depth);
+    }
+    if let Some(threads) = self.threads {
+      renderer.set_threads(threads);
+    }
+    if let Some(seed) = self.seed {
+      renderer.set_seed(seed);
+    }
+    if let Some(sampler) = self.sampler {
+      renderer.set_sampler(sampler.build());
+    }
+  }
+
+  // writes the sample count heatmap too when --heatmap asks for one and the AOVs with --aovs,
+  // and denoises with --denoise
+  pub fn render(&self, renderer: &Renderer, world: &dyn Hittable, camera: &dyn Camera) -> io::Result<Framebuffer> {
+    if let Some(directory) = &self.aovs {
+      renderer.render_aovs(world, camera).save(directory)?;
+    }
+
+    let framebuffer = match &self.heatmap {
+      Some(path) => {
+        let (framebuffer, counts) = renderer.render_with_sample_counts(world, camera);
+        println!("Samples per pixel: {:.1} on average", counts.mean());
+        counts.heatmap().save(path, false)?;
+        framebuffer
+      }
+      None => renderer.render(world, camera),
+    };
+
+    if !self.denoise {
+      return Ok(framebuffer);
+    }
+    let features = renderer.render_features(world, camera);
+    Ok(Denoiser::new().denoise(&framebuffer, &features))
+  }
+
+  pub fn output_path(&self, default: &str) -> String {
+    match (&self.output, &self.format) {
+      (Some(output), _) => output.clone(),
+      (None, Some(format)) => Path::new(default)
+        .with_extension(format.extension())
+        .to_string_lossy()
+        .into_owned(),
+      (None, None) => default.to_string(),
+    }
+  }
+
+  // writes to --output or the scene's default file, in the --format if one was given
+  pub fn save(&self, framebuffer: &Framebuffer, default: &str, gamma_correct: bool) -> io::Result<()> {
+    let path = self.output_path(default);
+    match self.format {
+      Some(format) => framebuffer.save_as(path, format, gamma_correct),
+      None => framebuffer.save(path, gamma_correct),
+    }
+  }
+}
+
+#[derive(Debug)]
+pub enum Command {
+  Render { scene: String, options: Options },
+  Bench { scene: String, runs: usize, options: Options },
+  List,
+  Help,
+}
+
+fn value<'a>(flag: &str, args: &mut impl Iterator<Item = &'a String>) -> Result<&'a String, String> {
+  args.next().ok_or(format!("Expected a value after {}", flag))
+}
+
+fn number<'a, T: std::str::FromStr>(flag: &str, args: &mut impl Iterator<Item = &'a String>) -> Result<T, String> {
+  let text = value(flag, args)?;
+  text
+    .parse::<T>()
+    .map_err(|_| format!("Expected a number after {}, but received {}", flag, text))
+}
+
+fn positive<'a>(flag: &str, args: &mut impl Iterator<Item = &'a String>) -> Result<usize, String> {
+  match number::<usize>(flag, args)? {
+    0 => Err(format!("Expected a positive number after {}, but received 0", flag)),
+    n => Ok(n),
+  }
+}
+
+// parses the arguments after the program name
+pub fn parse(args: &[String]) -> Result<Command, String> {
+  let mut args = args.iter();
+
+  let command = match args.next() {
+    Some(command) => command.as_str(),
+    None => return Err("Expected a command".to_string()),
+  };
+
+  let (command, scene) = match command {
+    "list" => return Ok(Command::List),
+    "help" | "--help" => return Ok(Command::Help),
+    "render" | "bench" => (command, None),
+    // the old `ray_tracing <index>` and `ray_tracing *` invocations
+    "*" => ("render", Some("all".to_string())),
+    index if index.parse::<usize>().is_ok() => ("render", Some(index.to_string())),
+    other => return Err(format!("Unknown command {}", other)),
+  };
+
+  let mut scene = scene;
+  let mut runs = 3;
+  let mut options = Options::default();
+
+  while let Some(arg) = args.next() {
+    match arg.as_str() {
+      "--width" => options.width = Some(positive(arg, &mut args)?),
+      "--height" => options.height = Some(positive(arg, &mut args)?),
+      "--spp" => options.samples_per_pixel = Some(positive(arg, &mut args)?),
+      "--adaptive" => {
+        let error = number::<f64>(arg, &mut args)?;
+        // nan and inf parse as numbers but would never be reached
+        if !error.is_finite() || error <= 0.0 {
+          return Err(format!("Expected a positive error after {}, but received {}", arg, error));
+        }
+        options.error_threshold = Some(error);
+      }
+      "--max-spp" => options.max_samples_per_pixel = Some(positive(arg, &mut args)?),
+      "--heatmap" => options.heatmap = Some(value(arg, &mut args)?.clone()),
+      "--denoise" => options.denoise = true,
+      "--aovs" => options.aovs = Some(value(arg, &mut args)?.clone()),
+      "--max-depth" => options.max_depth = Some(number(arg, &mut args)?),
+      "--threads" => options.threads = Some(positive(arg, &mut args)?),
+      "--seed" => options.seed = Some(number(arg, &mut args)?),
+      "--sampler" => {
+        let name = value(arg, &mut args)?;
+        match SamplerKind::from_name(&name.to_lowercase()) {
+          Some(sampler) => options.sampler = Some(sampler),
+          None => {
+            return Err(format!(
+              "Unknown sampler {}, expected independent, stratified, halton or sobol",
+              name
+            ))
+          }
+        }
+      }
+      "--runs" if command == "bench" => runs = positive(arg, &mut args)?,
+      "-o" | "--output" => options.output = Some(value(arg, &mut args)?.clone()),
+      "--format" => {
+        let name = value(arg, &mut args)?;
+        match ImageFormat::from_name(&name.to_lowercase()) {
+          Some(format) => options.format = Some(format),
+          None => return Err(format!("Unknown format {}, expected ppm, png, png16, hdr or pfm", name)),
+        }
+      }
+      "--help" => return Ok(Command::Help),
+      flag if flag.starts_with('-') => return Err(format!("Unknown option {}", flag)),
+      name if scene.is_none() => scene = Some(name.to_string()),
+      extra => return Err(format!("Unexpected argument {}", extra)),
+    }
+  }
+
+  if let Some(output) = &options.output {
+    let matches = match (ImageFormat::from_path(output), options.format) {
+      (Some(by_extension), Some(format)) => by_extension.extension() == format.extension(),
+      (by_extension, None) => by_extension.is_some(),
+      (None, Some(_)) => false,
+    };
+    if !matches {
+      let expected = match options.format {
+        Some(format) => format!(".{}", format.extension()),
+        None => ".ppm, .png, .hdr or .pfm".to_string(),
+      };
+      return Err(format!("Expected the output {} to end in {}", output, expected));
+    }
+  }
+
+  if let Some(heatmap) = &options.heatmap {
+    if ImageFormat::from_path(heatmap).is_none() {
+      return Err(format!("Expected the heatmap {} to end in .ppm, .png, .hdr or .pfm", heatmap));
+    }
+  }
+
+  let scene = match scene {
+    Some(scene) => scene,
+    None => return Err(format!("Expected a scene to {}", command)),
+  };
+
+  if scene == "all" && (options.output.is_some() || options.heatmap.is_some() || options.aovs.is_some()) {
+    return Err("Expected no --output, --heatmap or --aovs when rendering all scenes".to_string());
+  }
+
+  if command == "bench" {
+    Ok(Command::Bench { scene, runs, options })
+  } else {
+    Ok(Command::Render { scene, options })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+
+  use super::*;
+
+  fn args(line: &str) -> Vec<String> {
+    line.split_whitespace().map(|arg| arg.to_string()).collect()
+  }
+
+  #[test]
+  fn test_parse_render_flags() {
+    match parse(&args("render motion_blur --width 200 --spp 8 --threads 2 --seed 42 --sampler halton -o out.ppm")).unwrap() {
+      Command::Render { scene, options } => {
+        assert_eq!(scene, "motion_blur");
+        assert_eq!(options.width, Some(200));
+        assert_eq!(options.samples_per_pixel, Some(8));
+        assert_eq!(options.threads, Some(2));
+        assert_eq!(options.seed, Some(42));
+        assert_eq!(options.sampler, Some(SamplerKind::Halton));
+        assert_eq!(options.output_path("dist/15.ppm"), "out.ppm");
+        assert_eq!(options.image_size(800, 2.0), (200, 100));
+      }
+      command => panic!("Expected a render command, but received {:?}", command),
+    }
+
+    match parse(&args("15")).unwrap() {
+      Command::Render { scene, .. } => assert_eq!(scene, "15"),
+      command => panic!("Expected a render command, but received {:?}", command),
+    }
+
+    // samples map to pixel centers, so a single pixel row or column renders fine
+    match parse(&args("render 15 --width 1")).unwrap() {
+      Command::Render { options, .. } => assert_eq!(options.image_size(800, 2.0), (1, 1)),
+      command => panic!("Expected a render command, but received {:?}", command),
+    }
+  }
+
+  #[test]
+  fn test_parse_rejects_bad_arguments() {
+    assert!(parse(&args("")).is_err());
+    assert!(parse(&args("draw 1")).is_err());
+    assert!(parse(&args("render")).is_err());
+    assert!(parse(&args("render 1 --spp 0")).is_err());
+    assert!(parse(&args("render 1 --width")).is_err());
+    assert!(parse(&args("render 1 --runs 3")).is_err());
+    assert!(parse(&args("render 1 -o out.txt")).is_err());
+    assert!(parse(&args("render 1 --format hdr -o out.png")).is_err());
+    assert!(parse(&args("render 1 --format png16 -o out.png")).is_ok());
+    assert!(parse(&args("render 1 --format exr")).is_err());
+    assert!(parse(&args("render 1 --sampler random")).is_err());
+    assert!(parse(&args("render all -o out.ppm")).is_err());
+    assert!(parse(&args("render 1 --adaptive 0")).is_err());
+    assert!(parse(&args("render 1 --adaptive nan")).is_err());
+    assert!(parse(&args("render 1 --adaptive inf")).is_err());
+    assert!(parse(&args("render 1 --adaptive 0.02 --max-spp 0")).is_err());
+    assert!(parse(&args("render 1 --heatmap samples.txt")).is_err());
+    assert!(parse(&args("render all --heatmap samples.png")).is_err());
+    assert!(parse(&args("render 1 --adaptive 0.02 --max-spp 64 --heatmap samples.png")).is_ok());
+    assert!(parse(&args("render 1 --spp 4 --denoise")).is_ok());
+    assert!(parse(&args("render all --aovs dist/aovs")).is_err());
+    assert!(parse(&args("render 1 --aovs dist/aovs")).is_ok());
+    assert!(parse(&args("bench 1 --runs 2")).is_ok());
+  }
+}
@@ -1,20 +1,22 @@
-use std::fs;
 use std::io;
 
-static FILENAME: &'static str = "dist/00.ppm";
+use crate::cli::Options;
+use crate::framebuffer::Framebuffer;
+use crate::vec3::Vec3;
 
-pub fn run() -> io::Result<()> {
-  let image_width = 256;
-  let image_height = 256;
+static FILENAME: &str = "dist/00.ppm";
 
-  let part0 = format!("P3\n{} {}\n255\n", image_width, image_height);
+pub fn run(options: &Options) -> io::Result<()> {
+  let (image_width, image_height) = options.image_size(256, 1.0);
 
-  let mut contents = String::from(part0);
+  let mut framebuffer = Framebuffer::new(image_width, image_height);
 
-  for j in (0..image_height).rev() {
-    for i in 0..image_width {
-      let j = j as f64;
-      let i = i as f64;
+  for y in 0..image_height {
+    // rows are stored from the top, while g grows upwards
+    let j = (image_height - 1 - y) as f64;
+
+    for x in 0..image_width {
+      let i = x as f64;
       let w = image_width as f64;
       let h = image_height as f64;
 
@@ -22,16 +24,11 @@ pub fn run() -> io::Result<()> {
       let g = j / h;
       let b = 0.25;
 
-      let ir = (255.999 * r) as u32;
-      let ig = (255.999 * g) as u32;
-      let ib = (255.999 * b) as u32;
-      let content = format!("{} {} {}\n", ir, ig, ib);
-
-      contents.push_str(&content);
+      framebuffer.set(x, y, Vec3(r, g, b));
     }
   }
 
-  fs::write(FILENAME, contents.as_bytes())?;
+  options.save(&framebuffer, FILENAME, false)?;
 
   Ok(())
 }
@@ -1,32 +1,26 @@
-use std::fs;
 use std::io;
 
+use crate::cli::Options;
+use crate::framebuffer::Framebuffer;
 use crate::vec3::Vec3;
 
-static FILENAME: &'static str = "dist/01.ppm";
+static FILENAME: &str = "dist/01.ppm";
 
-pub fn run() -> io::Result<()> {
-  let image_width = 256;
-  let image_height = 256;
+pub fn run(options: &Options) -> io::Result<()> {
+  let (image_width, image_height) = options.image_size(256, 1.0);
 
-  let part0 = format!("P3\n{} {}\n255\n", image_width, image_height);
+  let mut framebuffer = Framebuffer::new(image_width, image_height);
 
-  let mut contents = String::from(part0);
+  for (index, pixel) in framebuffer.pixels.iter_mut().enumerate() {
+    let i = (index % image_width) as f64;
+    let j = (image_height - 1 - index / image_width) as f64;
+    let w = image_width as f64;
+    let h = image_height as f64;
 
-  for j in (0..image_height).rev() {
-    for i in 0..image_width {
-      let j = j as f64;
-      let i = i as f64;
-      let w = image_width as f64;
-      let h = image_height as f64;
-
-      let color = Vec3(i / w, j / h, 0.25);
-
-      contents.push_str(&color.to_rgb_string());
-    }
+    *pixel = Vec3(i / w, j / h, 0.25);
   }
 
-  fs::write(FILENAME, contents.as_bytes())?;
+  options.save(&framebuffer, FILENAME, false)?;
 
   Ok(())
 }
@@ -1,14 +1,16 @@
-use std::fs;
 use std::io;
 
+use crate::background::Background;
+use crate::camera::ViewportCamera;
+use crate::cli::Options;
+use crate::hittable::Hittable;
+use crate::hittable_list::HittableList;
+use crate::integrator::Integrator;
 use crate::ray::Ray;
+use crate::renderer::Renderer;
 use crate::vec3::Vec3;
 
-static FILENAME: &'static str = "dist/03.ppm";
-
-fn lerp(t: f64, start: Vec3, end: Vec3) -> Vec3 {
-  (1.0 - t) * start + (t * end)
-}
+static FILENAME: &str = "dist/03.ppm";
 
 fn hit_sphere(center: Vec3, radius: f64, ray: &Ray) -> bool {
   let oc = ray.origin - center;
@@ -20,28 +22,23 @@ fn hit_sphere(center: Vec3, radius: f64, ray: &Ray) -> bool {
   discriminant > 0.0
 }
 
-fn ray_color(ray: &Ray) -> Vec3 {
-  let center = Vec3(0.0, 0.0, -1.0);
-
-  if hit_sphere(center, 0.5, &ray) {
-    return Vec3(1.0, 0.0, 0.0);
-  }
+struct HitSphere {}
 
-  let unit_direction = Vec3::unit_vector(&ray.direction);
-  let t = 0.5 * (unit_direction.y() + 1.0);
+impl Integrator for HitSphere {
+  fn ray_color(&self, ray: &Ray, _world: &dyn Hittable, background: &Background, _depth: u64) -> Vec3 {
+    let center = Vec3(0.0, 0.0, -1.0);
 
-  let from = Vec3(1.0, 1.0, 1.0);
-  let to = Vec3(0.5, 0.7, 1.0);
+    if hit_sphere(center, 0.5, ray) {
+      return Vec3(1.0, 0.0, 0.0);
+    }
 
-  lerp(t, from, to)
+    background.value(ray)
+  }
 }
 
-pub fn run() -> io::Result<()> {
-  let aspect_ratio = 16.0 / 9.0;
-  let image_width = 384;
-  let image_height = ((image_width as f64) / aspect_ratio) as i32;
-
-  let part0 = format!("P3\n{} {}\n255\n", image_width, image_height);
+pub fn run(options: &Options) -> io::Result<()> {
+  let (image_width, image_height) = options.image_size(384, 16.0 / 9.0);
+  let aspect_ratio = image_width as f64 / image_height as f64;
 
   let viewport_height = 2.0;
   let viewport_width = aspect_ratio * viewport_height;
@@ -53,29 +50,18 @@ pub fn run() -> io::Result<()> {
 
   let lower_left_corner = origin - horizontal / 2.0 - vertical / 2.0 - Vec3(0.0, 0.0, focal_length);
 
-  let mut contents = String::from(part0);
-
-  for j in (0..image_height).rev() {
-    for i in 0..image_width {
-      let j = j as f64;
-      let i = i as f64;
-      let w = image_width as f64;
-      let h = image_height as f64;
-
-      let u = i / (w - 1.0);
-      let v = j / (h - 1.0);
+  let camera = ViewportCamera::new(origin, lower_left_corner, horizontal, vertical);
 
-      let direction = lower_left_corner + (u * horizontal) + (v * vertical) - origin;
+  let world = HittableList::new();
 
-      let ray = Ray::new(origin.clone(), direction);
+  let mut renderer = Renderer::new(image_width, image_height);
+  renderer.set_antialias(false);
+  renderer.set_integrator(Box::new(HitSphere {}));
+  options.configure(&mut renderer);
 
-      let color = ray_color(&ray);
-
-      contents.push_str(&color.to_rgb_string());
-    }
-  }
+  let framebuffer = options.render(&renderer, &world, &camera)?;
 
-  fs::write(FILENAME, contents.as_bytes())?;
+  options.save(&framebuffer, FILENAME, false)?;
 
   Ok(())
 }
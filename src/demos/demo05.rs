@@ -1,44 +1,18 @@
-use std::fs;
 use std::io;
 
-use crate::hittable::{HitRecord, Hittable};
+use crate::camera::ViewportCamera;
+use crate::cli::Options;
 use crate::hittable_list::HittableList;
-use crate::ray::Ray;
+use crate::integrator::NormalIntegrator;
+use crate::renderer::Renderer;
 use crate::sphere::Sphere;
 use crate::vec3::Vec3;
 
-static FILENAME: &'static str = "dist/05.ppm";
+static FILENAME: &str = "dist/05.ppm";
 
-fn lerp(t: f64, start: Vec3, end: Vec3) -> Vec3 {
-  (1.0 - t) * start + (t * end)
-}
-
-fn ray_color<W>(ray: &Ray, world: &W) -> Vec3
-where
-  W: Hittable,
-{
-  let mut record = HitRecord::new();
-
-  if world.hit(&ray, 0.0, f64::INFINITY, &mut record) {
-    return 0.5 * (record.normal + Vec3(1.0, 1.0, 1.0));
-  }
-
-  let unit_direction = Vec3::unit_vector(&ray.direction);
-  let t = 0.5 * (unit_direction.y() + 1.0);
-
-  let from = Vec3(1.0, 1.0, 1.0);
-  let to = Vec3(0.5, 0.7, 1.0);
-
-  lerp(t, from, to)
-}
-
-pub fn run() -> io::Result<()> {
-  let aspect_ratio = 16.0 / 9.0;
-  let image_width = 384;
-  let image_height = ((image_width as f64) / aspect_ratio) as i32;
-
-  let part0 = format!("P3\n{} {}\n255\n", image_width, image_height);
-  let mut contents = String::from(part0);
+pub fn run(options: &Options) -> io::Result<()> {
+  let (image_width, image_height) = options.image_size(384, 16.0 / 9.0);
+  let aspect_ratio = image_width as f64 / image_height as f64;
 
   let viewport_height = 2.0;
   let viewport_width = aspect_ratio * viewport_height;
@@ -49,6 +23,8 @@ pub fn run() -> io::Result<()> {
   let vertical = Vec3(0.0, viewport_height, 0.0);
   let lower_left_corner = origin - horizontal / 2.0 - vertical / 2.0 - Vec3(0.0, 0.0, focal_length);
 
+  let camera = ViewportCamera::new(origin, lower_left_corner, horizontal, vertical);
+
   let mut world = HittableList::new();
 
   let sphere_0 = Sphere::new(Vec3(0.0, 0.0, -1.0), 0.5);
@@ -57,27 +33,14 @@ pub fn run() -> io::Result<()> {
   world.add(Box::new(sphere_0));
   world.add(Box::new(sphere_1));
 
-  for j in (0..image_height).rev() {
-    for i in 0..image_width {
-      let j = j as f64;
-      let i = i as f64;
-      let w = image_width as f64;
-      let h = image_height as f64;
-
-      let u = i / (w - 1.0);
-      let v = j / (h - 1.0);
-
-      let direction = lower_left_corner + u * horizontal + v * vertical;
-
-      let ray = Ray::new(origin, direction);
-
-      let color = ray_color(&ray, &world);
+  let mut renderer = Renderer::new(image_width, image_height);
+  renderer.set_antialias(false);
+  renderer.set_integrator(Box::new(NormalIntegrator::new()));
+  options.configure(&mut renderer);
 
-      contents.push_str(&color.to_rgb_string());
-    }
-  }
+  let framebuffer = options.render(&renderer, &world, &camera)?;
 
-  fs::write(FILENAME, contents.as_bytes())?;
+  options.save(&framebuffer, FILENAME, false)?;
 
   Ok(())
 }
@@ -1,53 +1,46 @@
-use std::fs;
 use std::io;
 use std::time::Instant;
 
-use crate::camera::Camera;
+use crate::background::Background;
+use crate::camera::ViewportCamera;
+use crate::cli::Options;
 use crate::hittable::{HitRecord, Hittable};
 use crate::hittable_list::HittableList;
+use crate::integrator::Integrator;
 use crate::ray::Ray;
+use crate::renderer::Renderer;
 use crate::sphere::Sphere;
-use crate::utils::random;
 use crate::vec3::Vec3;
 
-static FILENAME: &'static str = "dist/08.ppm";
+static FILENAME: &str = "dist/08.ppm";
 
-fn ray_color<W>(ray: &Ray, world: &W, depth: u64) -> Vec3
-where
-  W: Hittable,
-{
-  if depth <= 0 {
-    return Vec3(0.0, 0.0, 0.0);
-  }
-
-  let mut record = HitRecord::new();
+struct HemisphereDiffuse {}
 
-  if world.hit(&ray, 0.001, f64::INFINITY, &mut record) {
-    let target = record.point + Vec3::random_in_hemisphere(record.normal);
-    let ray = Ray::new(record.point, target - record.point);
-    return 0.5 * ray_color(&ray, world, depth - 1);
-  }
+impl Integrator for HemisphereDiffuse {
+  fn ray_color(&self, ray: &Ray, world: &dyn Hittable, background: &Background, depth: u64) -> Vec3 {
+    if depth == 0 {
+      return Vec3(0.0, 0.0, 0.0);
+    }
 
-  let unit_direction = Vec3::unit_vector(&ray.direction);
-  let t = 0.5 * (unit_direction.y() + 1.0);
+    let mut record = HitRecord::new();
 
-  let from = Vec3(1.0, 1.0, 1.0);
-  let to = Vec3(0.5, 0.7, 1.0);
+    if world.hit(ray, 0.001, f64::INFINITY, &mut record) {
+      let target = record.point + Vec3::random_in_hemisphere(record.normal);
+      let ray = Ray::new(record.point, target - record.point);
+      return 0.5 * self.ray_color(&ray, world, background, depth - 1);
+    }
 
-  Vec3::lerp(t, from, to)
+    background.value(ray)
+  }
 }
 
-pub fn run() -> io::Result<()> {
-  let aspect_ratio = 16.0 / 9.0;
-  let image_width = 384;
-  let image_height = ((image_width as f64) / aspect_ratio) as i32;
+pub fn run(options: &Options) -> io::Result<()> {
+  let (image_width, image_height) = options.image_size(384, 16.0 / 9.0);
+  let aspect_ratio = image_width as f64 / image_height as f64;
 
   let samples_per_pixel = 50;
   let max_depth = 50;
 
-  let part0 = format!("P3\n{} {}\n255\n", image_width, image_height);
-  let mut contents = String::from(part0);
-
   let viewport_height = 2.0;
   let viewport_width = aspect_ratio * viewport_height;
   let focal_length = 1.0;
@@ -57,7 +50,7 @@ pub fn run() -> io::Result<()> {
   let vertical = Vec3(0.0, viewport_height, 0.0);
   let lower_left_corner = origin - horizontal / 2.0 - vertical / 2.0 - Vec3(0.0, 0.0, focal_length);
 
-  let camera = Camera::new(origin, lower_left_corner, horizontal, vertical);
+  let camera = ViewportCamera::new(origin, lower_left_corner, horizontal, vertical);
 
   let mut world = HittableList::new();
 
@@ -67,36 +60,21 @@ pub fn run() -> io::Result<()> {
   world.add(Box::new(sphere_0));
   world.add(Box::new(sphere_1));
 
+  let mut renderer = Renderer::new(image_width, image_height);
+  renderer.set_samples_per_pixel(samples_per_pixel);
+  renderer.set_max_depth(max_depth);
+  renderer.set_integrator(Box::new(HemisphereDiffuse {}));
+  options.configure(&mut renderer);
+
   let start = Instant::now();
 
-  for j in (0..image_height).rev() {
-    for i in 0..image_width {
-      let j = j as f64;
-      let i = i as f64;
-      let w = image_width as f64;
-      let h = image_height as f64;
-
-      let mut color = Vec3(0.0, 0.0, 0.0);
-
-      for _ in 0..samples_per_pixel {
-        let u = (i + random()) / (w - 1.0);
-        let v = (j + random()) / (h - 1.0);
-        let ray = camera.get_ray(u, v);
-        color = color + ray_color(&ray, &world, max_depth);
-      }
-
-      color = color / (samples_per_pixel as f64);
-      // gamma-correct for gamma=2.0
-      color = color.sqrt();
-      contents.push_str(&color.to_rgb_string());
-    }
-  }
+  let framebuffer = options.render(&renderer, &world, &camera)?;
 
   let duration = start.elapsed();
 
   println!("Ray Tracing taked time: {:?}", duration);
 
-  fs::write(FILENAME, contents.as_bytes())?;
+  options.save(&framebuffer, FILENAME, true)?;
 
   Ok(())
 }
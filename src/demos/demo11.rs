@@ -1,60 +1,24 @@
-use std::fs;
 use std::io;
 use std::time::Instant;
+use std::sync::Arc;
 
 use crate::camera::PositionalCamera;
-use crate::hittable::{HitRecord, Hittable};
+use crate::cli::Options;
 use crate::hittable_list::HittableList;
 use crate::material::{Dielectric, Lambertian, Metal};
-use crate::ray::Ray;
+use crate::renderer::Renderer;
 use crate::sphere::Sphere;
-use crate::utils::random;
 use crate::vec3::Vec3;
 
-static FILENAME: &'static str = "dist/11.ppm";
+static FILENAME: &str = "dist/11.ppm";
 
-fn ray_color<W>(ray: &Ray, world: &W, depth: u64) -> Vec3
-where
-  W: Hittable,
-{
-  if depth <= 0 {
-    return Vec3::fill(0.0);
-  }
-
-  let mut record = HitRecord::new();
-
-  if world.hit(&ray, 0.001, f64::INFINITY, &mut record) {
-    let mut scattered = Ray::new(Vec3::fill(0.0), Vec3::fill(0.0));
-    let mut attenuation = Vec3::fill(0.0);
-    let mut material = record.material.box_clone();
-
-    if material.scatter(&ray, &mut record, &mut attenuation, &mut scattered) {
-      return attenuation * ray_color(&scattered, world, depth - 1);
-    } else {
-      return Vec3::fill(0.0);
-    }
-  }
-
-  let unit_direction = ray.direction.unit_vector();
-  let t = 0.5 * (unit_direction.y() + 1.0);
-
-  let from = Vec3(1.0, 1.0, 1.0);
-  let to = Vec3(0.5, 0.7, 1.0);
-
-  Vec3::lerp(t, from, to)
-}
-
-pub fn run() -> io::Result<()> {
-  let aspect_ratio = 16.0 / 9.0;
-  let image_width = 384;
-  let image_height = ((image_width as f64) / aspect_ratio) as i32;
+pub fn run(options: &Options) -> io::Result<()> {
+  let (image_width, image_height) = options.image_size(384, 16.0 / 9.0);
+  let aspect_ratio = image_width as f64 / image_height as f64;
 
   let samples_per_pixel = 50;
   let max_depth = 50;
 
-  let part0 = format!("P3\n{} {}\n255\n", image_width, image_height);
-  let mut contents = String::from(part0);
-
   let camera = PositionalCamera::new(
     Vec3(-2.0, 2.0, 1.0),
     Vec3(0.0, 0.0, -1.0),
@@ -77,47 +41,31 @@ pub fn run() -> io::Result<()> {
   let material_3 = Dielectric::new(1.5);
   let material_4 = Dielectric::new(1.5);
 
-  sphere_0.set_material(Box::new(material_0));
-  sphere_1.set_material(Box::new(material_1));
-  sphere_2.set_material(Box::new(material_2));
-  sphere_3.set_material(Box::new(material_3));
-  sphere_4.set_material(Box::new(material_4));
+  sphere_0.set_material(Arc::new(material_0));
+  sphere_1.set_material(Arc::new(material_1));
+  sphere_2.set_material(Arc::new(material_2));
+  sphere_3.set_material(Arc::new(material_3));
+  sphere_4.set_material(Arc::new(material_4));
 
   world.add(Box::new(sphere_0));
   world.add(Box::new(sphere_1));
   world.add(Box::new(sphere_2));
   world.add(Box::new(sphere_3));
 
+  let mut renderer = Renderer::new(image_width, image_height);
+  renderer.set_samples_per_pixel(samples_per_pixel);
+  renderer.set_max_depth(max_depth);
+  options.configure(&mut renderer);
+
   let start = Instant::now();
 
-  for j in (0..image_height).rev() {
-    for i in 0..image_width {
-      let j = j as f64;
-      let i = i as f64;
-      let w = image_width as f64;
-      let h = image_height as f64;
-
-      let mut color = Vec3::fill(0.0);
-
-      for _ in 0..samples_per_pixel {
-        let u = (i + random()) / (w - 1.0);
-        let v = (j + random()) / (h - 1.0);
-        let ray = camera.get_ray(u, v);
-        color = color + ray_color(&ray, &world, max_depth);
-      }
-
-      color = color / (samples_per_pixel as f64);
-      // gamma-correct for gamma=2.0
-      color = color.sqrt();
-      contents.push_str(&color.to_rgb_string());
-    }
-  }
+  let framebuffer = options.render(&renderer, &world, &camera)?;
 
   let duration = start.elapsed();
 
   println!("Ray Tracing taked time: {:?}", duration);
 
-  fs::write(FILENAME, contents.as_bytes())?;
+  options.save(&framebuffer, FILENAME, true)?;
 
   Ok(())
 }
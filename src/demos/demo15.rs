@@ -1,66 +1,31 @@
-use std::fs;
 use std::io;
 use std::time::Instant;
+use std::sync::Arc;
 
-use crate::camera::ExposureCamera;
-use crate::hittable::{HitRecord, Hittable};
+use crate::bvh::{BvhNode, SplitHeuristic};
+use crate::camera::{Camera, ExposureCamera};
+use crate::cli::Options;
+use crate::hittable::Hittable;
 use crate::hittable_list::HittableList;
 use crate::material::{Dielectric, Lambertian, Material, Metal};
-use crate::ray::Ray;
+use crate::renderer::Renderer;
 use crate::sphere::{MovingSphere, Sphere};
 use crate::utils::random;
 use crate::utils::random_in;
 use crate::vec3::Vec3;
 
-extern crate threadpool;
-
-use std::sync::mpsc::channel;
-use std::sync::Arc;
-use threadpool::ThreadPool;
-
-static FILENAME: &'static str = "dist/15.ppm";
-
-fn ray_color<W>(ray: &Ray, world: &W, depth: u64) -> Vec3
-where
-  W: Hittable,
-{
-  if depth <= 0 {
-    return Vec3::fill(0.0);
-  }
-
-  let mut record = HitRecord::new();
-
-  if world.hit(&ray, 0.001, f64::INFINITY, &mut record) {
-    let mut scattered = Ray::new(Vec3::fill(0.0), Vec3::fill(0.0));
-    let mut attenuation = Vec3::fill(0.0);
-    let mut material = record.material.box_clone();
-
-    if material.scatter(&ray, &mut record, &mut attenuation, &mut scattered) {
-      return attenuation * ray_color(&scattered, world, depth - 1);
-    } else {
-      return Vec3::fill(0.0);
-    }
-  }
-
-  let unit_direction = ray.direction.unit_vector();
-  let t = 0.5 * (unit_direction.y() + 1.0);
-
-  let from = Vec3(1.0, 1.0, 1.0);
-  let to = Vec3(0.5, 0.7, 1.0);
-
-  Vec3::lerp(t, from, to)
-}
+static FILENAME: &str = "dist/15.ppm";
 
 fn random_scene() -> HittableList {
   let mut world = HittableList::new();
 
   let mut ground = Sphere::new(Vec3(0.0, -1000.0, 0.0), 1000.0);
   let ground_material = Lambertian::new(Vec3(0.5, 0.5, 0.5));
-  ground.set_material(Box::new(ground_material));
+  ground.set_material(Arc::new(ground_material));
 
   world.add(Box::new(ground));
 
-  let create_sphere = |center: Vec3, material: Box<dyn Material>| -> Box<dyn Hittable + Send> {
+  let create_sphere = |center: Vec3, material: Arc<dyn Material>| -> Box<dyn Hittable + Send> {
     if random() < 0.7 {
       let mut sphere = Sphere::new(center, 0.2);
       sphere.set_material(material);
@@ -91,19 +56,19 @@ fn random_scene() -> HittableList {
           // diffuse
           let albedo = Vec3::random() * Vec3::random();
           let material = Lambertian::new(albedo);
-          let sphere = create_sphere(center, Box::new(material));
+          let sphere = create_sphere(center, Arc::new(material));
           world.add(sphere);
         } else if choose_material < 0.95 {
           // metal
           let albedo = Vec3::random();
           let fuzz = random_in(0.0, 0.5);
           let material = Metal::new(albedo).set_fuzz(fuzz);
-          let sphere = create_sphere(center, Box::new(material));
+          let sphere = create_sphere(center, Arc::new(material));
           world.add(sphere);
         } else {
           // glass
           let material = Dielectric::new(1.5);
-          let sphere = create_sphere(center, Box::new(material));
+          let sphere = create_sphere(center, Arc::new(material));
           world.add(sphere);
         }
       }
@@ -112,37 +77,32 @@ fn random_scene() -> HittableList {
 
   let material = Dielectric::new(1.5);
   let mut sphere = Sphere::new(Vec3(0.0, 1.0, 0.0), 1.0);
-  sphere.set_material(Box::new(material));
+  sphere.set_material(Arc::new(material));
   world.add(Box::new(sphere));
 
   let material = Lambertian::new(Vec3(0.4, 0.2, 0.1));
   let mut sphere = Sphere::new(Vec3(-4.0, 1.0, 0.0), 1.0);
-  sphere.set_material(Box::new(material));
+  sphere.set_material(Arc::new(material));
   world.add(Box::new(sphere));
 
   let material = Metal::new(Vec3(0.7, 0.6, 0.5)).set_fuzz(0.0);
   let mut sphere = Sphere::new(Vec3(4.0, 1.0, 0.0), 1.0);
-  sphere.set_material(Box::new(material));
+  sphere.set_material(Arc::new(material));
   world.add(Box::new(sphere));
 
   world
 }
 
-type Pixels = Vec<Vec3>;
-
-pub fn run() -> io::Result<()> {
-  let aspect_ratio = 16.0 / 9.0;
-  let image_width = 800;
-  let image_height = ((image_width as f64) / aspect_ratio) as i32;
+pub fn run(options: &Options) -> io::Result<()> {
+  let (image_width, image_height) = options.image_size(800, 16.0 / 9.0);
+  let aspect_ratio = image_width as f64 / image_height as f64;
 
-  let pixels_size = (image_width * image_height) as usize;
-
-  let samples_per_pixel = 100;
+  // the sky is done after the first samples, the glass, shadows and blurred edges get more
+  let samples_per_pixel = 16;
+  let max_samples_per_pixel = 256;
+  let error_threshold = 0.05;
   let max_depth = 50;
 
-  let part0 = format!("P3\n{} {}\n255\n", image_width, image_height);
-  let mut contents = String::from(part0);
-
   let look_from = Vec3(13.0, 2.0, 3.0);
   let look_at = Vec3(0.0, 0.0, 0.0);
   let vup = Vec3(0.0, 1.0, 0.0);
@@ -161,71 +121,25 @@ pub fn run() -> io::Result<()> {
     1.0,
   );
 
-  let world = random_scene();
+  let (time0, time1) = camera.shutter_interval();
+  let world = BvhNode::new(random_scene(), time0, time1, SplitHeuristic::Sah);
 
-  let world = Arc::new(world);
-  let camera = Arc::new(camera);
-
-  let pool = ThreadPool::new(4);
-  let (sender, receiver) = channel::<Pixels>();
+  let mut renderer = Renderer::new(image_width, image_height);
+  renderer.set_samples_per_pixel(samples_per_pixel);
+  renderer.set_max_samples_per_pixel(max_samples_per_pixel);
+  renderer.set_error_threshold(error_threshold);
+  renderer.set_max_depth(max_depth);
+  options.configure(&mut renderer);
 
   let start = Instant::now();
 
   println!("Ray Tracing start");
 
-  for n in 0..samples_per_pixel {
-    let sender = sender.clone();
-    let world = Arc::clone(&world);
-    let camera = Arc::clone(&camera);
-    let tracing = move || {
-      let mut pixels = Vec::<Vec3>::with_capacity(pixels_size);
-
-      println!("Ray Tracing: start {}", n);
-
-      for j in (0..image_height).rev() {
-        for i in 0..image_width {
-          let j = j as f64;
-          let i = i as f64;
-          let w = image_width as f64;
-          let h = image_height as f64;
-          let u = (i + random()) / (w - 1.0);
-          let v = (j + random()) / (h - 1.0);
-          let ray = camera.get_ray(u, v);
-          let color = ray_color(&ray, &*world, max_depth);
-          pixels.push(color);
-        }
-      }
-
-      println!("Ray Tracing: finish {}", n);
-      sender.send(pixels).expect("ray tracing failed");
-    };
-    pool.execute(tracing);
-  }
-
-  let mut colors = Vec::<Vec3>::with_capacity(pixels_size);
-
-  for _ in 0..pixels_size {
-    colors.push(Vec3::fill(0.0));
-  }
-
-  for pixels in receiver.iter().take(samples_per_pixel) {
-    for (i, color) in colors.iter_mut().enumerate() {
-      *color = *color + pixels[i];
-    }
-  }
-
-  println!("ThreadPool taked time: {:?}", start.elapsed());
-
-  for color in colors {
-    let color = color / (samples_per_pixel as f64);
-    // gamma-correct for gamma=2.0
-    let color = color.sqrt();
-    contents.push_str(&color.to_rgb_string());
-  }
+  let framebuffer = options.render(&renderer, &world, &camera)?;
 
   println!("Ray Tracing taked time: {:?}", start.elapsed());
 
-  fs::write(FILENAME, contents.as_bytes())?;
+  options.save(&framebuffer, FILENAME, true)?;
 
   Ok(())
 }
@@ -0,0 +1,101 @@
+use std::io;
+use std::time::Instant;
+use std::sync::Arc;
+
+use crate::background::Background;
+use crate::camera::PositionalCamera;
+use crate::cli::Options;
+use crate::hittable_list::HittableList;
+use crate::integrator::MisPathTracer;
+use crate::material::{Dielectric, DiffuseLight, Lambertian, Material, Metal};
+use crate::renderer::Renderer;
+use crate::sphere::Sphere;
+use crate::vec3::Vec3;
+
+static FILENAME: &str = "dist/16.ppm";
+
+static LIGHT_CENTER: Vec3 = Vec3(0.0, 2.1, -0.5);
+static LIGHT_RADIUS: f64 = 0.3;
+
+// a closed room whose walls are huge spheres, lit only by a small glowing ball below the ceiling
+fn sphere_room() -> HittableList {
+  let mut world = HittableList::new();
+
+  let mut add_sphere = |center: Vec3, radius: f64, material: Arc<dyn Material>| {
+    let mut sphere = Sphere::new(center, radius);
+    sphere.set_material(material);
+    world.add(Box::new(sphere));
+  };
+
+  let wall_radius = 1000.0;
+  let half_size = 2.5;
+  let white = Vec3(0.73, 0.73, 0.73);
+  let red = Vec3(0.65, 0.05, 0.05);
+  let green = Vec3(0.12, 0.45, 0.15);
+
+  // floor, ceiling, back, front, left, right
+  let walls = [
+    (Vec3(0.0, -1.0, 0.0), white),
+    (Vec3(0.0, 1.0, 0.0), white),
+    (Vec3(0.0, 0.0, -1.0), white),
+    (Vec3(0.0, 0.0, 1.0), white),
+    (Vec3(-1.0, 0.0, 0.0), red),
+    (Vec3(1.0, 0.0, 0.0), green),
+  ];
+
+  for (direction, albedo) in walls.iter() {
+    let center = (wall_radius + half_size) * *direction;
+    add_sphere(center, wall_radius, Arc::new(Lambertian::new(*albedo)));
+  }
+
+  let light = DiffuseLight::new(Vec3::fill(15.0));
+  add_sphere(LIGHT_CENTER, LIGHT_RADIUS, Arc::new(light));
+
+  let glass = Dielectric::new(1.5);
+  add_sphere(Vec3(-1.0, -1.7, -0.8), 0.8, Arc::new(glass));
+
+  let metal = Metal::new(Vec3(0.8, 0.85, 0.88)).set_fuzz(0.05);
+  add_sphere(Vec3(1.1, -1.8, -1.4), 0.7, Arc::new(metal));
+
+  world
+}
+
+pub fn run(options: &Options) -> io::Result<()> {
+  let (image_width, image_height) = options.image_size(400, 1.0);
+  let aspect_ratio = image_width as f64 / image_height as f64;
+
+  let samples_per_pixel = 100;
+  let max_depth = 50;
+
+  let camera = PositionalCamera::new(
+    Vec3(0.0, 0.0, 2.4),
+    Vec3(0.0, 0.0, 0.0),
+    Vec3(0.0, 1.0, 0.0),
+    80.0,
+    aspect_ratio,
+  );
+
+  let world = sphere_room();
+
+  // the light is small, so every diffuse bounce also sends a shadow ray toward it,
+  // weighted against the bounce itself hitting the light
+  let mut lights = HittableList::new();
+  lights.add(Box::new(Sphere::new(LIGHT_CENTER, LIGHT_RADIUS)));
+
+  let mut renderer = Renderer::new(image_width, image_height);
+  renderer.set_samples_per_pixel(samples_per_pixel);
+  renderer.set_max_depth(max_depth);
+  renderer.set_background(Background::Color(Vec3::fill(0.0)));
+  renderer.set_integrator(Box::new(MisPathTracer::new(lights)));
+  options.configure(&mut renderer);
+
+  let start = Instant::now();
+
+  let framebuffer = options.render(&renderer, &world, &camera)?;
+
+  println!("Ray Tracing taked time: {:?}", start.elapsed());
+
+  options.save(&framebuffer, FILENAME, true)?;
+
+  Ok(())
+}
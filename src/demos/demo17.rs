@@ -0,0 +1,77 @@
+use std::io;
+use std::sync::Arc;
+use std::time::Instant;
+
+use crate::camera::PositionalCamera;
+use crate::cli::Options;
+use crate::hittable_list::HittableList;
+use crate::material::{Lambertian, Metal};
+use crate::renderer::Renderer;
+use crate::sphere::Sphere;
+use crate::texture::{CheckerTexture, ImageTexture, NoiseTexture};
+use crate::vec3::Vec3;
+
+static FILENAME: &str = "dist/17.ppm";
+
+// any earlier render can be wrapped around a sphere
+static IMAGE_FILENAME: &str = "dist/15.ppm";
+
+fn textured_scene() -> io::Result<HittableList> {
+  let mut world = HittableList::new();
+
+  let checker = CheckerTexture::from_colors(Vec3(0.2, 0.3, 0.1), Vec3(0.9, 0.9, 0.9));
+  let mut ground = Sphere::new(Vec3(0.0, -1000.0, 0.0), 1000.0);
+  ground.set_material(Arc::new(Lambertian::from_texture(Arc::new(checker))));
+  world.add(Box::new(ground));
+
+  let marble = NoiseTexture::new(4.0);
+  let mut sphere = Sphere::new(Vec3(-2.2, 1.0, 0.0), 1.0);
+  sphere.set_material(Arc::new(Lambertian::from_texture(Arc::new(marble))));
+  world.add(Box::new(sphere));
+
+  let image = ImageTexture::load(IMAGE_FILENAME)?;
+  let mut sphere = Sphere::new(Vec3(0.0, 1.0, 0.0), 1.0);
+  sphere.set_material(Arc::new(Lambertian::from_texture(Arc::new(image))));
+  world.add(Box::new(sphere));
+
+  let mut checker = CheckerTexture::from_colors(Vec3(0.8, 0.6, 0.2), Vec3(0.6, 0.6, 0.6));
+  checker.set_scale(20.0);
+  let mut sphere = Sphere::new(Vec3(2.2, 1.0, 0.0), 1.0);
+  sphere.set_material(Arc::new(Metal::from_texture(Arc::new(checker)).set_fuzz(0.1)));
+  world.add(Box::new(sphere));
+
+  Ok(world)
+}
+
+pub fn run(options: &Options) -> io::Result<()> {
+  let (image_width, image_height) = options.image_size(384, 16.0 / 9.0);
+  let aspect_ratio = image_width as f64 / image_height as f64;
+
+  let samples_per_pixel = 50;
+  let max_depth = 50;
+
+  let camera = PositionalCamera::new(
+    Vec3(0.0, 2.5, 9.0),
+    Vec3(0.0, 0.8, 0.0),
+    Vec3(0.0, 1.0, 0.0),
+    35.0,
+    aspect_ratio,
+  );
+
+  let world = textured_scene()?;
+
+  let mut renderer = Renderer::new(image_width, image_height);
+  renderer.set_samples_per_pixel(samples_per_pixel);
+  renderer.set_max_depth(max_depth);
+  options.configure(&mut renderer);
+
+  let start = Instant::now();
+
+  let framebuffer = options.render(&renderer, &world, &camera)?;
+
+  println!("Ray Tracing taked time: {:?}", start.elapsed());
+
+  options.save(&framebuffer, FILENAME, true)?;
+
+  Ok(())
+}
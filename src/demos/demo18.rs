@@ -0,0 +1,92 @@
+use std::io;
+use std::sync::Arc;
+use std::time::Instant;
+
+use crate::camera::PositionalCamera;
+use crate::cli::Options;
+use crate::hittable::Hittable;
+use crate::hittable_list::HittableList;
+use crate::material::{Dielectric, Lambertian, Metal};
+use crate::mesh::TriangleMesh;
+use crate::obj;
+use crate::renderer::Renderer;
+use crate::sphere::Sphere;
+use crate::texture::CheckerTexture;
+use crate::transform::{Transform, TransformedHittable};
+use crate::triangle::Triangle;
+use crate::vec3::Vec3;
+
+static FILENAME: &str = "dist/18.ppm";
+
+static MODEL_FILENAME: &str = "assets/icosphere.obj";
+
+fn mesh_scene() -> io::Result<HittableList> {
+  let mut world = HittableList::new();
+
+  let checker = CheckerTexture::from_colors(Vec3(0.2, 0.3, 0.1), Vec3(0.9, 0.9, 0.9));
+  let mut ground = Sphere::new(Vec3(0.0, -1000.0, 0.0), 1000.0);
+  ground.set_material(Arc::new(Lambertian::from_texture(Arc::new(checker))));
+  world.add(Box::new(ground));
+
+  // the model is a unit icosphere, its geometry and bvh are built once and shared by every instance
+  let model = obj::load(MODEL_FILENAME)?;
+  let mesh: Arc<dyn Hittable> = Arc::new(TriangleMesh::new(model.data));
+  let placements: [(Vec3, Vec3); 3] = [
+    (Vec3(-2.2, 1.0, 0.0), Vec3::fill(1.0)),
+    (Vec3(0.0, 1.0, 0.0), Vec3::fill(1.0)),
+    (Vec3(2.2, 0.7, 0.0), Vec3(1.0, 0.7, 1.0)),
+  ];
+
+  for (index, (center, scale)) in placements.iter().enumerate() {
+    let transform = Transform::scale(*scale)
+      .expect("Expected non-zero scale factors")
+      .then(Transform::translate(*center));
+    let mut instance = TransformedHittable::new(Arc::clone(&mesh), transform);
+    match index {
+      0 => instance.set_material(Arc::new(Lambertian::new(Vec3(0.7, 0.3, 0.3)))),
+      1 => instance.set_material(Arc::new(Dielectric::new(1.5))),
+      _ => instance.set_material(Arc::new(Metal::new(Vec3(0.8, 0.8, 0.8)).set_fuzz(0.05))),
+    }
+    world.add(Box::new(instance));
+  }
+
+  // a single free standing triangle behind the models
+  let mut triangle = Triangle::new(Vec3(-3.0, 0.0, -3.0), Vec3(3.0, 0.0, -3.0), Vec3(0.0, 3.5, -3.0));
+  triangle.set_material(Arc::new(Lambertian::new(Vec3(0.2, 0.4, 0.8))));
+  world.add(Box::new(triangle));
+
+  Ok(world)
+}
+
+pub fn run(options: &Options) -> io::Result<()> {
+  let (image_width, image_height) = options.image_size(384, 16.0 / 9.0);
+  let aspect_ratio = image_width as f64 / image_height as f64;
+
+  let samples_per_pixel = 50;
+  let max_depth = 50;
+
+  let camera = PositionalCamera::new(
+    Vec3(0.0, 2.5, 9.0),
+    Vec3(0.0, 0.8, 0.0),
+    Vec3(0.0, 1.0, 0.0),
+    35.0,
+    aspect_ratio,
+  );
+
+  let world = mesh_scene()?;
+
+  let mut renderer = Renderer::new(image_width, image_height);
+  renderer.set_samples_per_pixel(samples_per_pixel);
+  renderer.set_max_depth(max_depth);
+  options.configure(&mut renderer);
+
+  let start = Instant::now();
+
+  let framebuffer = options.render(&renderer, &world, &camera)?;
+
+  println!("Ray Tracing taked time: {:?}", start.elapsed());
+
+  options.save(&framebuffer, FILENAME, true)?;
+
+  Ok(())
+}
@@ -0,0 +1,13 @@
+use std::io;
+
+use crate::cli::Options;
+use crate::demos::run_scene_file;
+
+static FILENAME: &str = "dist/19.ppm";
+
+// demo15's random scene, described by a text file instead of code
+static SCENE_FILENAME: &str = "scenes/random.scene";
+
+pub fn run(options: &Options) -> io::Result<()> {
+  run_scene_file(SCENE_FILENAME, FILENAME, options)
+}
@@ -0,0 +1,107 @@
+use std::io;
+use std::sync::Arc;
+use std::time::Instant;
+
+use crate::background::Background;
+use crate::box_shape::BoxShape;
+use crate::camera::PositionalCamera;
+use crate::cli::Options;
+use crate::hittable_list::HittableList;
+use crate::integrator::MisPathTracer;
+use crate::material::{DiffuseLight, Lambertian, Material};
+use crate::rect::{XyRect, XzRect, YzRect};
+use crate::renderer::Renderer;
+use crate::transform::{Transform, TransformedHittable};
+use crate::vec3::Vec3;
+
+static FILENAME: &str = "dist/20.ppm";
+
+// the light in the ceiling, x0, x1, z0, z1 and height
+const LIGHT: (f64, f64, f64, f64, f64) = (213.0, 343.0, 227.0, 332.0, 554.0);
+
+// the classic Cornell box, 555 units on each side and open towards the camera
+fn cornell_box() -> HittableList {
+  let mut world = HittableList::new();
+
+  let red = Lambertian::new(Vec3(0.65, 0.05, 0.05));
+  let white: Arc<dyn Material> = Arc::new(Lambertian::new(Vec3(0.73, 0.73, 0.73)));
+  let green = Lambertian::new(Vec3(0.12, 0.45, 0.15));
+  let light = DiffuseLight::new(Vec3::fill(15.0));
+
+  let mut left = YzRect::new(0.0, 555.0, 0.0, 555.0, 555.0);
+  left.set_material(Arc::new(green));
+  world.add(Box::new(left));
+
+  let mut right = YzRect::new(0.0, 555.0, 0.0, 555.0, 0.0);
+  right.set_material(Arc::new(red));
+  world.add(Box::new(right));
+
+  let (x0, x1, z0, z1, k) = LIGHT;
+  let mut lamp = XzRect::new(x0, x1, z0, z1, k);
+  lamp.set_material(Arc::new(light));
+  world.add(Box::new(lamp));
+
+  let mut floor = XzRect::new(0.0, 555.0, 0.0, 555.0, 0.0);
+  floor.set_material(Arc::clone(&white));
+  world.add(Box::new(floor));
+
+  let mut ceiling = XzRect::new(0.0, 555.0, 0.0, 555.0, 555.0);
+  ceiling.set_material(Arc::clone(&white));
+  world.add(Box::new(ceiling));
+
+  let mut back = XyRect::new(0.0, 555.0, 0.0, 555.0, 555.0);
+  back.set_material(Arc::clone(&white));
+  world.add(Box::new(back));
+
+  // both boxes are built at the origin, then turned and moved into place
+  let mut tall = BoxShape::new(Vec3::fill(0.0), Vec3(165.0, 330.0, 165.0));
+  tall.set_material(Arc::clone(&white));
+  let transform = Transform::rotate_y(15.0).then(Transform::translate(Vec3(265.0, 0.0, 295.0)));
+  world.add(Box::new(TransformedHittable::new(Arc::new(tall), transform)));
+
+  let mut short = BoxShape::new(Vec3::fill(0.0), Vec3(165.0, 165.0, 165.0));
+  short.set_material(white);
+  let transform = Transform::rotate_y(-18.0).then(Transform::translate(Vec3(130.0, 0.0, 65.0)));
+  world.add(Box::new(TransformedHittable::new(Arc::new(short), transform)));
+
+  world
+}
+
+pub fn run(options: &Options) -> io::Result<()> {
+  let (image_width, image_height) = options.image_size(400, 1.0);
+  let aspect_ratio = image_width as f64 / image_height as f64;
+
+  let samples_per_pixel = 100;
+  let max_depth = 50;
+
+  let camera = PositionalCamera::new(
+    Vec3(278.0, 278.0, -800.0),
+    Vec3(278.0, 278.0, 0.0),
+    Vec3(0.0, 1.0, 0.0),
+    40.0,
+    aspect_ratio,
+  );
+
+  let world = cornell_box();
+
+  let (x0, x1, z0, z1, k) = LIGHT;
+  let mut lights = HittableList::new();
+  lights.add(Box::new(XzRect::new(x0, x1, z0, z1, k)));
+
+  let mut renderer = Renderer::new(image_width, image_height);
+  renderer.set_samples_per_pixel(samples_per_pixel);
+  renderer.set_max_depth(max_depth);
+  renderer.set_background(Background::Color(Vec3::fill(0.0)));
+  renderer.set_integrator(Box::new(MisPathTracer::new(lights)));
+  options.configure(&mut renderer);
+
+  let start = Instant::now();
+
+  let framebuffer = options.render(&renderer, &world, &camera)?;
+
+  println!("Ray Tracing taked time: {:?}", start.elapsed());
+
+  options.save(&framebuffer, FILENAME, true)?;
+
+  Ok(())
+}
@@ -0,0 +1,119 @@
+use std::io;
+use std::sync::Arc;
+use std::time::Instant;
+
+use crate::background::Background;
+use crate::camera::PositionalCamera;
+use crate::cli::Options;
+use crate::environment::EnvironmentMap;
+use crate::framebuffer::Framebuffer;
+use crate::hittable_list::HittableList;
+use crate::integrator::MisPathTracer;
+use crate::material::{Dielectric, Lambertian, Material, Metal};
+use crate::renderer::Renderer;
+use crate::sphere::Sphere;
+use crate::vec3::Vec3;
+
+static FILENAME: &str = "dist/22.ppm";
+
+// where the sun sits in the environment map, and its angular radius in degrees
+const SUN: (f64, f64) = (0.62, 0.28);
+const SUN_RADIUS: f64 = 2.0;
+
+// a blue sky fading to a pale horizon above dark ground, with a small and very bright sun,
+// the kind of map that is hopeless to light a scene with unless the sun is sampled directly
+pub fn sky_map(width: usize, height: usize) -> Framebuffer {
+  let mut image = Framebuffer::new(width, height);
+  let (sun_s, sun_t) = SUN;
+  let sun_theta = std::f64::consts::PI * sun_t;
+
+  for y in 0..height {
+    let t = (y as f64 + 0.5) / height as f64;
+    let theta = std::f64::consts::PI * t;
+
+    for x in 0..width {
+      let s = (x as f64 + 0.5) / width as f64;
+
+      let mut color = if t < 0.5 {
+        Vec3::lerp((1.0 - 2.0 * t).sqrt(), Vec3(0.9, 0.9, 0.85), Vec3(0.25, 0.45, 0.9))
+      } else {
+        Vec3(0.15, 0.12, 0.1)
+      };
+
+      // the angle between this pixel and the sun on the sphere
+      let delta_phi = 2.0 * std::f64::consts::PI * (s - sun_s);
+      let cosine = theta.cos() * sun_theta.cos() + theta.sin() * sun_theta.sin() * delta_phi.cos();
+      if cosine.clamp(-1.0, 1.0).acos().to_degrees() < SUN_RADIUS {
+        color = Vec3(1.0, 0.92, 0.8) * 500.0;
+      }
+
+      image.set(x, y, color);
+    }
+  }
+
+  image
+}
+
+fn spheres() -> HittableList {
+  let mut world = HittableList::new();
+
+  let mut add_sphere = |center: Vec3, radius: f64, material: Arc<dyn Material>| {
+    let mut sphere = Sphere::new(center, radius);
+    sphere.set_material(material);
+    world.add(Box::new(sphere));
+  };
+
+  add_sphere(
+    Vec3(0.0, -1000.0, 0.0),
+    1000.0,
+    Arc::new(Lambertian::new(Vec3(0.5, 0.5, 0.5))),
+  );
+  add_sphere(Vec3(-2.2, 1.0, 0.0), 1.0, Arc::new(Lambertian::new(Vec3(0.8, 0.3, 0.2))));
+  add_sphere(Vec3(0.0, 1.0, 0.0), 1.0, Arc::new(Dielectric::new(1.5)));
+  add_sphere(
+    Vec3(2.2, 1.0, 0.0),
+    1.0,
+    Arc::new(Metal::new(Vec3(0.9, 0.9, 0.9)).set_fuzz(0.02)),
+  );
+
+  world
+}
+
+pub fn run(options: &Options) -> io::Result<()> {
+  let (image_width, image_height) = options.image_size(384, 16.0 / 9.0);
+  let aspect_ratio = image_width as f64 / image_height as f64;
+
+  let samples_per_pixel = 64;
+  let max_depth = 50;
+
+  let camera = PositionalCamera::new(
+    Vec3(0.0, 2.0, 9.0),
+    Vec3(0.0, 0.9, 0.0),
+    Vec3(0.0, 1.0, 0.0),
+    35.0,
+    aspect_ratio,
+  );
+
+  let world = spheres();
+
+  let mut environment = EnvironmentMap::new(sky_map(512, 256));
+  environment.set_rotation(20.0);
+
+  // no lights in the world, the shadow rays only go to the sky
+  let mut renderer = Renderer::new(image_width, image_height);
+  renderer.set_samples_per_pixel(samples_per_pixel);
+  renderer.set_max_depth(max_depth);
+  renderer.set_background(Background::Environment(Arc::new(environment)));
+  renderer.set_integrator(Box::new(MisPathTracer::new(HittableList::new())));
+  options.configure(&mut renderer);
+
+  let start = Instant::now();
+
+  let framebuffer = options.render(&renderer, &world, &camera)?;
+
+  println!("Ray Tracing taked time: {:?}", start.elapsed());
+
+  options.save(&framebuffer, FILENAME, true)?;
+
+  Ok(())
+}
@@ -0,0 +1,78 @@
+use std::io;
+use std::sync::Arc;
+use std::time::Instant;
+
+use crate::background::Background;
+use crate::camera::PositionalCamera;
+use crate::cli::Options;
+use crate::demos::demo22::sky_map;
+use crate::environment::EnvironmentMap;
+use crate::hittable_list::HittableList;
+use crate::integrator::MisPathTracer;
+use crate::material::{Conductor, Lambertian, Material, RoughDielectric};
+use crate::renderer::Renderer;
+use crate::sphere::Sphere;
+use crate::vec3::Vec3;
+
+static FILENAME: &str = "dist/23.ppm";
+
+// gold, copper and aluminium from polished to brushed, and a frosted glass ball
+fn microfacet_spheres() -> HittableList {
+  let mut world = HittableList::new();
+
+  let mut add_sphere = |center: Vec3, radius: f64, material: Arc<dyn Material>| {
+    let mut sphere = Sphere::new(center, radius);
+    sphere.set_material(material);
+    world.add(Box::new(sphere));
+  };
+
+  add_sphere(
+    Vec3(0.0, -1000.0, 0.0),
+    1000.0,
+    Arc::new(Lambertian::new(Vec3(0.5, 0.5, 0.5))),
+  );
+  add_sphere(Vec3(-3.3, 1.0, 0.0), 1.0, Arc::new(Conductor::gold(0.15)));
+  add_sphere(Vec3(-1.1, 1.0, 0.0), 1.0, Arc::new(Conductor::copper(0.4)));
+  add_sphere(Vec3(1.1, 1.0, 0.0), 1.0, Arc::new(Conductor::aluminium(0.25)));
+  add_sphere(Vec3(3.3, 1.0, 0.0), 1.0, Arc::new(RoughDielectric::new(1.5, 0.3)));
+
+  world
+}
+
+pub fn run(options: &Options) -> io::Result<()> {
+  let (image_width, image_height) = options.image_size(480, 2.0);
+  let aspect_ratio = image_width as f64 / image_height as f64;
+
+  let samples_per_pixel = 64;
+  let max_depth = 50;
+
+  let camera = PositionalCamera::new(
+    Vec3(0.0, 2.0, 11.0),
+    Vec3(0.0, 0.9, 0.0),
+    Vec3(0.0, 1.0, 0.0),
+    30.0,
+    aspect_ratio,
+  );
+
+  let world = microfacet_spheres();
+
+  let mut environment = EnvironmentMap::new(sky_map(512, 256));
+  environment.set_rotation(20.0);
+
+  let mut renderer = Renderer::new(image_width, image_height);
+  renderer.set_samples_per_pixel(samples_per_pixel);
+  renderer.set_max_depth(max_depth);
+  renderer.set_background(Background::Environment(Arc::new(environment)));
+  renderer.set_integrator(Box::new(MisPathTracer::new(HittableList::new())));
+  options.configure(&mut renderer);
+
+  let start = Instant::now();
+
+  let framebuffer = options.render(&renderer, &world, &camera)?;
+
+  println!("Ray Tracing taked time: {:?}", start.elapsed());
+
+  options.save(&framebuffer, FILENAME, true)?;
+
+  Ok(())
+}
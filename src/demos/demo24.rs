@@ -0,0 +1,110 @@
+use std::io;
+use std::sync::Arc;
+use std::time::Instant;
+
+use crate::background::Background;
+use crate::camera::PositionalCamera;
+use crate::cli::Options;
+use crate::demos::demo22::sky_map;
+use crate::environment::EnvironmentMap;
+use crate::hittable_list::HittableList;
+use crate::integrator::MisPathTracer;
+use crate::material::{Lambertian, Material};
+use crate::principled::Principled;
+use crate::renderer::Renderer;
+use crate::sphere::Sphere;
+use crate::texture::CheckerTexture;
+use crate::vec3::Vec3;
+
+static FILENAME: &str = "dist/24.ppm";
+
+// one material for all of them: varnished plastic, velvet, brushed metal, tinted glass,
+// and paint chipped off a metal ball where a texture drives metallic and roughness
+fn principled_spheres() -> HittableList {
+  let mut world = HittableList::new();
+
+  let mut add_sphere = |center: Vec3, radius: f64, material: Arc<dyn Material>| {
+    let mut sphere = Sphere::new(center, radius);
+    sphere.set_material(material);
+    world.add(Box::new(sphere));
+  };
+
+  add_sphere(
+    Vec3(0.0, -1000.0, 0.0),
+    1000.0,
+    Arc::new(Lambertian::new(Vec3(0.5, 0.5, 0.5))),
+  );
+
+  let mut plastic = Principled::new(Vec3(0.1, 0.25, 0.7));
+  plastic.set_roughness(0.6);
+  plastic.set_clearcoat(1.0);
+  add_sphere(Vec3(-4.4, 1.0, 0.0), 1.0, Arc::new(plastic));
+
+  let mut velvet = Principled::new(Vec3(0.5, 0.05, 0.1));
+  velvet.set_roughness(1.0);
+  velvet.set_specular(0.1);
+  velvet.set_sheen(1.0);
+  add_sphere(Vec3(-2.2, 1.0, 0.0), 1.0, Arc::new(velvet));
+
+  let mut brass = Principled::new(Vec3(0.9, 0.7, 0.35));
+  brass.set_metallic(1.0);
+  brass.set_roughness(0.35);
+  add_sphere(Vec3(0.0, 1.0, 0.0), 1.0, Arc::new(brass));
+
+  let mut glass = Principled::new(Vec3(0.75, 0.95, 0.8));
+  glass.set_roughness(0.05);
+  glass.set_transmission(1.0);
+  add_sphere(Vec3(2.2, 1.0, 0.0), 1.0, Arc::new(glass));
+
+  // the same cells pick bare metal or yellow paint for every parameter
+  let patches = |metal: Vec3, paint: Vec3| {
+    let mut checker = CheckerTexture::from_colors(metal, paint);
+    checker.set_scale(4.0);
+    Arc::new(checker)
+  };
+  let mut chipped = Principled::from_texture(patches(Vec3(0.85, 0.85, 0.85), Vec3(0.9, 0.75, 0.1)));
+  chipped.set_metallic_texture(patches(Vec3::fill(1.0), Vec3::fill(0.0)));
+  chipped.set_roughness_texture(patches(Vec3::fill(0.2), Vec3::fill(0.5)));
+  chipped.set_clearcoat_texture(patches(Vec3::fill(0.0), Vec3::fill(1.0)));
+  add_sphere(Vec3(4.4, 1.0, 0.0), 1.0, Arc::new(chipped));
+
+  world
+}
+
+pub fn run(options: &Options) -> io::Result<()> {
+  let (image_width, image_height) = options.image_size(600, 2.5);
+  let aspect_ratio = image_width as f64 / image_height as f64;
+
+  let samples_per_pixel = 64;
+  let max_depth = 50;
+
+  let camera = PositionalCamera::new(
+    Vec3(0.0, 2.0, 13.0),
+    Vec3(0.0, 0.9, 0.0),
+    Vec3(0.0, 1.0, 0.0),
+    30.0,
+    aspect_ratio,
+  );
+
+  let world = principled_spheres();
+
+  let mut environment = EnvironmentMap::new(sky_map(512, 256));
+  environment.set_rotation(20.0);
+
+  let mut renderer = Renderer::new(image_width, image_height);
+  renderer.set_samples_per_pixel(samples_per_pixel);
+  renderer.set_max_depth(max_depth);
+  renderer.set_background(Background::Environment(Arc::new(environment)));
+  renderer.set_integrator(Box::new(MisPathTracer::new(HittableList::new())));
+  options.configure(&mut renderer);
+
+  let start = Instant::now();
+
+  let framebuffer = options.render(&renderer, &world, &camera)?;
+
+  println!("Ray Tracing taked time: {:?}", start.elapsed());
+
+  options.save(&framebuffer, FILENAME, true)?;
+
+  Ok(())
+}
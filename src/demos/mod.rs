@@ -1,3 +1,11 @@
+use std::io;
+use std::path::Path;
+use std::time::Instant;
+
+use crate::cli::Options;
+use crate::scene;
+use crate::utils;
+
 pub mod demo00;
 pub mod demo01;
 pub mod demo02;
@@ -14,3 +22,105 @@ pub mod demo12;
 pub mod demo13;
 pub mod demo14;
 pub mod demo15;
+pub mod demo16;
+pub mod demo17;
+pub mod demo18;
+pub mod demo19;
+pub mod demo20;
+pub mod demo21;
+pub mod demo22;
+pub mod demo23;
+pub mod demo24;
+
+pub struct Demo {
+  pub name: &'static str,
+  pub description: &'static str,
+  pub run: fn(&Options) -> io::Result<()>,
+}
+
+// the built-in scenes, a demo's index is its position here
+pub fn all() -> Vec<Demo> {
+  let demo = |name, description, run| Demo {
+    name,
+    description,
+    run,
+  };
+
+  vec![
+    demo("gradient", "a color gradient", demo00::run),
+    demo("gradient_vec3", "the same gradient filled pixel by pixel", demo01::run),
+    demo("sky", "rays through a viewport, showing the sky", demo02::run),
+    demo("red_sphere", "a flat red sphere", demo03::run),
+    demo("sphere_normals", "a sphere shaded by its normals", demo04::run),
+    demo("normal_world", "a hittable list shaded by normals", demo05::run),
+    demo("antialiasing", "the normal world with several samples per pixel", demo06::run),
+    demo("diffuse", "diffuse spheres lit by the sky", demo07::run),
+    demo("hemisphere", "diffuse spheres with hemisphere scattering", demo08::run),
+    demo("metal", "lambertian and metal spheres", demo09::run),
+    demo("glass", "fuzzy metal and a glass sphere", demo10::run),
+    demo("positional_camera", "a movable camera with a field of view", demo11::run),
+    demo("defocus_blur", "a lens camera with a wide aperture", demo12::run),
+    demo("random_spheres", "the final scene of the first book", demo13::run),
+    demo("random_spheres_hd", "the final scene at a higher resolution", demo14::run),
+    demo("motion_blur", "moving spheres in a BVH", demo15::run),
+    demo("area_light", "a room lit by an emissive sphere", demo16::run),
+    demo("textures", "checker, marble and image textures", demo17::run),
+    demo("mesh", "OBJ meshes and a triangle", demo18::run),
+    demo("scene_file", "the motion blur scene loaded from scenes/random.scene", demo19::run),
+    demo("cornell_box", "the Cornell box built from rectangles and boxes", demo20::run),
+    demo("cornell_smoke", "the Cornell box with blocks of smoke and fog", demo21::run),
+    demo("environment", "spheres lit by an environment map with a bright sun", demo22::run),
+    demo("microfacet", "rough gold, copper, aluminium and frosted glass", demo23::run),
+    demo("principled", "plastic, velvet, brass, glass and chipped paint from one material", demo24::run),
+  ]
+}
+
+// matches an index or a name
+pub fn find(name: &str) -> Option<Demo> {
+  all()
+    .into_iter()
+    .enumerate()
+    .find(|(index, demo)| demo.name == name || name.parse::<usize>() == Ok(*index))
+    .map(|(_, demo)| demo)
+}
+
+// renders a scene file with the command line overrides applied to its settings
+pub fn run_scene_file(path: &str, default_output: &str, options: &Options) -> io::Result<()> {
+  let mut scene = scene::load(path)?;
+
+  let settings = &mut scene.settings;
+  let aspect_ratio = settings.width as f64 / settings.height as f64;
+  let (width, height) = options.image_size(settings.width, aspect_ratio);
+  settings.width = width;
+  settings.height = height;
+  if let Some(seed) = options.seed {
+    settings.seed = seed;
+  }
+  utils::seed_random(settings.seed, 0);
+
+  let camera = scene.build_camera();
+  let world = scene.build_world()?;
+  let mut renderer = scene.build_renderer()?;
+  options.configure(&mut renderer);
+
+  let start = Instant::now();
+
+  println!("Ray Tracing start");
+
+  let framebuffer = options.render(&renderer, &world, camera.as_ref())?;
+
+  println!("Ray Tracing taked time: {:?}", start.elapsed());
+
+  options.save(&framebuffer, default_output, true)?;
+
+  Ok(())
+}
+
+// dist/<stem>.ppm for a scene file
+pub fn scene_file_output(path: &str) -> String {
+  let stem = Path::new(path)
+    .file_stem()
+    .and_then(|stem| stem.to_str())
+    .unwrap_or("scene");
+  format!("dist/{}.ppm", stem)
+}
@@ -0,0 +1,348 @@
+use crate::framebuffer::Framebuffer;
+use crate::vec3::Vec3;
+
+// the B3 spline the a-trous filter spreads further apart with every iteration
+const KERNEL: [f64; 5] = [1.0 / 16.0, 1.0 / 4.0, 3.0 / 8.0, 1.0 / 4.0, 1.0 / 16.0];
+
+// channels with less albedo keep their color instead of having it divided out
+const MIN_ALBEDO: f64 = 0.01;
+
+// what the camera sees in every pixel besides the light, row by row from the top like a
+// framebuffer. the denoiser keeps the edges these show
+#[derive(Debug, Clone)]
+pub struct FeatureBuffers {
+  pub albedo: Framebuffer,
+  // the average of the normals seen in a pixel, shorter where they disagree
+  pub normal: Framebuffer,
+  // the ray parameter t of the first hit, infinite where every sample missed
+  pub depth: Vec<f64>,
+}
+
+impl FeatureBuffers {
+  pub fn new(width: usize, height: usize) -> FeatureBuffers {
+    FeatureBuffers {
+      albedo: Framebuffer::new(width, height),
+      normal: Framebuffer::new(width, height),
+      depth: vec![f64::INFINITY; width * height],
+    }
+  }
+}
+
+// an edge-avoiding a-trous wavelet filter after Dammertz et al., "Edge-Avoiding A-Trous Wavelet
+// Transform for fast Global Illumination Filtering" (2010), with the color weights guided by
+// the luminance variance like in SVGF (Schied et al. 2017), estimated from the neighbours
+// since there is only one frame. the albedo is divided out first, so only the lighting is
+// blurred and textures stay sharp
+#[derive(Debug, Clone)]
+pub struct Denoiser {
+  iterations: usize,
+  color_sigma: f64,
+  normal_sigma: f64,
+  depth_sigma: f64,
+}
+
+impl Default for Denoiser {
+  fn default() -> Self {
+    Denoiser::new()
+  }
+}
+
+impl Denoiser {
+  pub fn new() -> Denoiser {
+    Denoiser {
+      iterations: 5,
+      color_sigma: 4.0,
+      normal_sigma: 16.0,
+      depth_sigma: 0.1,
+    }
+  }
+
+  // every iteration doubles the reach of the filter, 5 blur over about 60 pixels
+  pub fn set_iterations(&mut self, iterations: usize) {
+    self.iterations = iterations;
+  }
+
+  // how many standard deviations of noise count as the same color, more removes more noise
+  // and more of the shadows and highlights the features don't show
+  pub fn set_color_sigma(&mut self, color_sigma: f64) {
+    self.color_sigma = color_sigma;
+  }
+
+  // the power of the cosine between normals, more keeps sharper creases
+  pub fn set_normal_sigma(&mut self, normal_sigma: f64) {
+    self.normal_sigma = normal_sigma;
+  }
+
+  // the relative change in depth per pixel that still counts as the same surface
+  pub fn set_depth_sigma(&mut self, depth_sigma: f64) {
+    self.depth_sigma = depth_sigma;
+  }
+
+  pub fn denoise(&self, color: &Framebuffer, features: &FeatureBuffers) -> Framebuffer {
+    let (width, height) = (color.width, color.height);
+    let size = width * height;
+    let sizes = [features.albedo.pixels.len(), features.normal.pixels.len(), features.depth.len()];
+    if sizes.iter().any(|&length| length != size) {
+      panic!("Expected feature buffers of {}x{} pixels", width, height);
+    }
+
+    let albedo: Vec<Vec3> = features
+      .albedo
+      .pixels
+      .iter()
+      .map(|a| Vec3(divisor(a.x()), divisor(a.y()), divisor(a.z())))
+      .collect();
+    let normal: Vec<Vec3> = features
+      .normal
+      .pixels
+      .iter()
+      .map(|n| if n.len() > 0.0 { n.unit_vector() } else { *n })
+      .collect();
+    let guide = Guide {
+      width,
+      height,
+      normal: &normal,
+      depth: &features.depth,
+    };
+
+    let mut lighting: Vec<Vec3> = color
+      .pixels
+      .iter()
+      .zip(albedo.iter())
+      .map(|(c, a)| Vec3(c.x() / a.x(), c.y() / a.y(), c.z() / a.z()))
+      .collect();
+    let mut variance = self.estimate_variance(&guide, &lighting);
+
+    for iteration in 0..self.iterations {
+      let step = 1 << iteration;
+      let (filtered, filtered_variance) = self.filter(&guide, &lighting, &variance, step);
+      lighting = filtered;
+      variance = filtered_variance;
+    }
+
+    let mut framebuffer = Framebuffer::new(width, height);
+    for (index, pixel) in framebuffer.pixels.iter_mut().enumerate() {
+      *pixel = lighting[index] * albedo[index];
+    }
+    framebuffer
+  }
+
+  // the spread of the luminance among the neighbours on the same surface
+  fn estimate_variance(&self, guide: &Guide, lighting: &[Vec3]) -> Vec<f64> {
+    let mut variance = vec![0.0; lighting.len()];
+
+    for y in 0..guide.height {
+      for x in 0..guide.width {
+        let p = y * guide.width + x;
+        let (mut sum_weights, mut sum, mut sum_squares) = (0.0, 0.0, 0.0);
+
+        for (q, _) in guide.taps(x, y, 1) {
+          let weight = self.feature_weight(guide, p, q, 1);
+          let l = lighting[q].luminance();
+          sum_weights += weight;
+          sum += weight * l;
+          sum_squares += weight * l * l;
+        }
+
+        let mean = sum / sum_weights;
+        variance[p] = (sum_squares / sum_weights - mean * mean).max(0.0);
+      }
+    }
+
+    variance
+  }
+
+  fn filter(&self, guide: &Guide, lighting: &[Vec3], variance: &[f64], step: usize) -> (Vec<Vec3>, Vec<f64>) {
+    let mut filtered = vec![Vec3::fill(0.0); lighting.len()];
+    let mut filtered_variance = vec![0.0; lighting.len()];
+
+    for y in 0..guide.height {
+      for x in 0..guide.width {
+        let p = y * guide.width + x;
+        let l = lighting[p].luminance();
+        let sigma = self.color_sigma * blurred_variance(guide, variance, x, y).sqrt() + 1.0e-6;
+
+        let mut sum_weights = 0.0;
+        let mut sum = Vec3::fill(0.0);
+        let mut sum_variance = 0.0;
+
+        for (q, h) in guide.taps(x, y, step) {
+          let color_weight = (-(l - lighting[q].luminance()).abs() / sigma).exp();
+          let weight = h * color_weight * self.feature_weight(guide, p, q, step);
+          sum_weights += weight;
+          sum = sum + weight * lighting[q];
+          sum_variance += weight * weight * variance[q];
+        }
+
+        // the center tap always counts fully, so the weights never add up to 0
+        filtered[p] = sum / sum_weights;
+        filtered_variance[p] = sum_variance / (sum_weights * sum_weights);
+      }
+    }
+
+    (filtered, filtered_variance)
+  }
+
+  // how much pixel q looks like the same surface as pixel p, `step` pixels apart
+  fn feature_weight(&self, guide: &Guide, p: usize, q: usize, step: usize) -> f64 {
+    let (depth_p, depth_q) = (guide.depth[p], guide.depth[q]);
+    if depth_p.is_infinite() || depth_q.is_infinite() {
+      return if depth_p.is_infinite() && depth_q.is_infinite() {
+        1.0
+      } else {
+        0.0
+      };
+    }
+
+    let change = (depth_p - depth_q).abs() / (depth_p.min(depth_q) * step as f64).max(1.0e-9);
+    let depth_weight = (-change / self.depth_sigma).exp();
+    // mirrors and glass that only show the background leave no normal to compare
+    let (normal_p, normal_q) = (guide.normal[p], guide.normal[q]);
+    let normal_weight = if normal_p.length_squared() == 0.0 || normal_q.length_squared() == 0.0 {
+      1.0
+    } else {
+      normal_p.dot(normal_q).max(0.0).powf(self.normal_sigma)
+    };
+    depth_weight * normal_weight
+  }
+}
+
+// the features the filter follows, with normals of unit length or 0 where no surface was seen
+struct Guide<'a> {
+  width: usize,
+  height: usize,
+  normal: &'a [Vec3],
+  depth: &'a [f64],
+}
+
+impl<'a> Guide<'a> {
+  // the pixels of the 5x5 kernel around (x, y) spread `step` apart that lie inside the
+  // image, with their kernel weights
+  fn taps(&self, x: usize, y: usize, step: usize) -> impl Iterator<Item = (usize, f64)> + '_ {
+    let (x, y, step) = (x as i64, y as i64, step as i64);
+    (0..25).filter_map(move |tap| {
+      let (i, j) = (tap % 5, tap / 5);
+      let qx = x + (i - 2) * step;
+      let qy = y + (j - 2) * step;
+      if qx < 0 || qy < 0 || qx >= self.width as i64 || qy >= self.height as i64 {
+        return None;
+      }
+      let q = qy as usize * self.width + qx as usize;
+      Some((q, KERNEL[i as usize] * KERNEL[j as usize]))
+    })
+  }
+}
+
+// the variance under a 3x3 gaussian, steadier than a single pixel's
+fn blurred_variance(guide: &Guide, variance: &[f64], x: usize, y: usize) -> f64 {
+  const WEIGHTS: [f64; 3] = [0.25, 0.5, 0.25];
+  let (mut sum, mut sum_weights) = (0.0, 0.0);
+  for (j, weight_y) in WEIGHTS.iter().enumerate() {
+    for (i, weight_x) in WEIGHTS.iter().enumerate() {
+      let (qx, qy) = ((x + i).wrapping_sub(1), (y + j).wrapping_sub(1));
+      if qx < guide.width && qy < guide.height {
+        let weight = weight_x * weight_y;
+        sum += weight * variance[qy * guide.width + qx];
+        sum_weights += weight;
+      }
+    }
+  }
+  sum / sum_weights
+}
+
+fn divisor(albedo: f64) -> f64 {
+  if albedo > MIN_ALBEDO {
+    albedo
+  } else {
+    1.0
+  }
+}
+
+#[cfg(test)]
+mod tests {
+
+  use std::sync::Arc;
+
+  use super::*;
+  use crate::camera::PositionalCamera;
+  use crate::hittable_list::HittableList;
+  use crate::material::Lambertian;
+  use crate::renderer::Renderer;
+  use crate::sphere::Sphere;
+
+  fn mean_squared_error(a: &Framebuffer, b: &Framebuffer) -> f64 {
+    let sum: f64 = a
+      .pixels
+      .iter()
+      .zip(b.pixels.iter())
+      .map(|(x, y)| (*x - *y).length_squared())
+      .sum();
+    sum / a.pixels.len() as f64
+  }
+
+  #[test]
+  fn test_denoise_keeps_a_flat_image() {
+    let mut color = Framebuffer::new(16, 8);
+    let mut features = FeatureBuffers::new(16, 8);
+    for index in 0..16 * 8 {
+      color.pixels[index] = Vec3(0.2, 0.3, 0.4);
+      features.albedo.pixels[index] = Vec3(0.5, 0.5, 0.5);
+      features.normal.pixels[index] = Vec3(0.0, 0.0, 1.0);
+      features.depth[index] = 2.0;
+    }
+
+    let denoised = Denoiser::new().denoise(&color, &features);
+    assert!(mean_squared_error(&color, &denoised) < 1.0e-20);
+  }
+
+  #[test]
+  #[should_panic(expected = "Expected feature buffers of 16x8 pixels")]
+  fn test_denoise_checks_the_normal_size() {
+    let mut features = FeatureBuffers::new(16, 8);
+    features.normal = Framebuffer::new(8, 8);
+    Denoiser::new().denoise(&Framebuffer::new(16, 8), &features);
+  }
+
+  #[test]
+  fn test_denoise_moves_a_noisy_render_toward_the_reference() {
+    let mut ground = Sphere::new(Vec3(0.0, -100.5, -1.0), 100.0);
+    ground.set_material(Arc::new(Lambertian::new(Vec3(0.5, 0.6, 0.3))));
+    let mut sphere = Sphere::new(Vec3(0.0, 0.0, -1.0), 0.5);
+    sphere.set_material(Arc::new(Lambertian::new(Vec3(0.7, 0.3, 0.3))));
+    let mut world = HittableList::new();
+    world.add(Box::new(ground));
+    world.add(Box::new(sphere));
+    let camera = PositionalCamera::new(
+      Vec3(0.0, 0.5, 1.0),
+      Vec3(0.0, 0.0, -1.0),
+      Vec3(0.0, 1.0, 0.0),
+      60.0,
+      1.5,
+    );
+
+    let render = |samples_per_pixel: usize, seed: u64| {
+      let mut renderer = Renderer::new(48, 32);
+      renderer.set_samples_per_pixel(samples_per_pixel);
+      renderer.set_max_depth(8);
+      renderer.set_seed(seed);
+      // edges alias the same way at every sample count, so only the noise differs
+      renderer.set_antialias(false);
+      renderer
+    };
+
+    let reference = render(256, 1).render(&world, &camera);
+    let noisy_renderer = render(4, 2);
+    let noisy = noisy_renderer.render(&world, &camera);
+    let features = noisy_renderer.render_features(&world, &camera);
+    let denoised = Denoiser::new().denoise(&noisy, &features);
+
+    let noisy_error = mean_squared_error(&noisy, &reference);
+    let denoised_error = mean_squared_error(&denoised, &reference);
+    assert!(
+      denoised_error < noisy_error / 3.0,
+      "Expected denoising to cut the error {} at least by 3, but it is {}",
+      noisy_error,
+      denoised_error
+    );
+  }
+}
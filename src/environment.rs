@@ -0,0 +1,270 @@
+use std::f64::consts::PI;
+use std::io;
+use std::path::Path;
+
+use crate::framebuffer::Framebuffer;
+use crate::image;
+use crate::sampler::sample_2d;
+use crate::transform::Transform;
+use crate::vec3::Vec3;
+
+// a piecewise constant density over [0, 1) with one step per function value
+#[derive(Debug, Clone)]
+pub struct Distribution1d {
+  function: Vec<f64>,
+  cdf: Vec<f64>,
+  integral: f64,
+}
+
+impl Distribution1d {
+  // a function without any weight is sampled uniformly
+  pub fn new(function: Vec<f64>) -> Distribution1d {
+    let count = function.len().max(1);
+    let mut cdf = vec![0.0; count + 1];
+    for (i, value) in function.iter().enumerate() {
+      cdf[i + 1] = cdf[i] + value.max(0.0) / count as f64;
+    }
+
+    let integral = cdf[count];
+    for (i, value) in cdf.iter_mut().enumerate() {
+      *value = if integral > 0.0 {
+        *value / integral
+      } else {
+        i as f64 / count as f64
+      };
+    }
+
+    Distribution1d {
+      function,
+      cdf,
+      integral,
+    }
+  }
+
+  pub fn integral(&self) -> f64 {
+    self.integral
+  }
+
+  // maps u in [0, 1) to a point distributed like the function, with its density and step
+  pub fn sample(&self, u: f64) -> (f64, f64, usize) {
+    let count = self.cdf.len() - 1;
+    let index = (self.cdf.partition_point(|value| *value <= u).max(1) - 1).min(count - 1);
+
+    let width = self.cdf[index + 1] - self.cdf[index];
+    let offset = if width > 0.0 {
+      (u - self.cdf[index]) / width
+    } else {
+      0.0
+    };
+
+    ((index as f64 + offset) / count as f64, self.density(index), index)
+  }
+
+  // the density of every point in step index
+  pub fn density(&self, index: usize) -> f64 {
+    if self.integral > 0.0 {
+      self.function[index].max(0.0) / self.integral
+    } else {
+      1.0
+    }
+  }
+}
+
+// a piecewise constant density over the unit square, picking a row first and then a column in it
+#[derive(Debug, Clone)]
+pub struct Distribution2d {
+  rows: Vec<Distribution1d>,
+  marginal: Distribution1d,
+}
+
+impl Distribution2d {
+  // values are stored row by row, width of them per row
+  pub fn new(values: &[f64], width: usize) -> Distribution2d {
+    let rows: Vec<Distribution1d> = values
+      .chunks(width.max(1))
+      .map(|row| Distribution1d::new(row.to_vec()))
+      .collect();
+    let marginal = Distribution1d::new(rows.iter().map(|row| row.integral()).collect());
+    Distribution2d { rows, marginal }
+  }
+
+  // the point (x, y) of the square and its density
+  pub fn sample(&self, u: f64, v: f64) -> (f64, f64, f64) {
+    let (y, row_pdf, row) = self.marginal.sample(v);
+    let (x, column_pdf, _) = self.rows[row].sample(u);
+    (x, y, row_pdf * column_pdf)
+  }
+
+  pub fn pdf(&self, x: f64, y: f64) -> f64 {
+    let row_count = self.rows.len();
+    let row = ((y * row_count as f64) as usize).min(row_count - 1);
+    let columns = &self.rows[row];
+    let column_count = columns.function.len().max(1);
+    let column = ((x * column_count as f64) as usize).min(column_count - 1);
+    self.marginal.density(row) * columns.density(column)
+  }
+}
+
+// light arriving from every direction, stored as an equirectangular (latitude-longitude) image.
+// the top row looks straight up, the middle of the image looks down -z and x grows to the right.
+// directions are sampled in proportion to the brightness of the image, so small bright
+// sources like the sun are found by shadow rays instead of waiting for a bounce to hit them
+#[derive(Debug, Clone)]
+pub struct EnvironmentMap {
+  image: Framebuffer,
+  intensity: f64,
+  rotation: Transform,
+  distribution: Distribution2d,
+}
+
+impl EnvironmentMap {
+  // expects linear colors, like those of .hdr and .pfm files
+  pub fn new(image: Framebuffer) -> EnvironmentMap {
+    // rows near the poles cover less of the sphere than their pixel count suggests
+    let height = image.height.max(1) as f64;
+    let weights: Vec<f64> = image
+      .pixels
+      .iter()
+      .enumerate()
+      .map(|(index, color)| {
+        let row = (index / image.width.max(1)) as f64;
+        let theta = PI * (row + 0.5) / height;
+        color.luminance().max(0.0) * theta.sin()
+      })
+      .collect();
+    let distribution = Distribution2d::new(&weights, image.width);
+
+    EnvironmentMap {
+      image,
+      intensity: 1.0,
+      rotation: Transform::identity(),
+      distribution,
+    }
+  }
+
+  // a .hdr or .pfm file, see image::read
+  pub fn load<P: AsRef<Path>>(path: P) -> io::Result<EnvironmentMap> {
+    let image = image::read(path)?;
+    if image.width == 0 || image.height == 0 {
+      return Err(io::Error::new(
+        io::ErrorKind::InvalidData,
+        "Expected an environment map with at least one pixel".to_string(),
+      ));
+    }
+    Ok(EnvironmentMap::new(image))
+  }
+
+  // scales the light coming from the map
+  pub fn set_intensity(&mut self, intensity: f64) {
+    self.intensity = intensity;
+  }
+
+  // turns the map around the y axis
+  pub fn set_rotation(&mut self, degrees: f64) {
+    self.rotation = Transform::rotate_y(degrees);
+  }
+
+  // the point of the image a direction looks at, x to the right and y down, both in [0, 1]
+  fn image_point(&self, direction: Vec3) -> (f64, f64) {
+    let Vec3(x, y, z) = self.rotation.inverse().vector(direction).unit_vector();
+    let theta = y.clamp(-1.0, 1.0).acos();
+    let mut phi = (-x).atan2(z);
+    if phi < 0.0 {
+      phi += 2.0 * PI;
+    }
+    (phi / (2.0 * PI), theta / PI)
+  }
+
+  fn direction(&self, s: f64, t: f64) -> Vec3 {
+    let (sin_theta, cos_theta) = (PI * t).sin_cos();
+    let (sin_phi, cos_phi) = (2.0 * PI * s).sin_cos();
+    self
+      .rotation
+      .vector(Vec3(-sin_theta * sin_phi, cos_theta, sin_theta * cos_phi))
+  }
+
+  pub fn value(&self, direction: Vec3) -> Vec3 {
+    let (s, t) = self.image_point(direction);
+    let width = self.image.width;
+    let height = self.image.height;
+    let x = ((s * width as f64) as usize).min(width - 1);
+    let y = ((t * height as f64) as usize).min(height - 1);
+    self.intensity * self.image.get(x, y)
+  }
+
+  // density over solid angle of random picking direction
+  pub fn pdf_value(&self, direction: Vec3) -> f64 {
+    let (s, t) = self.image_point(direction);
+    let sin_theta = (PI * t).sin();
+    if sin_theta <= 0.0 {
+      return 0.0;
+    }
+    // the image covers 2 pi by pi radians of longitude and latitude
+    self.distribution.pdf(s, t) / (2.0 * PI * PI * sin_theta)
+  }
+
+  pub fn random(&self) -> Vec3 {
+    let (u, v) = sample_2d();
+    let (s, t, _) = self.distribution.sample(u, v);
+    self.direction(s, t)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+
+  use super::*;
+
+  #[test]
+  fn test_distribution_follows_function() {
+    let distribution = Distribution1d::new(vec![1.0, 0.0, 3.0]);
+    assert!((distribution.integral() - 4.0 / 3.0).abs() < 1.0e-9);
+
+    // a quarter of the samples land in the first step, the rest in the last
+    let (x, pdf, index) = distribution.sample(0.125);
+    assert_eq!(index, 0);
+    assert!((x - 1.0 / 6.0).abs() < 1.0e-9);
+    assert!((pdf - 0.75).abs() < 1.0e-9);
+    let (x, pdf, index) = distribution.sample(0.625);
+    assert_eq!(index, 2);
+    assert!((x - 5.0 / 6.0).abs() < 1.0e-9);
+    assert!((pdf - 2.25).abs() < 1.0e-9);
+  }
+
+  #[test]
+  fn test_environment_sampling_matches_pdf() {
+    // a dim sky with one bright pixel
+    let mut image = Framebuffer::new(16, 8);
+    for pixel in image.pixels.iter_mut() {
+      *pixel = Vec3::fill(0.1);
+    }
+    image.set(5, 2, Vec3::fill(50.0));
+    let mut environment = EnvironmentMap::new(image);
+    environment.set_rotation(30.0);
+
+    // image points and directions map onto each other
+    let direction = environment.direction(0.3, 0.6);
+    let (s, t) = environment.image_point(direction);
+    assert!((s - 0.3).abs() < 1.0e-9 && (t - 0.6).abs() < 1.0e-9);
+    assert!((environment.value(environment.direction(5.5 / 16.0, 2.5 / 8.0)) - Vec3::fill(50.0)).len() < 1.0e-9);
+
+    // the density integrates to 1 over the sphere
+    let steps = 400;
+    let mut integral = 0.0;
+    for i in 0..steps {
+      for j in 0..steps {
+        let s = (i as f64 + 0.5) / steps as f64;
+        let t = (j as f64 + 0.5) / steps as f64;
+        let solid_angle = 2.0 * PI * PI * (PI * t).sin() / (steps * steps) as f64;
+        integral += environment.pdf_value(environment.direction(s, t)) * solid_angle;
+      }
+    }
+    assert!((integral - 1.0).abs() < 1.0e-2);
+
+    // and most samples head for the bright pixel
+    let bright = (0..1000)
+      .filter(|_| environment.value(environment.random()).x() > 1.0)
+      .count();
+    assert!(bright > 800);
+  }
+}
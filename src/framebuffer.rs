@@ -0,0 +1,40 @@
+use std::io;
+use std::path::Path;
+
+use crate::image::{self, ImageFormat};
+use crate::vec3::Vec3;
+
+// linear colors stored row by row, starting from the top-left pixel
+#[derive(Debug, Clone)]
+pub struct Framebuffer {
+  pub width: usize,
+  pub height: usize,
+  pub pixels: Vec<Vec3>,
+}
+
+impl Framebuffer {
+  pub fn new(width: usize, height: usize) -> Framebuffer {
+    Framebuffer {
+      width,
+      height,
+      pixels: vec![Vec3::fill(0.0); width * height],
+    }
+  }
+
+  pub fn get(&self, x: usize, y: usize) -> Vec3 {
+    self.pixels[y * self.width + x]
+  }
+
+  pub fn set(&mut self, x: usize, y: usize, color: Vec3) {
+    self.pixels[y * self.width + x] = color;
+  }
+
+  // the format is picked by the extension, see image::write
+  pub fn save<P: AsRef<Path>>(&self, path: P, gamma_correct: bool) -> io::Result<()> {
+    image::write(path, self, gamma_correct)
+  }
+
+  pub fn save_as<P: AsRef<Path>>(&self, path: P, format: ImageFormat, gamma_correct: bool) -> io::Result<()> {
+    image::write_as(path, self, format, gamma_correct)
+  }
+}
@@ -1,24 +1,43 @@
+use crate::aabb::Aabb;
 use crate::material::{DefaultMaterial, Material};
 use crate::ray::Ray;
 use crate::vec3::Vec3;
 
-#[derive(Debug)]
-pub struct HitRecord {
+// what every shape has to say about the closest hit, borrowing the material of the shape it
+// came from so no hit ever allocates
+#[derive(Debug, Clone, Copy)]
+pub struct HitRecord<'a> {
   pub point: Vec3,
   pub normal: Vec3,
   pub t: f64,
+  // surface coordinates of the hit point
+  pub u: f64,
+  pub v: f64,
   pub front_face: bool,
-  pub material: Box<dyn Material>,
+  pub material: &'a dyn Material,
+  // the address of the object hit, set by the list or BVH holding it, 0 when unknown
+  pub object_id: usize,
 }
 
-impl HitRecord {
-  pub fn new() -> HitRecord {
+static DEFAULT_MATERIAL: DefaultMaterial = DefaultMaterial {};
+
+impl<'a> Default for HitRecord<'a> {
+  fn default() -> Self {
+    HitRecord::new()
+  }
+}
+
+impl<'a> HitRecord<'a> {
+  pub fn new() -> HitRecord<'a> {
     HitRecord {
       point: Vec3(0.0, 0.0, 0.0),
       normal: Vec3(0.0, 0.0, 0.0),
       t: 0.0,
+      u: 0.0,
+      v: 0.0,
       front_face: false,
-      material: Box::new(DefaultMaterial::new()),
+      material: &DEFAULT_MATERIAL,
+      object_id: 0,
     }
   }
   pub fn set_point(&mut self, point: Vec3) {
@@ -27,7 +46,11 @@ impl HitRecord {
   pub fn set_t(&mut self, t: f64) {
     self.t = t;
   }
-  pub fn set_material(&mut self, material: Box<dyn Material>) {
+  pub fn set_uv(&mut self, u: f64, v: f64) {
+    self.u = u;
+    self.v = v;
+  }
+  pub fn set_material(&mut self, material: &'a dyn Material) {
     self.material = material;
   }
   pub fn set_face_normal(&mut self, ray: &Ray, outward_normal: Vec3) {
@@ -43,6 +66,45 @@ impl HitRecord {
   }
 }
 
+// an id for an object that stays the same as long as the object does not move
+pub fn object_id(object: &dyn Hittable) -> usize {
+  object as *const dyn Hittable as *const () as usize
+}
+
+// hits object like object.hit, and marks the record with the object unless something inside
+// it already did. a miss leaves the record as it was
+pub fn hit_object<'a>(
+  object: &'a dyn Hittable,
+  ray: &Ray,
+  t_min: f64,
+  t_max: f64,
+  record: &mut HitRecord<'a>,
+) -> bool {
+  let previous = record.object_id;
+  record.object_id = 0;
+  if !object.hit(ray, t_min, t_max, record) {
+    record.object_id = previous;
+    return false;
+  }
+  if record.object_id == 0 {
+    record.object_id = object_id(object);
+  }
+  true
+}
+
 pub trait Hittable: Send + Sync {
-  fn hit(&self, ray: &Ray, t_min: f64, t_max: f64, record: &mut HitRecord) -> bool;
+  // the record borrows from self, so it can point at the material of whatever was hit
+  fn hit<'a>(&'a self, ray: &Ray, t_min: f64, t_max: f64, record: &mut HitRecord<'a>) -> bool;
+  // writes the box enclosing the object over the shutter interval [time0, time1],
+  // returns false for objects that cannot be bounded
+  fn bounding_box(&self, time0: f64, time1: f64, output_box: &mut Aabb) -> bool;
+  // density over solid angle, seen from origin, of `random` picking direction.
+  // 0 for objects that cannot be sampled, which the light sampling integrators skip
+  fn pdf_value(&self, _origin: Vec3, _direction: Vec3) -> f64 {
+    0.0
+  }
+  // a direction from origin toward a random point of the object
+  fn random(&self, _origin: Vec3) -> Vec3 {
+    Vec3(1.0, 0.0, 0.0)
+  }
 }
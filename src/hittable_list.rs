@@ -1,11 +1,20 @@
 #![allow(dead_code)]
-use crate::hittable::{HitRecord, Hittable};
+use crate::aabb::Aabb;
+use crate::hittable::{hit_object, HitRecord, Hittable};
 use crate::ray::Ray;
+use crate::sampler::sample_1d;
+use crate::vec3::Vec3;
 
 pub struct HittableList {
   pub objects: Vec<Box<dyn Hittable>>,
 }
 
+impl Default for HittableList {
+  fn default() -> Self {
+    HittableList::new()
+  }
+}
+
 impl HittableList {
   pub fn new() -> HittableList {
     HittableList { objects: vec![] }
@@ -21,13 +30,13 @@ impl HittableList {
 }
 
 impl Hittable for HittableList {
-  fn hit(&self, ray: &Ray, t_min: f64, t_max: f64, record: &mut HitRecord) -> bool {
+  fn hit<'a>(&'a self, ray: &Ray, t_min: f64, t_max: f64, record: &mut HitRecord<'a>) -> bool {
     let mut inner_record = HitRecord::new();
     let mut hit_anything = false;
     let mut closest_so_far = t_max;
 
     for object in &self.objects {
-      if object.hit(ray, t_min, closest_so_far, &mut inner_record) {
+      if hit_object(object.as_ref(), ray, t_min, closest_so_far, &mut inner_record) {
         hit_anything = true;
         closest_so_far = inner_record.t;
       }
@@ -39,4 +48,47 @@ impl Hittable for HittableList {
 
     hit_anything
   }
+
+  fn bounding_box(&self, time0: f64, time1: f64, output_box: &mut Aabb) -> bool {
+    if self.objects.is_empty() {
+      return false;
+    }
+
+    let mut temp_box = Aabb::empty();
+    let mut result_box = Aabb::empty();
+
+    for object in &self.objects {
+      if !object.bounding_box(time0, time1, &mut temp_box) {
+        return false;
+      }
+      result_box = Aabb::surrounding_box(result_box, temp_box);
+    }
+
+    *output_box = result_box;
+    true
+  }
+
+  // every object is picked with the same probability
+  fn pdf_value(&self, origin: Vec3, direction: Vec3) -> f64 {
+    if self.objects.is_empty() {
+      return 0.0;
+    }
+
+    let sum: f64 = self
+      .objects
+      .iter()
+      .map(|object| object.pdf_value(origin, direction))
+      .sum();
+    sum / self.objects.len() as f64
+  }
+
+  fn random(&self, origin: Vec3) -> Vec3 {
+    if self.objects.is_empty() {
+      return Vec3(1.0, 0.0, 0.0);
+    }
+
+    let count = self.objects.len();
+    let index = ((sample_1d() * count as f64) as usize).min(count - 1);
+    self.objects[index].random(origin)
+  }
 }
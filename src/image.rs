@@ -0,0 +1,669 @@
+use std::fs;
+use std::io;
+use std::io::Write;
+use std::path::Path;
+
+use crate::framebuffer::Framebuffer;
+use crate::utils::clamp;
+use crate::vec3::Vec3;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ImageFormat {
+  // binary P6
+  Ppm,
+  Png8,
+  Png16,
+  // Radiance RGBE
+  Hdr,
+  // portable float map
+  Pfm,
+}
+
+impl ImageFormat {
+  // .png picks the 8-bit variant
+  pub fn from_path<P: AsRef<Path>>(path: P) -> Option<ImageFormat> {
+    match extension(path.as_ref()).as_str() {
+      "ppm" => Some(ImageFormat::Ppm),
+      "png" => Some(ImageFormat::Png8),
+      "hdr" => Some(ImageFormat::Hdr),
+      "pfm" => Some(ImageFormat::Pfm),
+      _ => None,
+    }
+  }
+
+  pub fn from_name(name: &str) -> Option<ImageFormat> {
+    match name {
+      "ppm" => Some(ImageFormat::Ppm),
+      "png" | "png8" => Some(ImageFormat::Png8),
+      "png16" => Some(ImageFormat::Png16),
+      "hdr" => Some(ImageFormat::Hdr),
+      "pfm" => Some(ImageFormat::Pfm),
+      _ => None,
+    }
+  }
+
+  pub fn extension(&self) -> &'static str {
+    match self {
+      ImageFormat::Ppm => "ppm",
+      ImageFormat::Png8 | ImageFormat::Png16 => "png",
+      ImageFormat::Hdr => "hdr",
+      ImageFormat::Pfm => "pfm",
+    }
+  }
+
+  // float formats keep the linear radiance, so they are never gamma corrected
+  pub fn is_high_dynamic_range(&self) -> bool {
+    matches!(self, ImageFormat::Hdr | ImageFormat::Pfm)
+  }
+}
+
+fn invalid_data(message: String) -> io::Error {
+  io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn extension(path: &Path) -> String {
+  path
+    .extension()
+    .and_then(|ext| ext.to_str())
+    .unwrap_or("")
+    .to_lowercase()
+}
+
+// reads an image into a framebuffer, the format is picked by the file extension.
+// .ppm and .png colors come back encoded (not linearized) in [0, 1], .hdr and .pfm
+// keep the linear values they store
+pub fn read<P: AsRef<Path>>(path: P) -> io::Result<Framebuffer> {
+  let path = path.as_ref();
+
+  match extension(path).as_str() {
+    "ppm" => read_ppm(&fs::read(path)?),
+    "png" => read_png(fs::File::open(path)?),
+    "hdr" => read_hdr(&fs::read(path)?),
+    "pfm" => read_pfm(&fs::read(path)?),
+    ext => Err(invalid_data(format!(
+      "Expected a .ppm, .png, .hdr or .pfm image, but received .{}",
+      ext
+    ))),
+  }
+}
+
+// writes a framebuffer of linear colors, the format is picked by the file extension
+pub fn write<P: AsRef<Path>>(path: P, framebuffer: &Framebuffer, gamma_correct: bool) -> io::Result<()> {
+  let path = path.as_ref();
+
+  match ImageFormat::from_path(path) {
+    Some(format) => write_as(path, framebuffer, format, gamma_correct),
+    None => Err(invalid_data(format!(
+      "Expected a .ppm, .png, .hdr or .pfm image, but received .{}",
+      extension(path)
+    ))),
+  }
+}
+
+pub fn write_as<P: AsRef<Path>>(
+  path: P,
+  framebuffer: &Framebuffer,
+  format: ImageFormat,
+  gamma_correct: bool,
+) -> io::Result<()> {
+  let mut writer = io::BufWriter::new(fs::File::create(path)?);
+  encode(&mut writer, framebuffer, format, gamma_correct)?;
+  writer.flush()
+}
+
+pub fn encode<W: Write>(
+  writer: &mut W,
+  framebuffer: &Framebuffer,
+  format: ImageFormat,
+  gamma_correct: bool,
+) -> io::Result<()> {
+  match format {
+    ImageFormat::Ppm => encode_ppm(writer, framebuffer, gamma_correct),
+    ImageFormat::Png8 => encode_png(writer, framebuffer, png::BitDepth::Eight, gamma_correct),
+    ImageFormat::Png16 => encode_png(writer, framebuffer, png::BitDepth::Sixteen, gamma_correct),
+    ImageFormat::Hdr => encode_hdr(writer, framebuffer),
+    ImageFormat::Pfm => encode_pfm(writer, framebuffer),
+  }
+}
+
+// gamma-correct for gamma=2.0, then map [0, 1] onto the integers [0, max]
+fn quantize(value: f64, max: u32, gamma_correct: bool) -> u32 {
+  let value = if gamma_correct { value.max(0.0).sqrt() } else { value };
+  ((max + 1) as f64 * clamp(value, 0.0, 1.0)).min(max as f64) as u32
+}
+
+fn encode_ppm<W: Write>(writer: &mut W, framebuffer: &Framebuffer, gamma_correct: bool) -> io::Result<()> {
+  write!(writer, "P6\n{} {}\n255\n", framebuffer.width, framebuffer.height)?;
+
+  let mut row = Vec::with_capacity(framebuffer.width * 3);
+  for line in framebuffer.pixels.chunks(framebuffer.width.max(1)) {
+    row.clear();
+    for color in line {
+      for value in [color.x(), color.y(), color.z()].iter() {
+        row.push(quantize(*value, 255, gamma_correct) as u8);
+      }
+    }
+    writer.write_all(&row)?;
+  }
+
+  Ok(())
+}
+
+fn encode_png<W: Write>(
+  writer: &mut W,
+  framebuffer: &Framebuffer,
+  bit_depth: png::BitDepth,
+  gamma_correct: bool,
+) -> io::Result<()> {
+  let mut encoder = png::Encoder::new(writer, framebuffer.width as u32, framebuffer.height as u32);
+  encoder.set_color(png::ColorType::Rgb);
+  encoder.set_depth(bit_depth);
+
+  let mut data = vec![];
+  for color in framebuffer.pixels.iter() {
+    for value in [color.x(), color.y(), color.z()].iter() {
+      match bit_depth {
+        // 16-bit samples are stored big-endian
+        png::BitDepth::Sixteen => data.extend_from_slice(&(quantize(*value, 65535, gamma_correct) as u16).to_be_bytes()),
+        _ => data.push(quantize(*value, 255, gamma_correct) as u8),
+      }
+    }
+  }
+
+  let mut png_writer = encoder
+    .write_header()
+    .map_err(|err| invalid_data(err.to_string()))?;
+  png_writer
+    .write_image_data(&data)
+    .map_err(|err| invalid_data(err.to_string()))
+}
+
+// a shared exponent with an 8-bit mantissa per channel
+fn to_rgbe(color: Vec3) -> [u8; 4] {
+  let Vec3(r, g, b) = color;
+  let (r, g, b) = (r.max(0.0), g.max(0.0), b.max(0.0));
+  let max = r.max(g).max(b);
+
+  if max < 1.0e-32 {
+    return [0, 0, 0, 0];
+  }
+
+  // max = mantissa * 2^exponent with mantissa in [0.5, 1)
+  let mut exponent = max.log2().floor() as i32 + 1;
+  if max / 2f64.powi(exponent) >= 1.0 {
+    exponent += 1;
+  }
+  let scale = 256.0 / 2f64.powi(exponent);
+
+  [
+    (r * scale) as u8,
+    (g * scale) as u8,
+    (b * scale) as u8,
+    (exponent + 128) as u8,
+  ]
+}
+
+// flat scanlines, which every RGBE reader accepts next to the run length encoded ones
+fn encode_hdr<W: Write>(writer: &mut W, framebuffer: &Framebuffer) -> io::Result<()> {
+  write!(
+    writer,
+    "#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {} +X {}\n",
+    framebuffer.height, framebuffer.width
+  )?;
+
+  let mut row = Vec::with_capacity(framebuffer.width * 4);
+  for line in framebuffer.pixels.chunks(framebuffer.width.max(1)) {
+    row.clear();
+    for color in line {
+      row.extend_from_slice(&to_rgbe(*color));
+    }
+    writer.write_all(&row)?;
+  }
+
+  Ok(())
+}
+
+// little-endian floats, a negative scale marks the byte order, rows run from the bottom up
+fn encode_pfm<W: Write>(writer: &mut W, framebuffer: &Framebuffer) -> io::Result<()> {
+  write!(writer, "PF\n{} {}\n-1.0\n", framebuffer.width, framebuffer.height)?;
+
+  let mut row = Vec::with_capacity(framebuffer.width * 12);
+  for line in framebuffer.pixels.chunks(framebuffer.width.max(1)).rev() {
+    row.clear();
+    for color in line {
+      for value in [color.x(), color.y(), color.z()].iter() {
+        row.extend_from_slice(&(*value as f32).to_le_bytes());
+      }
+    }
+    writer.write_all(&row)?;
+  }
+
+  Ok(())
+}
+
+struct PpmReader<'a> {
+  bytes: &'a [u8],
+  offset: usize,
+}
+
+impl<'a> PpmReader<'a> {
+  fn skip_whitespace_and_comments(&mut self) {
+    while self.offset < self.bytes.len() {
+      let byte = self.bytes[self.offset];
+      if byte == b'#' {
+        while self.offset < self.bytes.len() && self.bytes[self.offset] != b'\n' {
+          self.offset += 1;
+        }
+      } else if byte.is_ascii_whitespace() {
+        self.offset += 1;
+      } else {
+        break;
+      }
+    }
+  }
+
+  fn token(&mut self) -> io::Result<&'a str> {
+    self.skip_whitespace_and_comments();
+    let start = self.offset;
+    while self.offset < self.bytes.len() && !self.bytes[self.offset].is_ascii_whitespace() {
+      self.offset += 1;
+    }
+    if start == self.offset {
+      return Err(invalid_data("Unexpected end of PPM data".to_string()));
+    }
+    std::str::from_utf8(&self.bytes[start..self.offset])
+      .map_err(|_| invalid_data("Expected ASCII PPM header".to_string()))
+  }
+
+  fn number(&mut self) -> io::Result<usize> {
+    let token = self.token()?;
+    token
+      .parse::<usize>()
+      .map_err(|_| invalid_data(format!("Expected a number in PPM data, but received {}", token)))
+  }
+}
+
+pub fn read_ppm(bytes: &[u8]) -> io::Result<Framebuffer> {
+  let mut reader = PpmReader { bytes, offset: 0 };
+  let magic = reader.token()?;
+
+  if magic != "P3" && magic != "P6" {
+    return Err(invalid_data(format!(
+      "Expected a P3 or P6 PPM image, but received {}",
+      magic
+    )));
+  }
+
+  let width = reader.number()?;
+  let height = reader.number()?;
+  let max_value = reader.number()?;
+
+  if max_value == 0 || max_value > 65535 {
+    return Err(invalid_data(format!("Invalid PPM max value {}", max_value)));
+  }
+
+  let scale = 1.0 / (max_value as f64);
+  let unexpected_end = || invalid_data("Unexpected end of PPM data".to_string());
+  // headers are not trusted: every sample takes at least a byte, so the remaining data bounds
+  // the size before anything is allocated
+  let count = pixel_bytes(width, height, 3).ok_or_else(unexpected_end)?;
+  if count > bytes.len() - reader.offset {
+    return Err(unexpected_end());
+  }
+  let mut samples = Vec::with_capacity(count);
+
+  if magic == "P3" {
+    for _ in 0..count {
+      samples.push(reader.number()? as f64 * scale);
+    }
+  } else {
+    // exactly one whitespace byte separates the header from the binary raster
+    let start = reader.offset + 1;
+    let bytes_per_sample = if max_value < 256 { 1 } else { 2 };
+    let end = start + count * bytes_per_sample;
+
+    if end > bytes.len() {
+      return Err(unexpected_end());
+    }
+
+    for chunk in bytes[start..end].chunks(bytes_per_sample) {
+      let value = if bytes_per_sample == 1 {
+        chunk[0] as usize
+      } else {
+        ((chunk[0] as usize) << 8) | chunk[1] as usize
+      };
+      samples.push(value as f64 * scale);
+    }
+  }
+
+  Ok(from_samples(width, height, &samples, 3))
+}
+
+pub fn read_png<R: io::Read>(reader: R) -> io::Result<Framebuffer> {
+  let mut decoder = png::Decoder::new(reader);
+  decoder.set_transformations(png::Transformations::EXPAND);
+  let mut reader = decoder
+    .read_info()
+    .map_err(|err| invalid_data(err.to_string()))?;
+  let mut buffer = vec![0; reader.output_buffer_size()];
+  let info = reader
+    .next_frame(&mut buffer)
+    .map_err(|err| invalid_data(err.to_string()))?;
+
+  let width = info.width as usize;
+  let height = info.height as usize;
+  let channels = info.color_type.samples();
+  let bytes = &buffer[..info.buffer_size()];
+
+  let samples: Vec<f64> = match info.bit_depth {
+    png::BitDepth::Sixteen => bytes
+      .chunks(2)
+      .map(|chunk| (((chunk[0] as u32) << 8) | chunk[1] as u32) as f64 / 65535.0)
+      .collect(),
+    _ => bytes.iter().map(|byte| *byte as f64 / 255.0).collect(),
+  };
+
+  Ok(from_samples(width, height, &samples, channels))
+}
+
+fn from_rgbe(rgbe: &[u8]) -> Vec3 {
+  if rgbe[3] == 0 {
+    return Vec3::fill(0.0);
+  }
+
+  // the middle of the range each mantissa was truncated from
+  let scale = 2f64.powi(rgbe[3] as i32 - 128 - 8);
+  Vec3(
+    (rgbe[0] as f64 + 0.5) * scale,
+    (rgbe[1] as f64 + 0.5) * scale,
+    (rgbe[2] as f64 + 0.5) * scale,
+  )
+}
+
+// Radiance RGBE with flat or run length encoded scanlines, in the usual -Y height +X width orientation
+pub fn read_hdr(bytes: &[u8]) -> io::Result<Framebuffer> {
+  let unexpected_end = || invalid_data("Unexpected end of HDR data".to_string());
+  let mut offset = 0;
+  let mut next_line = || -> io::Result<&[u8]> {
+    let start = offset;
+    let length = bytes[start..].iter().position(|byte| *byte == b'\n').ok_or_else(unexpected_end)?;
+    offset = start + length + 1;
+    Ok(&bytes[start..start + length])
+  };
+
+  if !next_line()?.starts_with(b"#?") {
+    return Err(invalid_data("Expected a #?RADIANCE HDR image".to_string()));
+  }
+
+  // the header ends with an empty line
+  loop {
+    let line = next_line()?;
+    if line.is_empty() {
+      break;
+    }
+    if line.starts_with(b"FORMAT=") && line != b"FORMAT=32-bit_rle_rgbe" {
+      return Err(invalid_data(format!(
+        "Expected 32-bit_rle_rgbe HDR data, but received {}",
+        String::from_utf8_lossy(&line[7..])
+      )));
+    }
+  }
+
+  let resolution = String::from_utf8_lossy(next_line()?).into_owned();
+  let parts: Vec<&str> = resolution.split_whitespace().collect();
+  let (height, width) = match parts.as_slice() {
+    ["-Y", height, "+X", width] => match (height.parse::<usize>(), width.parse::<usize>()) {
+      (Ok(height), Ok(width)) => (height, width),
+      _ => (0, 0),
+    },
+    _ => (0, 0),
+  };
+  if width == 0 || height == 0 {
+    return Err(invalid_data(format!(
+      "Expected a -Y <height> +X <width> HDR resolution, but received {}",
+      resolution
+    )));
+  }
+
+  // a run-length encoded scanline takes at least 2 bytes per channel for every 127 pixels,
+  // which bounds the size before the framebuffer is allocated
+  let row_bytes = if (8..0x8000).contains(&width) {
+    4 + 8 * width.div_ceil(127)
+  } else {
+    width.saturating_mul(4)
+  };
+  match row_bytes.checked_mul(height) {
+    Some(size) if size <= bytes.len() - offset => {}
+    _ => return Err(unexpected_end()),
+  }
+
+  let mut framebuffer = Framebuffer::new(width, height);
+  let mut scanline = vec![0u8; width * 4];
+
+  for y in 0..height {
+    let encoded = (8..0x8000).contains(&width)
+      && bytes.len() >= offset + 4
+      && bytes[offset] == 2
+      && bytes[offset + 1] == 2
+      && ((bytes[offset + 2] as usize) << 8 | bytes[offset + 3] as usize) == width;
+
+    if encoded {
+      offset += 4;
+      // each channel is stored on its own as runs of one repeated byte or literal bytes
+      for channel in 0..4 {
+        let mut x = 0;
+        while x < width {
+          let count = *bytes.get(offset).ok_or_else(unexpected_end)? as usize;
+          offset += 1;
+          let (length, repeat) = if count > 128 { (count - 128, true) } else { (count, false) };
+          if length == 0 || x + length > width {
+            return Err(invalid_data("Invalid run length in HDR data".to_string()));
+          }
+          for i in 0..length {
+            let index = if repeat { offset } else { offset + i };
+            scanline[(x + i) * 4 + channel] = *bytes.get(index).ok_or_else(unexpected_end)?;
+          }
+          offset += if repeat { 1 } else { length };
+          x += length;
+        }
+      }
+    } else {
+      let end = offset + width * 4;
+      if end > bytes.len() {
+        return Err(unexpected_end());
+      }
+      scanline.copy_from_slice(&bytes[offset..end]);
+      offset = end;
+    }
+
+    for (x, rgbe) in scanline.chunks(4).enumerate() {
+      framebuffer.set(x, y, from_rgbe(rgbe));
+    }
+  }
+
+  Ok(framebuffer)
+}
+
+// color (PF) or grayscale (Pf) float maps, the sign of the scale gives the byte order
+pub fn read_pfm(bytes: &[u8]) -> io::Result<Framebuffer> {
+  let mut reader = PpmReader { bytes, offset: 0 };
+  let magic = reader.token()?;
+  let channels = match magic {
+    "PF" => 3,
+    "Pf" => 1,
+    _ => {
+      return Err(invalid_data(format!(
+        "Expected a PF or Pf float map, but received {}",
+        magic
+      )))
+    }
+  };
+
+  let width = reader.number()?;
+  let height = reader.number()?;
+  let scale = reader.token()?;
+  let little_endian = match scale.parse::<f64>() {
+    Ok(scale) if scale != 0.0 => scale < 0.0,
+    _ => return Err(invalid_data(format!("Invalid PFM scale {}", scale))),
+  };
+
+  let start = reader.offset + 1;
+  let end = pixel_bytes(width, height, channels * 4).and_then(|size| start.checked_add(size));
+  let end = match end {
+    Some(end) if end <= bytes.len() => end,
+    _ => return Err(invalid_data("Unexpected end of PFM data".to_string())),
+  };
+
+  let mut samples: Vec<f64> = bytes[start..end]
+    .chunks(4)
+    .map(|chunk| {
+      let chunk = [chunk[0], chunk[1], chunk[2], chunk[3]];
+      if little_endian {
+        f32::from_le_bytes(chunk) as f64
+      } else {
+        f32::from_be_bytes(chunk) as f64
+      }
+    })
+    .collect();
+
+  // rows are stored from the bottom up
+  let row = width * channels;
+  if row > 0 {
+    let rows: Vec<Vec<f64>> = samples.chunks(row).rev().map(|line| line.to_vec()).collect();
+    samples = rows.concat();
+  }
+
+  Ok(from_samples(width, height, &samples, channels))
+}
+
+// the size of a width x height raster, None when it does not fit in memory
+fn pixel_bytes(width: usize, height: usize, bytes_per_pixel: usize) -> Option<usize> {
+  width.checked_mul(height)?.checked_mul(bytes_per_pixel)
+}
+
+// grayscale images repeat their single channel, alpha channels are dropped
+fn from_samples(width: usize, height: usize, samples: &[f64], channels: usize) -> Framebuffer {
+  let mut framebuffer = Framebuffer::new(width, height);
+
+  for (pixel, chunk) in framebuffer.pixels.iter_mut().zip(samples.chunks(channels)) {
+    *pixel = if channels < 3 {
+      Vec3::fill(chunk[0])
+    } else {
+      Vec3(chunk[0], chunk[1], chunk[2])
+    };
+  }
+
+  framebuffer
+}
+
+#[cfg(test)]
+mod tests {
+
+  use super::*;
+
+  #[test]
+  fn test_read_ascii_and_binary_ppm() {
+    let ascii = b"P3\n# comment\n2 1\n255\n255 0 0  0 0 255\n";
+    let image = read_ppm(ascii).unwrap();
+    assert_eq!((image.width, image.height), (2, 1));
+    assert!(image.get(0, 0).eq(Vec3(1.0, 0.0, 0.0)));
+    assert!(image.get(1, 0).eq(Vec3(0.0, 0.0, 1.0)));
+
+    let mut binary = b"P6 2 1 255\n".to_vec();
+    binary.extend_from_slice(&[255, 0, 0, 0, 0, 255]);
+    let image = read_ppm(&binary).unwrap();
+    assert!(image.get(0, 0).eq(Vec3(1.0, 0.0, 0.0)));
+    assert!(image.get(1, 0).eq(Vec3(0.0, 0.0, 1.0)));
+  }
+
+  fn gradient() -> Framebuffer {
+    let mut image = Framebuffer::new(3, 2);
+    image.set(0, 0, Vec3(1.0, 0.0, 0.0));
+    image.set(1, 0, Vec3(0.0, 0.25, 0.0));
+    image.set(2, 1, Vec3(0.0, 0.0, 4.0));
+    image
+  }
+
+  #[test]
+  fn test_binary_ppm_and_png_round_trip() {
+    let image = gradient();
+
+    for format in [ImageFormat::Ppm, ImageFormat::Png8, ImageFormat::Png16].iter() {
+      let mut bytes = vec![];
+      encode(&mut bytes, &image, *format, true).unwrap();
+      let decoded = match format {
+        ImageFormat::Ppm => read_ppm(&bytes).unwrap(),
+        _ => read_png(&bytes[..]).unwrap(),
+      };
+
+      assert_eq!((decoded.width, decoded.height), (3, 2));
+      assert!((decoded.get(0, 0).x() - 1.0).abs() < 1.0e-9);
+      // gamma corrected, and clamped to 1 on the way out
+      assert!((decoded.get(1, 0).y() - 0.5).abs() < 1.0 / 255.0);
+      assert!((decoded.get(2, 1).z() - 1.0).abs() < 1.0e-9);
+    }
+  }
+
+  #[test]
+  fn test_float_formats_keep_high_dynamic_range() {
+    let image = gradient();
+
+    let mut bytes = vec![];
+    encode(&mut bytes, &image, ImageFormat::Pfm, true).unwrap();
+    let header = b"PF\n3 2\n-1.0\n";
+    assert!(bytes.starts_with(header));
+    assert_eq!(bytes.len(), header.len() + 3 * 2 * 12);
+    // the first row written is the bottom one, its last pixel holds the blue 4.0
+    let last = &bytes[header.len() + 2 * 12 + 8..header.len() + 3 * 12];
+    assert_eq!(f32::from_le_bytes([last[0], last[1], last[2], last[3]]), 4.0);
+
+    let rgbe = to_rgbe(Vec3(0.0, 0.0, 4.0));
+    let scale = 2f64.powi(rgbe[3] as i32 - 128 - 8);
+    assert!((rgbe[2] as f64 * scale - 4.0).abs() < 1.0e-9);
+    assert_eq!(to_rgbe(Vec3::fill(0.0)), [0, 0, 0, 0]);
+  }
+
+  #[test]
+  fn test_read_hdr_and_pfm() {
+    let image = gradient();
+
+    let mut bytes = vec![];
+    encode(&mut bytes, &image, ImageFormat::Pfm, true).unwrap();
+    let decoded = read_pfm(&bytes).unwrap();
+    assert_eq!((decoded.width, decoded.height), (3, 2));
+    assert!(decoded.get(2, 1).eq(Vec3(0.0, 0.0, 4.0)));
+    assert!(decoded.get(1, 0).eq(Vec3(0.0, 0.25, 0.0)));
+
+    let mut bytes = vec![];
+    encode(&mut bytes, &image, ImageFormat::Hdr, true).unwrap();
+    let decoded = read_hdr(&bytes).unwrap();
+    assert!((decoded.get(2, 1) - Vec3(0.0, 0.0, 4.0)).len() < 4.0 / 128.0);
+    assert!((decoded.get(0, 0) - Vec3(1.0, 0.0, 0.0)).len() < 1.0 / 128.0);
+
+    // one run length encoded scanline of 8 pixels, red repeats, green is literal
+    let mut encoded = b"#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y 1 +X 8\n".to_vec();
+    encoded.extend_from_slice(&[2, 2, 0, 8]);
+    encoded.extend_from_slice(&[136, 128]);
+    encoded.extend_from_slice(&[8, 0, 16, 32, 48, 64, 80, 96, 112]);
+    encoded.extend_from_slice(&[136, 0]);
+    encoded.extend_from_slice(&[136, 129]);
+    let decoded = read_hdr(&encoded).unwrap();
+    assert_eq!((decoded.width, decoded.height), (8, 1));
+    assert!((decoded.get(0, 0).x() - 1.0).abs() < 0.01);
+    assert!((decoded.get(7, 0).y() - 0.875).abs() < 0.01);
+  }
+
+  #[test]
+  fn test_read_ppm_rejects_other_formats() {
+    assert!(read_ppm(b"P5\n1 1\n255\n0").is_err());
+    assert!(read_ppm(b"P6\n2 2\n255\n\x00").is_err());
+  }
+
+  #[test]
+  fn test_huge_headers_are_rejected_before_allocating() {
+    assert!(read_ppm(b"P3 100000 100000 255 0 0 0").is_err());
+    assert!(read_ppm(format!("P6 {} {} 255\n\x00", usize::MAX, 3).as_bytes()).is_err());
+    assert!(read_pfm(format!("PF\n{} {}\n-1.0\n\x00", usize::MAX / 2, 5).as_bytes()).is_err());
+    assert!(read_hdr(b"#?RADIANCE\n\n-Y 100000 +X 100000\n\x02\x02").is_err());
+  }
+}
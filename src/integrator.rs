@@ -0,0 +1,376 @@
+
+use crate::background::Background;
+use crate::hittable::{HitRecord, Hittable};
+use crate::hittable_list::HittableList;
+use crate::material::Material;
+use crate::pdf::{power_heuristic, HittablePdf, Pdf};
+use crate::ray::Ray;
+use crate::vec3::Vec3;
+
+pub trait Integrator: Send + Sync {
+  fn ray_color(&self, ray: &Ray, world: &dyn Hittable, background: &Background, depth: u64) -> Vec3;
+}
+
+// follows scattered rays through the materials, collecting the light emitted along the way,
+// until they escape to the background
+pub struct PathTracer {}
+
+impl Default for PathTracer {
+  fn default() -> Self {
+    PathTracer::new()
+  }
+}
+
+impl PathTracer {
+  pub fn new() -> PathTracer {
+    PathTracer {}
+  }
+}
+
+impl Integrator for PathTracer {
+  fn ray_color(&self, ray: &Ray, world: &dyn Hittable, background: &Background, depth: u64) -> Vec3 {
+    if depth == 0 {
+      return Vec3::fill(0.0);
+    }
+
+    let mut record = HitRecord::new();
+
+    if !world.hit(ray, 0.001, f64::INFINITY, &mut record) {
+      return background.value(ray);
+    }
+
+    let material = record.material;
+    let emitted = material.emitted(record.u, record.v, record.point);
+
+    match material.scatter(ray, &record) {
+      Some(scatter) => {
+        emitted + scatter.attenuation * self.ray_color(&scatter.scattered, world, background, depth - 1)
+      }
+      None => emitted,
+    }
+  }
+}
+
+// a path tracer that also sends a shadow ray toward a random point of the lights at every
+// diffuse bounce. light the bounce itself runs into is not counted again when the lights
+// could have picked that direction
+pub struct NextEventPathTracer {
+  lights: HittableList,
+}
+
+impl NextEventPathTracer {
+  // the lights are only sampled, the world needs its own copies of them to hit
+  pub fn new(lights: HittableList) -> NextEventPathTracer {
+    NextEventPathTracer { lights }
+  }
+
+  // count_lights is false after a diffuse bounce, whose shadow ray already collected the lights
+  fn trace(
+    &self,
+    ray: &Ray,
+    world: &dyn Hittable,
+    background: &Background,
+    depth: u64,
+    count_lights: bool,
+  ) -> Vec3 {
+    if depth == 0 {
+      return Vec3::fill(0.0);
+    }
+
+    let mut record = HitRecord::new();
+
+    if !world.hit(ray, 0.001, f64::INFINITY, &mut record) {
+      return background.value(ray);
+    }
+
+    let material = record.material;
+    let mut emitted = material.emitted(record.u, record.v, record.point);
+
+    if !count_lights && self.lights.pdf_value(ray.origin, ray.direction) > 0.0 {
+      emitted = Vec3::fill(0.0);
+    }
+
+    let scatter = match material.scatter(ray, &record) {
+      Some(scatter) => scatter,
+      None => return emitted,
+    };
+    let (attenuation, scattered) = (scatter.attenuation, scatter.scattered);
+
+    if !material.is_diffuse() {
+      return emitted + attenuation * self.trace(&scattered, world, background, depth - 1, true);
+    }
+
+    let direct = self.direct_light(ray, world, &record, material);
+    emitted + direct + attenuation * self.trace(&scattered, world, background, depth - 1, false)
+  }
+
+  // light reaching the hit point from one sampled point of the lights
+  fn direct_light(
+    &self,
+    ray: &Ray,
+    world: &dyn Hittable,
+    record: &HitRecord,
+    material: &dyn Material,
+  ) -> Vec3 {
+    let direction = self.lights.random(record.point);
+    let pdf = self.lights.pdf_value(record.point, direction);
+    if pdf <= 0.0 {
+      return Vec3::fill(0.0);
+    }
+
+    let brdf_cosine = material.evaluate(ray, record, direction);
+    if brdf_cosine.length_squared() == 0.0 {
+      return Vec3::fill(0.0);
+    }
+
+    // whatever the shadow ray hits first, an occluder gives off no light
+    let mut shadow_ray = Ray::new(record.point, direction);
+    shadow_ray.time = ray.time;
+    let mut light_record = HitRecord::new();
+    if !world.hit(&shadow_ray, 0.001, f64::INFINITY, &mut light_record) {
+      return Vec3::fill(0.0);
+    }
+
+    let emitted = light_record
+      .material
+      .emitted(light_record.u, light_record.v, light_record.point);
+    brdf_cosine * emitted / pdf
+  }
+}
+
+impl Integrator for NextEventPathTracer {
+  fn ray_color(&self, ray: &Ray, world: &dyn Hittable, background: &Background, depth: u64) -> Vec3 {
+    self.trace(ray, world, background, depth, true)
+  }
+}
+
+// finds the lights two ways at every diffuse bounce: a shadow ray toward a point sampled on
+// the lights, and the scattered ray running into one. both are weighted by the power heuristic,
+// so glossy bounces toward large lights and diffuse bounces toward small ones are both cheap.
+// an environment map background is sampled the same way, next to the lights
+pub struct MisPathTracer {
+  lights: HittableList,
+}
+
+impl MisPathTracer {
+  // the lights are only sampled, the world needs its own copies of them to hit
+  pub fn new(lights: HittableList) -> MisPathTracer {
+    MisPathTracer { lights }
+  }
+
+  // scatter_pdf is the density the previous bounce picked this ray with,
+  // None for camera rays and mirror-like bounces that light sampling cannot reproduce
+  fn trace(
+    &self,
+    ray: &Ray,
+    world: &dyn Hittable,
+    background: &Background,
+    depth: u64,
+    scatter_pdf: Option<f64>,
+  ) -> Vec3 {
+    if depth == 0 {
+      return Vec3::fill(0.0);
+    }
+
+    let mut record = HitRecord::new();
+
+    if !world.hit(ray, 0.001, f64::INFINITY, &mut record) {
+      let color = background.value(ray);
+      return match scatter_pdf {
+        Some(scatter_pdf) if background.is_sampled() => {
+          color * power_heuristic(scatter_pdf, background.pdf_value(ray.direction))
+        }
+        _ => color,
+      };
+    }
+
+    let material = record.material;
+    let mut emitted = material.emitted(record.u, record.v, record.point);
+
+    if let Some(scatter_pdf) = scatter_pdf {
+      let light_pdf = self.lights.pdf_value(ray.origin, ray.direction);
+      emitted = emitted * power_heuristic(scatter_pdf, light_pdf);
+    }
+
+    let scatter = match material.scatter(ray, &record) {
+      Some(scatter) => scatter,
+      None => return emitted,
+    };
+    let (attenuation, scattered) = (scatter.attenuation, scatter.scattered);
+
+    if !material.is_diffuse() {
+      return emitted + attenuation * self.trace(&scattered, world, background, depth - 1, None);
+    }
+
+    let scatter_pdf = material.scattering_pdf(ray, &record, &scattered);
+    let direct = self.sample_lights(ray, world, &record, material)
+      + self.sample_background(ray, world, background, &record, material);
+    emitted + direct + attenuation * self.trace(&scattered, world, background, depth - 1, Some(scatter_pdf))
+  }
+
+  fn sample_lights(
+    &self,
+    ray: &Ray,
+    world: &dyn Hittable,
+    record: &HitRecord,
+    material: &dyn Material,
+  ) -> Vec3 {
+    let light_pdf = HittablePdf::new(&self.lights, record.point);
+    let direction = light_pdf.generate();
+    let pdf = light_pdf.value(direction);
+    if pdf <= 0.0 {
+      return Vec3::fill(0.0);
+    }
+
+    let brdf_cosine = material.evaluate(ray, record, direction);
+    if brdf_cosine.length_squared() == 0.0 {
+      return Vec3::fill(0.0);
+    }
+
+    let mut shadow_ray = Ray::new(record.point, direction);
+    shadow_ray.time = ray.time;
+    let mut light_record = HitRecord::new();
+    if !world.hit(&shadow_ray, 0.001, f64::INFINITY, &mut light_record) {
+      return Vec3::fill(0.0);
+    }
+
+    let emitted = light_record
+      .material
+      .emitted(light_record.u, light_record.v, light_record.point);
+    let scatter_pdf = material.scattering_pdf(ray, record, &shadow_ray);
+    brdf_cosine * emitted * (power_heuristic(pdf, scatter_pdf) / pdf)
+  }
+
+  // like sample_lights, for an environment the shadow ray has to escape to
+  fn sample_background(
+    &self,
+    ray: &Ray,
+    world: &dyn Hittable,
+    background: &Background,
+    record: &HitRecord,
+    material: &dyn Material,
+  ) -> Vec3 {
+    if !background.is_sampled() {
+      return Vec3::fill(0.0);
+    }
+
+    let direction = background.random();
+    let pdf = background.pdf_value(direction);
+    if pdf <= 0.0 {
+      return Vec3::fill(0.0);
+    }
+
+    let brdf_cosine = material.evaluate(ray, record, direction);
+    if brdf_cosine.length_squared() == 0.0 {
+      return Vec3::fill(0.0);
+    }
+
+    let mut shadow_ray = Ray::new(record.point, direction);
+    shadow_ray.time = ray.time;
+    if world.hit(&shadow_ray, 0.001, f64::INFINITY, &mut HitRecord::new()) {
+      return Vec3::fill(0.0);
+    }
+
+    let scatter_pdf = material.scattering_pdf(ray, record, &shadow_ray);
+    brdf_cosine * background.value(&shadow_ray) * (power_heuristic(pdf, scatter_pdf) / pdf)
+  }
+}
+
+impl Integrator for MisPathTracer {
+  fn ray_color(&self, ray: &Ray, world: &dyn Hittable, background: &Background, depth: u64) -> Vec3 {
+    self.trace(ray, world, background, depth, None)
+  }
+}
+
+// shades the first hit by its normal, mapped from [-1, 1] to [0, 1]
+pub struct NormalIntegrator {}
+
+impl Default for NormalIntegrator {
+  fn default() -> Self {
+    NormalIntegrator::new()
+  }
+}
+
+impl NormalIntegrator {
+  pub fn new() -> NormalIntegrator {
+    NormalIntegrator {}
+  }
+}
+
+impl Integrator for NormalIntegrator {
+  fn ray_color(&self, ray: &Ray, world: &dyn Hittable, background: &Background, _depth: u64) -> Vec3 {
+    let mut record = HitRecord::new();
+
+    if world.hit(ray, 0.0, f64::INFINITY, &mut record) {
+      0.5 * (record.normal + Vec3(1.0, 1.0, 1.0))
+    } else {
+      background.value(ray)
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+
+  use std::sync::Arc;
+
+  use super::*;
+  use crate::hittable_list::HittableList;
+  use crate::material::{DiffuseLight, Lambertian};
+  use crate::sphere::Sphere;
+
+  #[test]
+  fn test_path_tracer_sees_emitted_light() {
+    let mut light = Sphere::new(Vec3(0.0, 0.0, -2.0), 0.5);
+    light.set_material(Arc::new(DiffuseLight::new(Vec3(4.0, 3.0, 2.0))));
+    let mut world = HittableList::new();
+    world.add(Box::new(light));
+
+    let background = Background::Color(Vec3::fill(0.0));
+    let integrator = PathTracer::new();
+
+    let hit = Ray::new(Vec3::fill(0.0), Vec3(0.0, 0.0, -1.0));
+    let color = integrator.ray_color(&hit, &world, &background, 10);
+    assert!((color - Vec3(4.0, 3.0, 2.0)).len() < 1.0e-9);
+
+    let miss = Ray::new(Vec3::fill(0.0), Vec3(0.0, 1.0, 0.0));
+    let color = integrator.ray_color(&miss, &world, &background, 10);
+    assert!(color.len() < 1.0e-9);
+  }
+
+  #[test]
+  fn test_light_sampling_matches_analytic_direct_light() {
+    // a sphere light of radiance 1 and sin(alpha) = 1/3 seen from the top of a white-ish floor,
+    // which reflects albedo * radiance * sin(alpha)^2 straight up
+    let mut floor = Sphere::new(Vec3(0.0, -1000.0, 0.0), 1000.0);
+    floor.set_material(Arc::new(Lambertian::new(Vec3::fill(0.5))));
+    let mut light = Sphere::new(Vec3(0.0, 3.0, 0.0), 1.0);
+    light.set_material(Arc::new(DiffuseLight::new(Vec3::fill(1.0))));
+
+    let mut world = HittableList::new();
+    world.add(Box::new(floor));
+    world.add(Box::new(light));
+
+    let background = Background::Color(Vec3::fill(0.0));
+    let ray = Ray::new(Vec3(0.5, 1.0, 0.0), Vec3(-0.5, -1.0, 0.0));
+    let expected = 0.5 / 9.0;
+
+    let mean = |integrator: &dyn Integrator| {
+      let samples = 2000;
+      let mut sum = Vec3::fill(0.0);
+      for _ in 0..samples {
+        sum = sum + integrator.ray_color(&ray, &world, &background, 5);
+      }
+      (sum / samples as f64).x()
+    };
+
+    let light_copy = || {
+      let mut lights = HittableList::new();
+      lights.add(Box::new(Sphere::new(Vec3(0.0, 3.0, 0.0), 1.0)));
+      lights
+    };
+
+    assert!((mean(&NextEventPathTracer::new(light_copy())) - expected).abs() < 0.02 * expected);
+    assert!((mean(&MisPathTracer::new(light_copy())) - expected).abs() < 0.02 * expected);
+  }
+}
@@ -1,8 +1,37 @@
+pub mod aabb;
+pub mod aov;
+pub mod background;
+pub mod box_shape;
+pub mod bvh;
 pub mod camera;
+pub mod denoise;
+pub mod environment;
+pub mod framebuffer;
 pub mod hittable;
 pub mod hittable_list;
+pub mod image;
+pub mod integrator;
 pub mod material;
+pub mod medium;
+pub mod mesh;
+pub mod microfacet;
+pub mod obj;
+pub mod onb;
+pub mod pdf;
+pub mod perlin;
+pub mod plane;
+pub mod principled;
+pub mod progressive;
+pub mod quad;
 pub mod ray;
+pub mod rect;
+pub mod renderer;
+pub mod sampler;
+pub mod scene;
+pub mod scheduler;
 pub mod sphere;
+pub mod texture;
+pub mod transform;
+pub mod triangle;
 pub mod utils;
-pub mod vec3;
\ No newline at end of file
+pub mod vec3;
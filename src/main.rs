@@ -1,64 +1,154 @@
 use std::env;
 use std::io;
+use std::process::ExitCode;
+use std::time::{Duration, Instant};
 
-mod camera;
-mod hittable;
-mod hittable_list;
-mod material;
-mod ray;
-mod sphere;
-mod utils;
-mod vec3;
+use ray_tracing::{
+    background, box_shape, bvh, camera, environment, framebuffer, hittable, hittable_list,
+    integrator, material, medium, mesh, obj, principled, ray, rect, renderer, scene, sphere,
+    texture, transform, triangle, utils, vec3,
+};
 
+mod cli;
 mod demos;
 
-type Demos = Vec<Box<dyn Fn() -> io::Result<()>>>;
+use cli::{Command, Options};
 
-fn main() -> io::Result<()> {
-    let args: Vec<String> = env::args().collect();
+// exit codes besides success
+const EXIT_RENDER_ERROR: u8 = 1;
+const EXIT_USAGE_ERROR: u8 = 2;
 
-    if args.len() != 2 {
-        panic!("Expected one arguments. cargo run {number}");
+enum Target {
+    All,
+    Demo(demos::Demo),
+    File(String),
+}
+
+fn resolve(scene: &str) -> Result<Target, String> {
+    if scene == "all" {
+        return Ok(Target::All);
+    }
+
+    if scene.ends_with(".scene") {
+        return Ok(Target::File(scene.to_string()));
     }
 
-    let demos: Demos = vec![
-        Box::new(demos::demo00::run),
-        Box::new(demos::demo01::run),
-        Box::new(demos::demo02::run),
-        Box::new(demos::demo03::run),
-        Box::new(demos::demo04::run),
-        Box::new(demos::demo05::run),
-        Box::new(demos::demo06::run),
-        Box::new(demos::demo07::run),
-        Box::new(demos::demo08::run),
-        Box::new(demos::demo09::run),
-        Box::new(demos::demo10::run),
-        Box::new(demos::demo11::run),
-        Box::new(demos::demo12::run),
-        Box::new(demos::demo13::run),
-        Box::new(demos::demo14::run),
-        Box::new(demos::demo15::run),
-    ];
-
-    let length = demos.len();
-
-    // run all demo
-    if args[1] == "*" {
-        for run in demos.iter() {
-            run()?;
+    match demos::find(scene) {
+        Some(demo) => Ok(Target::Demo(demo)),
+        None => Err(format!(
+            "Unknown scene {}, run `ray_tracing list` to see the built-in scenes",
+            scene
+        )),
+    }
+}
+
+fn run_demo(demo: &demos::Demo, options: &Options) -> io::Result<()> {
+    // scenes built from random() come out the same for the same seed
+    utils::seed_random(options.seed.unwrap_or(0), 0);
+    (demo.run)(options)
+}
+
+fn render(target: &Target, options: &Options) -> io::Result<()> {
+    match target {
+        Target::All => {
+            for demo in demos::all() {
+                run_demo(&demo, options)?;
+            }
+            Ok(())
         }
-        return Ok(());
+        Target::Demo(demo) => run_demo(demo, options),
+        Target::File(path) => {
+            demos::run_scene_file(path, &demos::scene_file_output(path), options)
+        }
+    }
+}
+
+fn list() {
+    for (index, demo) in demos::all().iter().enumerate() {
+        println!("{:>2}  {:<18} {}", index, demo.name, demo.description);
+    }
+}
+
+fn bench(target: &Target, runs: usize, options: &Options) -> io::Result<()> {
+    // keep the tracked images in dist/ untouched unless an output is asked for
+    let mut options = options.clone();
+    // the extension follows --format, like the default output of a render
+    if options.output.is_none() {
+        let output = env::temp_dir().join("ray_tracing_bench.ppm");
+        options.output = Some(options.output_path(&output.to_string_lossy()));
     }
 
-    // run specified demo
-    if let Ok(n) = args[1].parse::<usize>() {
-        if n < length {
-            &demos[n]()?;
-            return Ok(());
-        } else {
-            panic!("demo{} is not implemented yet", n)
+    let mut timings: Vec<Duration> = vec![];
+
+    for run in 0..runs {
+        let start = Instant::now();
+        render(target, &options)?;
+        let elapsed = start.elapsed();
+        println!("run {}: {:?}", run + 1, elapsed);
+        timings.push(elapsed);
+    }
+
+    let total: Duration = timings.iter().sum();
+    let fastest = timings.iter().min().unwrap();
+    println!(
+        "{} runs, fastest {:?}, mean {:?}",
+        runs,
+        fastest,
+        total / runs as u32
+    );
+
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    let command = match cli::parse(&args) {
+        Ok(command) => command,
+        Err(message) => {
+            eprintln!("error: {}\n\n{}", message, cli::USAGE);
+            return ExitCode::from(EXIT_USAGE_ERROR);
+        }
+    };
+
+    let result = match command {
+        Command::Help => {
+            println!("{}", cli::USAGE);
+            Ok(())
+        }
+        Command::List => {
+            list();
+            Ok(())
+        }
+        Command::Render { scene, options } => match resolve(&scene) {
+            Ok(target) => render(&target, &options),
+            Err(message) => {
+                eprintln!("error: {}", message);
+                return ExitCode::from(EXIT_USAGE_ERROR);
+            }
+        },
+        Command::Bench {
+            scene,
+            runs,
+            options,
+        } => match resolve(&scene) {
+            Ok(Target::All) => {
+                eprintln!("error: Expected a single scene to bench");
+                return ExitCode::from(EXIT_USAGE_ERROR);
+            }
+            Ok(target) => bench(&target, runs, &options),
+            Err(message) => {
+                eprintln!("error: {}", message);
+                return ExitCode::from(EXIT_USAGE_ERROR);
+            }
+        },
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            ExitCode::from(EXIT_RENDER_ERROR)
         }
-    } else {
-        panic!("Expected a number. cargo run {number}");
     }
 }
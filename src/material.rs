@@ -1,24 +1,67 @@
 use std::fmt::Debug;
+use std::sync::Arc;
 
 use crate::hittable::HitRecord;
+use crate::microfacet::{fresnel_conductor, fresnel_dielectric, TrowbridgeReitz};
+use crate::onb::Onb;
+use crate::pdf::{CosinePdf, Pdf};
 use crate::ray::Ray;
-use crate::utils::random;
+use crate::sampler::{sample_1d, sample_2d};
+use crate::texture::{SolidColor, Texture};
 use crate::vec3::Vec3;
 
+// a ray leaving the surface and the share of the light along it that makes it back to ray_in
+#[derive(Debug, Clone)]
+pub struct ScatterRecord {
+  pub attenuation: Vec3,
+  pub scattered: Ray,
+}
+
+impl ScatterRecord {
+  pub fn new(attenuation: Vec3, scattered: Ray) -> ScatterRecord {
+    ScatterRecord {
+      attenuation,
+      scattered,
+    }
+  }
+}
+
+// materials are shared between shapes and threads, nothing about them changes while rendering
 pub trait Material: Debug + Send + Sync {
-  fn scatter(
-    &mut self,
-    ray_in: &Ray,
-    record: &mut HitRecord,
-    attenuation: &mut Vec3,
-    scattered: &mut Ray,
-  ) -> bool;
-  fn box_clone(&self) -> Box<dyn Material>;
+  // None when the light is absorbed
+  fn scatter(&self, ray_in: &Ray, record: &HitRecord) -> Option<ScatterRecord>;
+  // light given off by the surface itself, black for everything except light sources
+  fn emitted(&self, _u: f64, _v: f64, _point: Vec3) -> Vec3 {
+    Vec3::fill(0.0)
+  }
+  // diffuse surfaces can be lit by sampling the lights, others only reflect what scatter finds
+  fn is_diffuse(&self) -> bool {
+    false
+  }
+  // the brdf times the cosine at the surface, for light arriving along direction and leaving along -ray_in
+  fn evaluate(&self, _ray_in: &Ray, _record: &HitRecord, _direction: Vec3) -> Vec3 {
+    Vec3::fill(0.0)
+  }
+  // density over solid angle of scatter picking the scattered direction, 0 for mirror-like
+  // materials whose single direction no other sampling technique can find
+  fn scattering_pdf(&self, _ray_in: &Ray, _record: &HitRecord, _scattered: &Ray) -> f64 {
+    0.0
+  }
+  // the color of the surface without its lighting, a feature for the denoiser
+  fn albedo(&self, _record: &HitRecord) -> Vec3 {
+    Vec3::fill(1.0)
+  }
 }
 
 #[derive(Debug)]
 pub struct DefaultMaterial {}
 
+impl Default for DefaultMaterial {
+  fn default() -> Self {
+    DefaultMaterial::new()
+  }
+}
+
 impl DefaultMaterial {
   pub fn new() -> DefaultMaterial {
     DefaultMaterial {}
@@ -26,85 +69,86 @@ impl DefaultMaterial {
 }
 
 impl Material for DefaultMaterial {
-  fn scatter(
-    &mut self,
-    _ray_in: &Ray,
-    _record: &mut HitRecord,
-    _attenuation: &mut Vec3,
-    _scattered: &mut Ray,
-  ) -> bool {
-    false
-  }
-  fn box_clone(&self) -> Box<dyn Material> {
-    Box::new(DefaultMaterial::new())
+  fn scatter(&self, _ray_in: &Ray, _record: &HitRecord) -> Option<ScatterRecord> {
+    None
   }
 }
 
 #[derive(Debug)]
 pub struct Lambertian {
-  albedo: Vec3,
+  albedo: Arc<dyn Texture>,
 }
 
 impl Lambertian {
   pub fn new(albedo: Vec3) -> Lambertian {
+    Lambertian::from_texture(Arc::new(SolidColor::new(albedo)))
+  }
+  pub fn from_texture(albedo: Arc<dyn Texture>) -> Lambertian {
     Lambertian { albedo }
   }
 }
 
 impl Material for Lambertian {
-  fn scatter(
-    &mut self,
-    _ray_in: &Ray,
-    record: &mut HitRecord,
-    attenuation: &mut Vec3,
-    scattered: &mut Ray,
-  ) -> bool {
-    let scatter_direction = record.normal + Vec3::random_unit_vector();
-    *scattered = Ray::new(record.point, scatter_direction);
-    *attenuation = self.albedo;
+  fn scatter(&self, _ray_in: &Ray, record: &HitRecord) -> Option<ScatterRecord> {
+    // brdf * cosine / pdf leaves only the albedo for cosine weighted directions
+    let pdf = CosinePdf::new(record.normal);
+    Some(ScatterRecord::new(
+      self.albedo.value(record.u, record.v, record.point),
+      Ray::new(record.point, pdf.generate()),
+    ))
+  }
+  fn is_diffuse(&self) -> bool {
     true
   }
-  fn box_clone(&self) -> Box<dyn Material> {
-    Box::new(Lambertian::new(self.albedo))
+  fn evaluate(&self, _ray_in: &Ray, record: &HitRecord, direction: Vec3) -> Vec3 {
+    let cosine = record.normal.dot(direction.unit_vector()).max(0.0);
+    self.albedo.value(record.u, record.v, record.point) * (cosine / std::f64::consts::PI)
+  }
+  fn scattering_pdf(&self, _ray_in: &Ray, record: &HitRecord, scattered: &Ray) -> f64 {
+    CosinePdf::new(record.normal).value(scattered.direction)
+  }
+  fn albedo(&self, record: &HitRecord) -> Vec3 {
+    self.albedo.value(record.u, record.v, record.point)
   }
 }
 
 #[derive(Debug)]
 pub struct Metal {
-  pub albedo: Vec3,
+  pub albedo: Arc<dyn Texture>,
   pub fuzz: f64,
 }
 
 impl Metal {
   pub fn new(albedo: Vec3) -> Metal {
+    Metal::from_texture(Arc::new(SolidColor::new(albedo)))
+  }
+  pub fn from_texture(albedo: Arc<dyn Texture>) -> Metal {
     Metal { albedo, fuzz: 0.0 }
   }
   pub fn set_fuzz(&self, fuzz: f64) -> Self {
     let fuzz = if fuzz < 1.0 { fuzz } else { 1.0 };
     Metal {
-      albedo: self.albedo,
+      albedo: Arc::clone(&self.albedo),
       fuzz,
     }
   }
 }
 
 impl Material for Metal {
-  fn scatter(
-    &mut self,
-    ray_in: &Ray,
-    record: &mut HitRecord,
-    attenuation: &mut Vec3,
-    scattered: &mut Ray,
-  ) -> bool {
+  fn scatter(&self, ray_in: &Ray, record: &HitRecord) -> Option<ScatterRecord> {
     let reflected = Vec3::reflect(ray_in.direction.unit_vector(), record.normal);
     let reflected = reflected + self.fuzz * Vec3::random_unit_vector();
 
-    *scattered = Ray::new(record.point, reflected);
-    *attenuation = self.albedo;
-    scattered.direction.dot(record.normal) > 0.0
+    if reflected.dot(record.normal) <= 0.0 {
+      return None;
+    }
+    Some(ScatterRecord::new(
+      self.albedo.value(record.u, record.v, record.point),
+      Ray::new(record.point, reflected),
+    ))
   }
-  fn box_clone(&self) -> Box<dyn Material> {
-    Box::new(Metal::new(self.albedo).set_fuzz(self.fuzz))
+  fn albedo(&self, record: &HitRecord) -> Vec3 {
+    self.albedo.value(record.u, record.v, record.point)
   }
 }
 
@@ -120,13 +164,7 @@ impl Dielectric {
 }
 
 impl Material for Dielectric {
-  fn scatter(
-    &mut self,
-    ray_in: &Ray,
-    record: &mut HitRecord,
-    attenuation: &mut Vec3,
-    scattered: &mut Ray,
-  ) -> bool {
+  fn scatter(&self, ray_in: &Ray, record: &HitRecord) -> Option<ScatterRecord> {
     let etai_over_etat = if record.front_face {
       1.0 / self.refract_index
     } else {
@@ -147,23 +185,18 @@ impl Material for Dielectric {
       Ray::new(record.point, refracted)
     };
 
-    *attenuation = Vec3::fill(1.0);
-
-    if etai_over_etat * sin_theta > 1.0 {
-      *scattered = reflect();
+    let scattered = if etai_over_etat * sin_theta > 1.0 {
+      reflect()
     } else {
       let reflect_prop = schlick(cos_theta, etai_over_etat);
 
-      if random() < reflect_prop {
-        *scattered = reflect();
+      if sample_1d() < reflect_prop {
+        reflect()
       } else {
-        *scattered = refract();
+        refract()
       }
-    }
-    true
-  }
-  fn box_clone(&self) -> Box<dyn Material> {
-    Box::new(Dielectric::new(self.refract_index))
+    };
+    Some(ScatterRecord::new(Vec3::fill(1.0), scattered))
   }
 }
 
@@ -172,3 +205,372 @@ fn schlick(cosine: f64, refract_index: f64) -> f64 {
   let r0 = r0.powi(2);
   r0 + (1.0 - r0) * (1.0 - cosine).powi(5)
 }
+
+// mirrors the view direction about a microfacet normal, in a local frame
+fn reflect_local(wo: Vec3, wm: Vec3) -> Vec3 {
+  2.0 * wo.dot(wm) * wm - wo
+}
+
+// a metal with a rough GGX surface, colored by the Fresnel reflectance of its complex index
+// of refraction eta + i k. unlike Metal's fuzz, the roughness keeps energy and brightens
+// toward grazing angles like real metal does
+#[derive(Debug)]
+pub struct Conductor {
+  eta: Vec3,
+  k: Vec3,
+  distribution: TrowbridgeReitz,
+}
+
+impl Conductor {
+  // roughness runs from 0 for a mirror to 1 for a fully matte surface
+  pub fn new(eta: Vec3, k: Vec3, roughness: f64) -> Conductor {
+    Conductor {
+      eta,
+      k,
+      distribution: TrowbridgeReitz::from_roughness(roughness),
+    }
+  }
+  // one of CONDUCTORS by name
+  pub fn preset(name: &str, roughness: f64) -> Option<Conductor> {
+    CONDUCTORS
+      .iter()
+      .find(|(preset, _, _)| *preset == name)
+      .map(|(_, eta, k)| Conductor::new(*eta, *k, roughness))
+  }
+  pub fn gold(roughness: f64) -> Conductor {
+    Conductor::preset("gold", roughness).unwrap()
+  }
+  pub fn copper(roughness: f64) -> Conductor {
+    Conductor::preset("copper", roughness).unwrap()
+  }
+  pub fn aluminium(roughness: f64) -> Conductor {
+    Conductor::preset("aluminium", roughness).unwrap()
+  }
+}
+
+// names with the measured eta and k at the red, green and blue wavelengths
+pub const CONDUCTORS: [(&str, Vec3, Vec3); 3] = [
+  ("gold", Vec3(0.143, 0.374, 1.442), Vec3(3.983, 2.385, 1.603)),
+  ("copper", Vec3(0.200, 0.924, 1.102), Vec3(3.912, 2.452, 2.142)),
+  ("aluminium", Vec3(1.657, 0.880, 0.521), Vec3(9.224, 6.269, 4.837)),
+];
+
+impl Material for Conductor {
+  fn scatter(&self, ray_in: &Ray, record: &HitRecord) -> Option<ScatterRecord> {
+    let frame = Onb::from_w(record.normal);
+    let wo = frame.to_local(-ray_in.direction.unit_vector());
+    if wo.z() <= 0.0 {
+      return None;
+    }
+
+    let (attenuation, wi) = if self.distribution.is_smooth() {
+      (fresnel_conductor(wo.z(), self.eta, self.k), Vec3(-wo.x(), -wo.y(), wo.z()))
+    } else {
+      let (u, v) = sample_2d();
+      let wm = self.distribution.sample_visible_normal(wo, u, v);
+      let wi = reflect_local(wo, wm);
+      if wi.z() <= 0.0 {
+        return None;
+      }
+      // brdf * cosine / pdf of a visible normal sample
+      let shadowing = self.distribution.g(wo, wi) / self.distribution.g1(wo);
+      (fresnel_conductor(wo.dot(wm), self.eta, self.k) * shadowing, wi)
+    };
+
+    let mut scattered = Ray::new(record.point, frame.local(wi));
+    scattered.time = ray_in.time;
+    Some(ScatterRecord::new(attenuation, scattered))
+  }
+  // rough surfaces take light samples too, mirrors cannot
+  fn is_diffuse(&self) -> bool {
+    !self.distribution.is_smooth()
+  }
+  fn evaluate(&self, ray_in: &Ray, record: &HitRecord, direction: Vec3) -> Vec3 {
+    let frame = Onb::from_w(record.normal);
+    let wo = frame.to_local(-ray_in.direction.unit_vector());
+    let wi = frame.to_local(direction.unit_vector());
+    if self.distribution.is_smooth() || wo.z() <= 0.0 || wi.z() <= 0.0 {
+      return Vec3::fill(0.0);
+    }
+
+    let wm = (wo + wi).unit_vector();
+    let specular = self.distribution.d(wm) * self.distribution.g(wo, wi) / (4.0 * wo.z());
+    fresnel_conductor(wo.dot(wm), self.eta, self.k) * specular
+  }
+  fn scattering_pdf(&self, ray_in: &Ray, record: &HitRecord, scattered: &Ray) -> f64 {
+    let frame = Onb::from_w(record.normal);
+    let wo = frame.to_local(-ray_in.direction.unit_vector());
+    let wi = frame.to_local(scattered.direction.unit_vector());
+    if self.distribution.is_smooth() || wo.z() <= 0.0 || wi.z() <= 0.0 {
+      return 0.0;
+    }
+
+    // a reflected direction is picked with the density of its half vector, squeezed by the reflection
+    let wm = (wo + wi).unit_vector();
+    self.distribution.pdf_visible_normal(wo, wm) / (4.0 * wo.dot(wm))
+  }
+  // the reflectance head on
+  fn albedo(&self, _record: &HitRecord) -> Vec3 {
+    fresnel_conductor(1.0, self.eta, self.k)
+  }
+}
+
+// glass with a rough GGX surface, frosted rather than clear, with the exact Fresnel reflectance
+#[derive(Debug)]
+pub struct RoughDielectric {
+  refract_index: f64,
+  distribution: TrowbridgeReitz,
+}
+
+impl RoughDielectric {
+  pub fn new(refract_index: f64, roughness: f64) -> RoughDielectric {
+    RoughDielectric {
+      refract_index,
+      distribution: TrowbridgeReitz::from_roughness(roughness),
+    }
+  }
+}
+
+impl Material for RoughDielectric {
+  fn scatter(&self, ray_in: &Ray, record: &HitRecord) -> Option<ScatterRecord> {
+    // the normal faces the incoming ray, eta is the index of the side it enters over the side it leaves
+    let frame = Onb::from_w(record.normal);
+    let wo = frame.to_local(-ray_in.direction.unit_vector());
+    if wo.z() <= 0.0 {
+      return None;
+    }
+    let eta = if record.front_face {
+      self.refract_index
+    } else {
+      1.0 / self.refract_index
+    };
+
+    let wm = if self.distribution.is_smooth() {
+      Vec3(0.0, 0.0, 1.0)
+    } else {
+      let (u, v) = sample_2d();
+      self.distribution.sample_visible_normal(wo, u, v)
+    };
+
+    // reflection and refraction are picked by their Fresnel weights, which then cancel
+    let cos_o = wo.dot(wm);
+    let wi = if sample_1d() < fresnel_dielectric(cos_o, eta) {
+      let wi = reflect_local(wo, wm);
+      if wi.z() <= 0.0 {
+        return None;
+      }
+      wi
+    } else {
+      let sin2_t = (1.0 - cos_o * cos_o) / (eta * eta);
+      let cos_t = (1.0 - sin2_t).max(0.0).sqrt();
+      let wi = -wo / eta + (cos_o / eta - cos_t) * wm;
+      if wi.z() >= 0.0 {
+        return None;
+      }
+      wi
+    };
+
+    // like Dielectric, radiance is not rescaled by eta^2 on the way through
+    let attenuation = if self.distribution.is_smooth() {
+      Vec3::fill(1.0)
+    } else {
+      Vec3::fill(self.distribution.g(wo, wi) / self.distribution.g1(wo))
+    };
+    let mut scattered = Ray::new(record.point, frame.local(wi));
+    scattered.time = ray_in.time;
+    Some(ScatterRecord::new(attenuation, scattered))
+  }
+}
+
+#[derive(Debug)]
+pub struct DiffuseLight {
+  emit: Arc<dyn Texture>,
+}
+
+impl DiffuseLight {
+  pub fn new(emit: Vec3) -> DiffuseLight {
+    DiffuseLight::from_texture(Arc::new(SolidColor::new(emit)))
+  }
+  pub fn from_texture(emit: Arc<dyn Texture>) -> DiffuseLight {
+    DiffuseLight { emit }
+  }
+}
+
+impl Material for DiffuseLight {
+  fn scatter(&self, _ray_in: &Ray, _record: &HitRecord) -> Option<ScatterRecord> {
+    None
+  }
+  fn emitted(&self, u: f64, v: f64, point: Vec3) -> Vec3 {
+    self.emit.value(u, v, point)
+  }
+}
+
+// the phase function of a medium scattering equally in every direction, see ConstantMedium
+#[derive(Debug)]
+pub struct Isotropic {
+  albedo: Arc<dyn Texture>,
+}
+
+impl Isotropic {
+  pub fn new(albedo: Vec3) -> Isotropic {
+    Isotropic::from_texture(Arc::new(SolidColor::new(albedo)))
+  }
+  pub fn from_texture(albedo: Arc<dyn Texture>) -> Isotropic {
+    Isotropic { albedo }
+  }
+}
+
+impl Material for Isotropic {
+  fn scatter(&self, ray_in: &Ray, record: &HitRecord) -> Option<ScatterRecord> {
+    let mut scattered = Ray::new(record.point, Vec3::random_unit_vector());
+    scattered.time = ray_in.time;
+    Some(ScatterRecord::new(
+      self.albedo.value(record.u, record.v, record.point),
+      scattered,
+    ))
+  }
+  fn is_diffuse(&self) -> bool {
+    true
+  }
+  // a phase function has no surface, so there is no cosine
+  fn evaluate(&self, _ray_in: &Ray, record: &HitRecord, _direction: Vec3) -> Vec3 {
+    self.albedo.value(record.u, record.v, record.point) / (4.0 * std::f64::consts::PI)
+  }
+  fn scattering_pdf(&self, _ray_in: &Ray, _record: &HitRecord, _scattered: &Ray) -> f64 {
+    1.0 / (4.0 * std::f64::consts::PI)
+  }
+  fn albedo(&self, record: &HitRecord) -> Vec3 {
+    self.albedo.value(record.u, record.v, record.point)
+  }
+}
+
+// an anisotropic phase function, g above 0 scatters mostly forward like haze and clouds,
+// below 0 mostly back toward where the light came from
+#[derive(Debug)]
+pub struct HenyeyGreenstein {
+  albedo: Arc<dyn Texture>,
+  g: f64,
+}
+
+impl HenyeyGreenstein {
+  pub fn new(albedo: Vec3, g: f64) -> HenyeyGreenstein {
+    HenyeyGreenstein::from_texture(Arc::new(SolidColor::new(albedo)), g)
+  }
+  // g is kept inside (-1, 1), at the ends all light would leave along a single direction
+  pub fn from_texture(albedo: Arc<dyn Texture>, g: f64) -> HenyeyGreenstein {
+    HenyeyGreenstein {
+      albedo,
+      g: g.clamp(-0.99, 0.99),
+    }
+  }
+}
+
+impl Material for HenyeyGreenstein {
+  fn scatter(&self, ray_in: &Ray, record: &HitRecord) -> Option<ScatterRecord> {
+    let (u, v) = sample_2d();
+    let direction = sample_henyey_greenstein(ray_in.direction.unit_vector(), self.g, u, v);
+    let mut scattered = Ray::new(record.point, direction);
+    scattered.time = ray_in.time;
+    // directions are drawn with the density of the phase function itself
+    Some(ScatterRecord::new(
+      self.albedo.value(record.u, record.v, record.point),
+      scattered,
+    ))
+  }
+  fn is_diffuse(&self) -> bool {
+    true
+  }
+  fn evaluate(&self, ray_in: &Ray, record: &HitRecord, direction: Vec3) -> Vec3 {
+    let cosine = ray_in.direction.unit_vector().dot(direction.unit_vector());
+    self.albedo.value(record.u, record.v, record.point) * henyey_greenstein(cosine, self.g)
+  }
+  fn scattering_pdf(&self, ray_in: &Ray, _record: &HitRecord, scattered: &Ray) -> f64 {
+    let cosine = ray_in.direction.unit_vector().dot(scattered.direction.unit_vector());
+    henyey_greenstein(cosine, self.g)
+  }
+  fn albedo(&self, record: &HitRecord) -> Vec3 {
+    self.albedo.value(record.u, record.v, record.point)
+  }
+}
+
+// density over solid angle of turning by an angle with this cosine from the direction of travel
+pub fn henyey_greenstein(cosine: f64, g: f64) -> f64 {
+  let denominator = 1.0 + g * g - 2.0 * g * cosine;
+  (1.0 - g * g) / (4.0 * std::f64::consts::PI * denominator * denominator.sqrt())
+}
+
+// inverts the cumulative distribution of the cosine, u = 1 keeps going straight ahead for g above 0
+fn sample_henyey_greenstein(direction: Vec3, g: f64, u: f64, v: f64) -> Vec3 {
+  let cosine = if g.abs() < 1.0e-3 {
+    1.0 - 2.0 * u
+  } else {
+    let term = (1.0 - g * g) / (1.0 - g + 2.0 * g * u);
+    ((1.0 + g * g - term * term) / (2.0 * g)).clamp(-1.0, 1.0)
+  };
+  let sine = (1.0 - cosine * cosine).max(0.0).sqrt();
+  let phi = 2.0 * std::f64::consts::PI * v;
+
+  Onb::from_w(direction).local(Vec3(sine * phi.cos(), sine * phi.sin(), cosine))
+}
+
+#[cfg(test)]
+mod tests {
+
+  use super::*;
+
+  #[test]
+  fn test_conductor_sampling_matches_evaluate() {
+    let gold = Conductor::gold(0.4);
+    let mut record = HitRecord::new();
+    record.normal = Vec3(0.0, 1.0, 0.0);
+    record.front_face = true;
+    let ray_in = Ray::new(Vec3(-1.0, 1.0, 0.3), Vec3(1.0, -1.0, -0.3));
+
+    // the weight of every sampled direction is its brdf * cosine over its density
+    for _ in 0..100 {
+      let scatter = match gold.scatter(&ray_in, &record) {
+        Some(scatter) => scatter,
+        None => continue,
+      };
+      let pdf = gold.scattering_pdf(&ray_in, &record, &scatter.scattered);
+      let expected = gold.evaluate(&ray_in, &record, scatter.scattered.direction) / pdf;
+      assert!((scatter.attenuation - expected).len() < 1.0e-6);
+    }
+
+    // a mirror reflects head on light by its Fresnel reflectance
+    let mirror = Conductor::aluminium(0.0);
+    let head_on = Ray::new(Vec3(0.0, 1.0, 0.0), Vec3(0.0, -1.0, 0.0));
+    let scatter = mirror.scatter(&head_on, &record).unwrap();
+    assert!((scatter.scattered.direction - Vec3(0.0, 1.0, 0.0)).len() < 1.0e-9);
+    assert!(scatter.attenuation.x() > 0.9 && scatter.attenuation.x() < 1.0);
+  }
+
+  #[test]
+  fn test_henyey_greenstein_sampling_matches_density() {
+    let g = 0.6;
+    let direction = Vec3(0.0, 0.0, -1.0);
+
+    // the density integrates to 1 over the sphere
+    let steps = 20000;
+    let integral: f64 = (0..steps)
+      .map(|step| {
+        let cosine = -1.0 + 2.0 * (step as f64 + 0.5) / steps as f64;
+        henyey_greenstein(cosine, g) * 2.0 * std::f64::consts::PI * (2.0 / steps as f64)
+      })
+      .sum();
+    assert!((integral - 1.0).abs() < 1.0e-3);
+
+    // and the mean cosine of its samples is g
+    let count = 256;
+    let mut mean = 0.0;
+    for i in 0..count {
+      for j in 0..count {
+        let u = (i as f64 + 0.5) / count as f64;
+        let v = (j as f64 + 0.5) / count as f64;
+        mean += sample_henyey_greenstein(direction, g, u, v).dot(direction);
+      }
+    }
+    mean /= (count * count) as f64;
+    assert!((mean - g).abs() < 1.0e-3);
+  }
+}
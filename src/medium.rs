@@ -0,0 +1,108 @@
+use std::sync::Arc;
+
+use crate::aabb::Aabb;
+use crate::hittable::{HitRecord, Hittable};
+use crate::material::{Isotropic, Material};
+use crate::ray::Ray;
+use crate::sampler::sample_1d;
+use crate::vec3::Vec3;
+
+// a volume of smoke or fog filling a boundary object, with the same density everywhere.
+// a ray passing through scatters after an exponentially distributed distance, or leaves the
+// volume untouched, so thin media are see-through and dense ones look nearly solid.
+// the boundary is expected to be convex, a ray only enters and leaves it once
+pub struct ConstantMedium {
+  boundary: Box<dyn Hittable>,
+  neg_inv_density: f64,
+  phase_function: Arc<dyn Material>,
+}
+
+impl ConstantMedium {
+  pub fn new(boundary: Box<dyn Hittable>, density: f64, albedo: Vec3) -> ConstantMedium {
+    ConstantMedium {
+      boundary,
+      neg_inv_density: -1.0 / density,
+      phase_function: Arc::new(Isotropic::new(albedo)),
+    }
+  }
+
+  // replaces the isotropic scattering, e.g. with a HenyeyGreenstein phase function
+  pub fn set_phase_function(&mut self, phase_function: Arc<dyn Material>) {
+    self.phase_function = phase_function;
+  }
+}
+
+impl Hittable for ConstantMedium {
+  fn hit<'a>(&'a self, ray: &Ray, t_min: f64, t_max: f64, record: &mut HitRecord<'a>) -> bool {
+    // where the whole line enters and leaves the boundary, the ray may start inside it
+    let mut entry = HitRecord::new();
+    let mut exit = HitRecord::new();
+    if !self.boundary.hit(ray, f64::NEG_INFINITY, f64::INFINITY, &mut entry) {
+      return false;
+    }
+    if !self.boundary.hit(ray, entry.t + 0.0001, f64::INFINITY, &mut exit) {
+      return false;
+    }
+
+    let t0 = entry.t.max(t_min).max(0.0);
+    let t1 = exit.t.min(t_max);
+    if t0 >= t1 {
+      return false;
+    }
+
+    let ray_length = ray.direction.len();
+    let distance_inside = (t1 - t0) * ray_length;
+    let hit_distance = self.neg_inv_density * (1.0 - sample_1d()).ln();
+    if hit_distance > distance_inside {
+      return false;
+    }
+
+    record.set_t(t0 + hit_distance / ray_length);
+    record.set_point(ray.at(record.t));
+    record.set_uv(0.0, 0.0);
+    // a point inside a volume has no surface, phase functions ignore the normal
+    record.normal = Vec3(1.0, 0.0, 0.0);
+    record.front_face = true;
+    record.set_material(self.phase_function.as_ref());
+    true
+  }
+
+  fn bounding_box(&self, time0: f64, time1: f64, output_box: &mut Aabb) -> bool {
+    self.boundary.bounding_box(time0, time1, output_box)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+
+  use super::*;
+  use crate::box_shape::BoxShape;
+
+  #[test]
+  fn test_transmittance_follows_density() {
+    let density = 0.5;
+    let boundary = BoxShape::new(Vec3(-1.0, -1.0, -1.0), Vec3(1.0, 1.0, 1.0));
+    let medium = ConstantMedium::new(Box::new(boundary), density, Vec3::fill(0.5));
+
+    // a ray crossing 2 units of the medium gets through with probability e^(-density * 2)
+    let ray = Ray::new(Vec3(0.0, 0.0, 5.0), Vec3(0.0, 0.0, -2.0));
+    let count = 20000;
+    let mut record = HitRecord::new();
+    let mut passed = 0;
+    for _ in 0..count {
+      if medium.hit(&ray, 0.001, f64::INFINITY, &mut record) {
+        assert!(record.point.z() < 1.0 && record.point.z() > -1.0);
+      } else {
+        passed += 1;
+      }
+    }
+    let expected = (-density * 2.0_f64).exp();
+    assert!((passed as f64 / count as f64 - expected).abs() < 0.02);
+
+    // rays starting inside only travel through the rest of it
+    let inside = Ray::new(Vec3(0.0, 0.0, 0.0), Vec3(0.0, 0.0, -1.0));
+    if medium.hit(&inside, 0.001, f64::INFINITY, &mut record) {
+      assert!(record.t > 0.0 && record.t < 1.0);
+    }
+  }
+}
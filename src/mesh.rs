@@ -0,0 +1,177 @@
+use std::sync::Arc;
+
+use crate::aabb::Aabb;
+use crate::bvh::{BvhNode, SplitHeuristic};
+use crate::hittable::{self, HitRecord, Hittable};
+use crate::hittable_list::HittableList;
+use crate::material::{DefaultMaterial, Material};
+use crate::ray::Ray;
+use crate::triangle::{intersect_triangle, triangle_box};
+use crate::vec3::Vec3;
+
+// indices of one triangle corner into the shared buffers of a MeshData
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MeshVertex {
+  pub position: usize,
+  pub normal: Option<usize>,
+  pub uv: Option<usize>,
+}
+
+impl MeshVertex {
+  pub fn new(position: usize) -> MeshVertex {
+    MeshVertex {
+      position,
+      normal: None,
+      uv: None,
+    }
+  }
+}
+
+// vertex buffers shared by every triangle of a mesh
+#[derive(Debug, Clone, Default)]
+pub struct MeshData {
+  pub positions: Vec<Vec3>,
+  pub normals: Vec<Vec3>,
+  pub uvs: Vec<(f64, f64)>,
+  pub faces: Vec<[MeshVertex; 3]>,
+}
+
+impl MeshData {
+  pub fn new() -> MeshData {
+    MeshData::default()
+  }
+
+  fn corners(&self, face: usize) -> (Vec3, Vec3, Vec3) {
+    let [a, b, c] = self.faces[face];
+    (self.positions[a.position], self.positions[b.position], self.positions[c.position])
+  }
+}
+
+// one face of a mesh, only used as a leaf of the mesh's BVH
+struct MeshTriangle {
+  data: Arc<MeshData>,
+  face: usize,
+}
+
+impl Hittable for MeshTriangle {
+  fn hit<'a>(&'a self, ray: &Ray, t_min: f64, t_max: f64, record: &mut HitRecord<'a>) -> bool {
+    let data = &self.data;
+    let (p0, p1, p2) = data.corners(self.face);
+    let (t, b0, b1, b2) = match intersect_triangle(ray, p0, p1, p2, t_min, t_max) {
+      Some(hit) => hit,
+      None => return false,
+    };
+
+    let [c0, c1, c2] = data.faces[self.face];
+    let geometric_normal = (p1 - p0).cross(p2 - p0).unit_vector();
+
+    // vertex normals are interpolated only when all three corners have one
+    let outward_normal = match (c0.normal, c1.normal, c2.normal) {
+      (Some(n0), Some(n1), Some(n2)) => {
+        let normal = b0 * data.normals[n0] + b1 * data.normals[n1] + b2 * data.normals[n2];
+        if normal.length_squared() > 0.0 {
+          normal.unit_vector()
+        } else {
+          geometric_normal
+        }
+      }
+      _ => geometric_normal,
+    };
+
+    let (u, v) = match (c0.uv, c1.uv, c2.uv) {
+      (Some(t0), Some(t1), Some(t2)) => {
+        let (t0, t1, t2) = (data.uvs[t0], data.uvs[t1], data.uvs[t2]);
+        (
+          b0 * t0.0 + b1 * t1.0 + b2 * t2.0,
+          b0 * t0.1 + b1 * t1.1 + b2 * t2.1,
+        )
+      }
+      _ => (b1, b2),
+    };
+
+    record.set_t(t);
+    record.set_point(ray.at(t));
+    record.set_uv(u, v);
+    // the side is decided by the geometric normal, a shading normal may lean past the ray
+    let front_face = ray.direction.dot(geometric_normal) < 0.0;
+    record.front_face = front_face;
+    record.normal = if front_face { outward_normal } else { -outward_normal };
+    true
+  }
+
+  fn bounding_box(&self, _time0: f64, _time1: f64, output_box: &mut Aabb) -> bool {
+    let (p0, p1, p2) = self.data.corners(self.face);
+    *output_box = triangle_box(p0, p1, p2);
+    true
+  }
+}
+
+pub struct TriangleMesh {
+  data: Arc<MeshData>,
+  // None for a mesh without faces
+  bvh: Option<BvhNode>,
+  material: Arc<dyn Material>,
+}
+
+impl TriangleMesh {
+  pub fn new(data: MeshData) -> TriangleMesh {
+    TriangleMesh::from_shared(Arc::new(data))
+  }
+
+  // several meshes, e.g. with different materials, may share one set of buffers
+  pub fn from_shared(data: Arc<MeshData>) -> TriangleMesh {
+    let mut list = HittableList::new();
+    for face in 0..data.faces.len() {
+      list.add(Box::new(MeshTriangle {
+        data: Arc::clone(&data),
+        face,
+      }));
+    }
+
+    let bvh = if list.objects.is_empty() {
+      None
+    } else {
+      Some(BvhNode::new(list, 0.0, 0.0, SplitHeuristic::Sah))
+    };
+
+    TriangleMesh {
+      data,
+      bvh,
+      material: Arc::new(DefaultMaterial::new()),
+    }
+  }
+
+  pub fn set_material(&mut self, material: Arc<dyn Material>) {
+    self.material = material;
+  }
+
+  pub fn data(&self) -> &MeshData {
+    &self.data
+  }
+}
+
+impl Hittable for TriangleMesh {
+  fn hit<'a>(&'a self, ray: &Ray, t_min: f64, t_max: f64, record: &mut HitRecord<'a>) -> bool {
+    let bvh = match &self.bvh {
+      Some(bvh) => bvh,
+      None => return false,
+    };
+
+    // the material is attached once, after the closest face is known, and the whole mesh is
+    // one object
+    if bvh.hit(ray, t_min, t_max, record) {
+      record.set_material(self.material.as_ref());
+      record.object_id = hittable::object_id(self);
+      true
+    } else {
+      false
+    }
+  }
+
+  fn bounding_box(&self, time0: f64, time1: f64, output_box: &mut Aabb) -> bool {
+    match &self.bvh {
+      Some(bvh) => bvh.bounding_box(time0, time1, output_box),
+      None => false,
+    }
+  }
+}
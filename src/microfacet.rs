@@ -0,0 +1,191 @@
+use std::f64::consts::PI;
+
+use crate::vec3::Vec3;
+
+// the Trowbridge-Reitz (GGX) distribution of microfacet normals, in a local frame whose z
+// axis is the surface normal. directions point away from the surface
+#[derive(Debug, Clone, Copy)]
+pub struct TrowbridgeReitz {
+  alpha: f64,
+}
+
+impl TrowbridgeReitz {
+  // alpha is the roughness squared, which makes a roughness slider look roughly linear
+  pub fn from_roughness(roughness: f64) -> TrowbridgeReitz {
+    let roughness = roughness.clamp(0.0, 1.0);
+    TrowbridgeReitz {
+      alpha: (roughness * roughness).max(1.0e-4),
+    }
+  }
+
+  // below this the surface is treated as a perfect mirror, the distribution gets too sharp to sample
+  pub fn is_smooth(&self) -> bool {
+    self.alpha < 1.0e-3
+  }
+
+  // density of microfacet normals per unit of projected area
+  pub fn d(&self, wm: Vec3) -> f64 {
+    let cos2 = wm.z() * wm.z();
+    if cos2 <= 0.0 {
+      return 0.0;
+    }
+    let tan2 = (1.0 - cos2) / cos2;
+    let alpha2 = self.alpha * self.alpha;
+    let e = 1.0 + tan2 / alpha2;
+    1.0 / (PI * alpha2 * cos2 * cos2 * e * e)
+  }
+
+  // Smith's auxiliary function, the share of microfacets seen from w that hide others
+  fn lambda(&self, w: Vec3) -> f64 {
+    let cos2 = w.z() * w.z();
+    if cos2 <= 0.0 {
+      return f64::INFINITY;
+    }
+    let tan2 = (1.0 - cos2) / cos2;
+    0.5 * ((1.0 + self.alpha * self.alpha * tan2).sqrt() - 1.0)
+  }
+
+  // fraction of the microfacets facing w that w sees
+  pub fn g1(&self, w: Vec3) -> f64 {
+    1.0 / (1.0 + self.lambda(w))
+  }
+
+  // fraction of the microfacets visible from both wo and wi, height correlated
+  pub fn g(&self, wo: Vec3, wi: Vec3) -> f64 {
+    1.0 / (1.0 + self.lambda(wo) + self.lambda(wi))
+  }
+
+  // a microfacet normal as seen from wo (Heitz 2018), only normals wo can see are ever picked
+  pub fn sample_visible_normal(&self, wo: Vec3, u: f64, v: f64) -> Vec3 {
+    // stretch the view into the configuration of a hemisphere of unit roughness
+    let wh = Vec3(self.alpha * wo.x(), self.alpha * wo.y(), wo.z()).unit_vector();
+    let length2 = wh.x() * wh.x() + wh.y() * wh.y();
+    let t1 = if length2 > 0.0 {
+      Vec3(-wh.y(), wh.x(), 0.0) / length2.sqrt()
+    } else {
+      Vec3(1.0, 0.0, 0.0)
+    };
+    let t2 = wh.cross(t1);
+
+    // a point of the disk, squashed onto the part of it the hemisphere projects to
+    let r = u.sqrt();
+    let phi = 2.0 * PI * v;
+    let p1 = r * phi.cos();
+    let s = 0.5 * (1.0 + wh.z());
+    let p2 = (1.0 - s) * (1.0 - p1 * p1).max(0.0).sqrt() + s * r * phi.sin();
+
+    let nh = p1 * t1 + p2 * t2 + (1.0 - p1 * p1 - p2 * p2).max(0.0).sqrt() * wh;
+    Vec3(self.alpha * nh.x(), self.alpha * nh.y(), nh.z().max(1.0e-6)).unit_vector()
+  }
+
+  // density over solid angle of sample_visible_normal picking wm
+  pub fn pdf_visible_normal(&self, wo: Vec3, wm: Vec3) -> f64 {
+    if wo.z() <= 0.0 {
+      return 0.0;
+    }
+    self.g1(wo) / wo.z() * self.d(wm) * wo.dot(wm).max(0.0)
+  }
+}
+
+// unpolarized reflectance of a boundary to a dielectric with relative index eta,
+// for light arriving at cos_i to the normal on the outside (negative cos_i comes from inside)
+pub fn fresnel_dielectric(cos_i: f64, eta: f64) -> f64 {
+  let (cos_i, eta) = if cos_i < 0.0 {
+    (-cos_i, 1.0 / eta)
+  } else {
+    (cos_i, eta)
+  };
+  let cos_i = cos_i.min(1.0);
+
+  let sin2_t = (1.0 - cos_i * cos_i) / (eta * eta);
+  if sin2_t >= 1.0 {
+    // total internal reflection
+    return 1.0;
+  }
+  let cos_t = (1.0 - sin2_t).sqrt();
+
+  let parallel = (eta * cos_i - cos_t) / (eta * cos_i + cos_t);
+  let perpendicular = (cos_i - eta * cos_t) / (cos_i + eta * cos_t);
+  0.5 * (parallel * parallel + perpendicular * perpendicular)
+}
+
+// reflectance of a conductor with the complex index eta + i k, per color channel
+pub fn fresnel_conductor(cos_i: f64, eta: Vec3, k: Vec3) -> Vec3 {
+  let channel = |eta: f64, k: f64| {
+    let cos2 = cos_i.clamp(0.0, 1.0).powi(2);
+    let sin2 = 1.0 - cos2;
+    let t0 = eta * eta - k * k - sin2;
+    let a2_plus_b2 = (t0 * t0 + 4.0 * eta * eta * k * k).sqrt();
+    let a = (0.5 * (a2_plus_b2 + t0)).max(0.0).sqrt();
+
+    let t1 = a2_plus_b2 + cos2;
+    let t2 = 2.0 * cos_i.clamp(0.0, 1.0) * a;
+    let perpendicular = (t1 - t2) / (t1 + t2);
+
+    let t3 = cos2 * a2_plus_b2 + sin2 * sin2;
+    let t4 = t2 * sin2;
+    let parallel = perpendicular * (t3 - t4) / (t3 + t4);
+    0.5 * (parallel + perpendicular)
+  };
+
+  Vec3(channel(eta.x(), k.x()), channel(eta.y(), k.y()), channel(eta.z(), k.z()))
+}
+
+#[cfg(test)]
+mod tests {
+
+  use super::*;
+
+  #[test]
+  fn test_fresnel() {
+    // (eta - 1)^2 / (eta + 1)^2 head on, everything beyond the critical angle inside
+    assert!((fresnel_dielectric(1.0, 1.5) - 0.04).abs() < 1.0e-9);
+    assert_eq!(fresnel_dielectric(-0.2, 1.5), 1.0);
+    assert!((fresnel_dielectric(0.0, 1.5) - 1.0).abs() < 1.0e-9);
+
+    // a conductor without absorption is a dielectric
+    for cosine in [0.1, 0.5, 0.9] {
+      let conductor = fresnel_conductor(cosine, Vec3::fill(1.5), Vec3::fill(0.0));
+      assert!((conductor.x() - fresnel_dielectric(cosine, 1.5)).abs() < 1.0e-9);
+    }
+  }
+
+  #[test]
+  fn test_visible_normals_match_density() {
+    let distribution = TrowbridgeReitz::from_roughness(0.5);
+    let wo = Vec3(0.6, 0.0, 0.8);
+
+    // the density integrates to 1 over the hemisphere, and gives the share of samples in a cap
+    let steps = 400;
+    let (mut total, mut cap) = (0.0, 0.0);
+    for i in 0..steps {
+      for j in 0..steps {
+        let cos_theta = (i as f64 + 0.5) / steps as f64;
+        let phi = 2.0 * PI * (j as f64 + 0.5) / steps as f64;
+        let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+        let wm = Vec3(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta);
+        let density = distribution.pdf_visible_normal(wo, wm) * 2.0 * PI / (steps * steps) as f64;
+        total += density;
+        if cos_theta > 0.9 {
+          cap += density;
+        }
+      }
+    }
+    assert!((total - 1.0).abs() < 1.0e-2);
+
+    let count = 128;
+    let mut inside = 0;
+    for i in 0..count {
+      for j in 0..count {
+        let u = (i as f64 + 0.5) / count as f64;
+        let v = (j as f64 + 0.5) / count as f64;
+        let wm = distribution.sample_visible_normal(wo, u, v);
+        assert!(wm.z() > 0.0 && wm.dot(wo) > 0.0);
+        if wm.z() > 0.9 {
+          inside += 1;
+        }
+      }
+    }
+    assert!((inside as f64 / (count * count) as f64 - cap).abs() < 1.0e-2);
+  }
+}
@@ -0,0 +1,252 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::mesh::{MeshData, MeshVertex, TriangleMesh};
+use crate::vec3::Vec3;
+
+// a named run of faces, started by a `g` or `o` statement
+#[derive(Debug, Clone, PartialEq)]
+pub struct ObjGroup {
+  pub name: String,
+  // indices into ObjModel::data.faces
+  pub faces: Vec<usize>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ObjModel {
+  pub data: MeshData,
+  pub groups: Vec<ObjGroup>,
+}
+
+impl ObjModel {
+  // one mesh holding every face of the file
+  pub fn to_mesh(&self) -> TriangleMesh {
+    TriangleMesh::new(self.data.clone())
+  }
+
+  // a mesh of a single group, or None when no group has that name
+  pub fn group_mesh(&self, name: &str) -> Option<TriangleMesh> {
+    let faces: Vec<[MeshVertex; 3]> = self
+      .groups
+      .iter()
+      .filter(|group| group.name == name)
+      .flat_map(|group| group.faces.iter().map(|face| self.data.faces[*face]))
+      .collect();
+
+    if faces.is_empty() {
+      return None;
+    }
+
+    let data = MeshData {
+      positions: self.data.positions.clone(),
+      normals: self.data.normals.clone(),
+      uvs: self.data.uvs.clone(),
+      faces,
+    };
+    Some(TriangleMesh::new(data))
+  }
+}
+
+fn parse_error(line: usize, message: String) -> io::Error {
+  io::Error::new(io::ErrorKind::InvalidData, format!("line {}: {}", line, message))
+}
+
+fn parse_floats(line: usize, fields: &[&str], count: usize) -> io::Result<Vec<f64>> {
+  if fields.len() < count {
+    return Err(parse_error(
+      line,
+      format!("Expected {} numbers, but received {}", count, fields.len()),
+    ));
+  }
+
+  fields[..count]
+    .iter()
+    .map(|field| {
+      field
+        .parse::<f64>()
+        .map_err(|_| parse_error(line, format!("Expected a number, but received {}", field)))
+    })
+    .collect()
+}
+
+// OBJ indices start at 1, negative ones count back from the latest element
+fn resolve_index(line: usize, field: &str, len: usize) -> io::Result<usize> {
+  let index = field
+    .parse::<i64>()
+    .map_err(|_| parse_error(line, format!("Expected an index, but received {}", field)))?;
+
+  let resolved = if index > 0 {
+    index - 1
+  } else {
+    len as i64 + index
+  };
+
+  if index == 0 || resolved < 0 || resolved >= len as i64 {
+    return Err(parse_error(line, format!("Index {} is out of range", index)));
+  }
+
+  Ok(resolved as usize)
+}
+
+// accepts the v, v/vt, v//vn and v/vt/vn forms
+fn parse_vertex(line: usize, field: &str, data: &MeshData) -> io::Result<MeshVertex> {
+  let parts: Vec<&str> = field.split('/').collect();
+
+  if parts.len() > 3 {
+    return Err(parse_error(line, format!("Invalid face vertex {}", field)));
+  }
+
+  let mut vertex = MeshVertex::new(resolve_index(line, parts[0], data.positions.len())?);
+
+  if let Some(uv) = parts.get(1).filter(|part| !part.is_empty()) {
+    vertex.uv = Some(resolve_index(line, uv, data.uvs.len())?);
+  }
+  if let Some(normal) = parts.get(2).filter(|part| !part.is_empty()) {
+    vertex.normal = Some(resolve_index(line, normal, data.normals.len())?);
+  }
+
+  Ok(vertex)
+}
+
+pub fn parse(source: &str) -> io::Result<ObjModel> {
+  let mut data = MeshData::new();
+  let mut groups: Vec<ObjGroup> = vec![];
+
+  for (number, line) in source.lines().enumerate() {
+    let number = number + 1;
+    let line = match line.find('#') {
+      Some(comment) => &line[..comment],
+      None => line,
+    };
+    let fields: Vec<&str> = line.split_whitespace().collect();
+
+    if fields.is_empty() {
+      continue;
+    }
+
+    match fields[0] {
+      "v" => {
+        let xyz = parse_floats(number, &fields[1..], 3)?;
+        data.positions.push(Vec3(xyz[0], xyz[1], xyz[2]));
+      }
+      "vn" => {
+        let xyz = parse_floats(number, &fields[1..], 3)?;
+        data.normals.push(Vec3(xyz[0], xyz[1], xyz[2]));
+      }
+      "vt" => {
+        // the optional w coordinate is ignored
+        let uv = parse_floats(number, &fields[1..], 1)?;
+        let v = if fields.len() > 2 {
+          parse_floats(number, &fields[2..], 1)?[0]
+        } else {
+          0.0
+        };
+        data.uvs.push((uv[0], v));
+      }
+      "f" => {
+        if fields.len() < 4 {
+          return Err(parse_error(
+            number,
+            format!("Expected at least 3 face vertices, but received {}", fields.len() - 1),
+          ));
+        }
+
+        let vertices = fields[1..]
+          .iter()
+          .map(|field| parse_vertex(number, field, &data))
+          .collect::<io::Result<Vec<MeshVertex>>>()?;
+
+        // polygons are split into a fan around their first vertex
+        for i in 1..vertices.len() - 1 {
+          if let Some(group) = groups.last_mut() {
+            group.faces.push(data.faces.len());
+          }
+          data.faces.push([vertices[0], vertices[i], vertices[i + 1]]);
+        }
+      }
+      "g" | "o" => {
+        let name = fields[1..].join(" ");
+        groups.push(ObjGroup { name, faces: vec![] });
+      }
+      // materials, smoothing groups and the rest are not used by the renderer
+      _ => {}
+    }
+  }
+
+  groups.retain(|group| !group.faces.is_empty());
+
+  Ok(ObjModel { data, groups })
+}
+
+pub fn load<P: AsRef<Path>>(path: P) -> io::Result<ObjModel> {
+  parse(&fs::read_to_string(path)?)
+}
+
+#[cfg(test)]
+mod tests {
+
+  use super::*;
+  use crate::hittable::{HitRecord, Hittable};
+  use crate::ray::Ray;
+
+  static QUAD: &str = "
+# a unit square in the xy plane
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+vt 0 0
+vt 1 0
+vt 1 1
+vt 0 1
+vn 0 0 1
+o square
+f 1/1/1 2/2/1 3/3/1 4/4/1
+g negative
+f -4//1 -3//1 -2//1
+";
+
+  #[test]
+  fn test_parse_faces_and_groups() {
+    let model = parse(QUAD).unwrap();
+    assert_eq!(model.data.positions.len(), 4);
+    assert_eq!(model.data.faces.len(), 3);
+    assert_eq!(model.groups.len(), 2);
+    assert_eq!(model.groups[0].name, "square");
+    assert_eq!(model.groups[0].faces, vec![0, 1]);
+
+    let fan = model.data.faces[1];
+    assert_eq!(fan[0].position, 0);
+    assert_eq!(fan[2].position, 3);
+    assert_eq!(fan[2].uv, Some(3));
+
+    let negative = model.data.faces[2];
+    assert_eq!(negative[0].position, 0);
+    assert_eq!(negative[0].uv, None);
+    assert_eq!(negative[0].normal, Some(0));
+  }
+
+  #[test]
+  fn test_parse_reports_line_numbers() {
+    let err = parse("v 0 0 0\nf 1 2 3\n").unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    assert!(err.to_string().starts_with("line 2:"));
+  }
+
+  #[test]
+  fn test_mesh_interpolates_uv() {
+    let model = parse(QUAD).unwrap();
+    let mesh = model.group_mesh("square").unwrap();
+    let mut record = HitRecord::new();
+
+    let ray = Ray::new(Vec3(0.25, 0.75, 1.0), Vec3(0.0, 0.0, -1.0));
+    assert!(mesh.hit(&ray, 0.001, f64::INFINITY, &mut record));
+    assert!((record.t - 1.0).abs() < 1.0e-9);
+    assert!((record.u - 0.25).abs() < 1.0e-9);
+    assert!((record.v - 0.75).abs() < 1.0e-9);
+    assert!((record.normal - Vec3(0.0, 0.0, 1.0)).len() < 1.0e-9);
+
+    assert!(model.group_mesh("missing").is_none());
+  }
+}
@@ -0,0 +1,54 @@
+use crate::vec3::Vec3;
+
+// an orthonormal basis around a direction, w points along it
+#[derive(Debug, Clone, Copy)]
+pub struct Onb {
+  pub u: Vec3,
+  pub v: Vec3,
+  pub w: Vec3,
+}
+
+impl Onb {
+  pub fn from_w(direction: Vec3) -> Onb {
+    let w = direction.unit_vector();
+    // any axis that is not too close to w
+    let a = if w.x().abs() > 0.9 {
+      Vec3(0.0, 1.0, 0.0)
+    } else {
+      Vec3(1.0, 0.0, 0.0)
+    };
+    let v = w.cross(a).unit_vector();
+    let u = w.cross(v);
+
+    Onb { u, v, w }
+  }
+
+  // a vector given by its coordinates in the basis, in world space
+  pub fn local(&self, a: Vec3) -> Vec3 {
+    a.x() * self.u + a.y() * self.v + a.z() * self.w
+  }
+
+  // the coordinates of a world space vector in the basis
+  pub fn to_local(&self, a: Vec3) -> Vec3 {
+    Vec3(a.dot(self.u), a.dot(self.v), a.dot(self.w))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+
+  use super::*;
+
+  #[test]
+  fn test_onb_is_orthonormal() {
+    for direction in [Vec3(0.0, 0.0, 2.0), Vec3(-3.0, 0.1, 0.2), Vec3(0.3, -0.5, 0.8)] {
+      let onb = Onb::from_w(direction);
+      assert!((onb.w - direction.unit_vector()).len() < 1.0e-12);
+      assert!(onb.u.dot(onb.v).abs() < 1.0e-12);
+      assert!(onb.u.dot(onb.w).abs() < 1.0e-12);
+      assert!((onb.u.len() - 1.0).abs() < 1.0e-12);
+      assert!((onb.v.len() - 1.0).abs() < 1.0e-12);
+      assert!((onb.local(Vec3(0.0, 0.0, 1.0)) - onb.w).len() < 1.0e-12);
+    }
+  }
+}
@@ -0,0 +1,100 @@
+use std::f64::consts::PI;
+
+use crate::hittable::Hittable;
+use crate::onb::Onb;
+use crate::vec3::Vec3;
+
+// a distribution of directions that can be sampled and evaluated
+pub trait Pdf {
+  // density over solid angle of generate picking direction
+  fn value(&self, direction: Vec3) -> f64;
+  fn generate(&self) -> Vec3;
+}
+
+// directions around a normal with a density proportional to their cosine, what a lambertian surface scatters
+pub struct CosinePdf {
+  uvw: Onb,
+}
+
+impl CosinePdf {
+  pub fn new(normal: Vec3) -> CosinePdf {
+    CosinePdf {
+      uvw: Onb::from_w(normal),
+    }
+  }
+}
+
+impl Pdf for CosinePdf {
+  fn value(&self, direction: Vec3) -> f64 {
+    let cosine = direction.unit_vector().dot(self.uvw.w);
+    if cosine <= 0.0 {
+      0.0
+    } else {
+      cosine / PI
+    }
+  }
+
+  fn generate(&self) -> Vec3 {
+    self.uvw.local(Vec3::random_cosine_direction())
+  }
+}
+
+// directions from origin toward an object, see Hittable::pdf_value and Hittable::random
+pub struct HittablePdf<'a> {
+  object: &'a dyn Hittable,
+  origin: Vec3,
+}
+
+impl<'a> HittablePdf<'a> {
+  pub fn new(object: &'a dyn Hittable, origin: Vec3) -> HittablePdf<'a> {
+    HittablePdf { object, origin }
+  }
+}
+
+impl Pdf for HittablePdf<'_> {
+  fn value(&self, direction: Vec3) -> f64 {
+    self.object.pdf_value(self.origin, direction)
+  }
+
+  fn generate(&self) -> Vec3 {
+    self.object.random(self.origin)
+  }
+}
+
+// Veach's power heuristic with an exponent of 2, the weight of a sample drawn with pdf
+// when other_pdf could have drawn it as well
+pub fn power_heuristic(pdf: f64, other_pdf: f64) -> f64 {
+  let a = pdf * pdf;
+  let b = other_pdf * other_pdf;
+  if a + b == 0.0 {
+    return 0.0;
+  }
+  a / (a + b)
+}
+
+#[cfg(test)]
+mod tests {
+
+  use super::*;
+
+  #[test]
+  fn test_cosine_pdf_samples_the_hemisphere() {
+    let normal = Vec3(0.0, 1.0, 1.0);
+    let pdf = CosinePdf::new(normal);
+
+    // the mean cosine of a cosine weighted hemisphere is 2/3
+    let samples = 4000;
+    let mut sum = 0.0;
+    for _ in 0..samples {
+      let direction = pdf.generate();
+      let cosine = direction.unit_vector().dot(normal.unit_vector());
+      assert!(cosine >= 0.0);
+      assert!((pdf.value(direction) - cosine / PI).abs() < 1.0e-9);
+      sum += cosine;
+    }
+    assert!((sum / samples as f64 - 2.0 / 3.0).abs() < 0.02);
+
+    assert_eq!(pdf.value(-normal), 0.0);
+    assert!((power_heuristic(1.0, 1.0) - 0.5).abs() < 1.0e-12);
+  }
+}
@@ -0,0 +1,113 @@
+use crate::utils::random_int;
+use crate::vec3::Vec3;
+
+const POINT_COUNT: usize = 256;
+
+#[derive(Debug, Clone)]
+pub struct Perlin {
+  random_vectors: Vec<Vec3>,
+  perm_x: Vec<usize>,
+  perm_y: Vec<usize>,
+  perm_z: Vec<usize>,
+}
+
+impl Default for Perlin {
+  fn default() -> Self {
+    Perlin::new()
+  }
+}
+
+impl Perlin {
+  pub fn new() -> Perlin {
+    let random_vectors = (0..POINT_COUNT)
+      .map(|_| Vec3::random_in(-1.0, 1.0).unit_vector())
+      .collect();
+
+    Perlin {
+      random_vectors,
+      perm_x: generate_perm(),
+      perm_y: generate_perm(),
+      perm_z: generate_perm(),
+    }
+  }
+
+  pub fn noise(&self, point: Vec3) -> f64 {
+    let u = point.x() - point.x().floor();
+    let v = point.y() - point.y().floor();
+    let w = point.z() - point.z().floor();
+
+    let i = point.x().floor() as i64;
+    let j = point.y().floor() as i64;
+    let k = point.z().floor() as i64;
+
+    let mut c = [[[Vec3::fill(0.0); 2]; 2]; 2];
+
+    for (di, plane) in c.iter_mut().enumerate() {
+      for (dj, row) in plane.iter_mut().enumerate() {
+        for (dk, corner) in row.iter_mut().enumerate() {
+          let index = self.perm_x[wrap(i + di as i64)]
+            ^ self.perm_y[wrap(j + dj as i64)]
+            ^ self.perm_z[wrap(k + dk as i64)];
+          *corner = self.random_vectors[index];
+        }
+      }
+    }
+
+    perlin_interp(&c, u, v, w)
+  }
+
+  // sums several octaves of noise, each at double the frequency and half the weight of the last
+  pub fn turb(&self, point: Vec3, depth: usize) -> f64 {
+    let mut accum = 0.0;
+    let mut temp_point = point;
+    let mut weight = 1.0;
+
+    for _ in 0..depth {
+      accum += weight * self.noise(temp_point);
+      weight *= 0.5;
+      temp_point = 2.0 * temp_point;
+    }
+
+    accum.abs()
+  }
+}
+
+fn wrap(n: i64) -> usize {
+  (n & (POINT_COUNT as i64 - 1)) as usize
+}
+
+fn generate_perm() -> Vec<usize> {
+  let mut perm: Vec<usize> = (0..POINT_COUNT).collect();
+
+  for i in (1..POINT_COUNT).rev() {
+    let target = random_int(0, i as i64) as usize;
+    perm.swap(i, target);
+  }
+
+  perm
+}
+
+fn perlin_interp(c: &[[[Vec3; 2]; 2]; 2], u: f64, v: f64, w: f64) -> f64 {
+  // hermite smoothing hides the grid lines of plain trilinear interpolation
+  let uu = u * u * (3.0 - 2.0 * u);
+  let vv = v * v * (3.0 - 2.0 * v);
+  let ww = w * w * (3.0 - 2.0 * w);
+  let mut accum = 0.0;
+
+  for (i, plane) in c.iter().enumerate() {
+    for (j, row) in plane.iter().enumerate() {
+      for (k, corner) in row.iter().enumerate() {
+        let fi = i as f64;
+        let fj = j as f64;
+        let fk = k as f64;
+        let weight = Vec3(u - fi, v - fj, w - fk);
+        accum += (fi * uu + (1.0 - fi) * (1.0 - uu))
+          * (fj * vv + (1.0 - fj) * (1.0 - vv))
+          * (fk * ww + (1.0 - fk) * (1.0 - ww))
+          * corner.dot(weight);
+      }
+    }
+  }
+
+  accum
+}
@@ -0,0 +1,66 @@
+use std::sync::Arc;
+
+use crate::aabb::Aabb;
+use crate::hittable::{HitRecord, Hittable};
+use crate::material::{DefaultMaterial, Material};
+use crate::onb::Onb;
+use crate::ray::Ray;
+use crate::vec3::Vec3;
+
+// the infinite plane through point, facing along normal. it has no bounding box,
+// so a BvhNode keeps it out of the tree and tests it on every ray.
+// (u, v) are distances from point along two axes of the plane, textures repeat over them
+#[derive(Debug)]
+pub struct Plane {
+  pub point: Vec3,
+  pub material: Arc<dyn Material>,
+  axes: Onb,
+}
+
+impl Plane {
+  pub fn new(point: Vec3, normal: Vec3) -> Plane {
+    Plane {
+      point,
+      material: Arc::new(DefaultMaterial::new()),
+      axes: Onb::from_w(normal),
+    }
+  }
+
+  pub fn set_material(&mut self, material: Arc<dyn Material>) {
+    self.material = material;
+  }
+
+  pub fn normal(&self) -> Vec3 {
+    self.axes.w
+  }
+}
+
+impl Hittable for Plane {
+  fn hit<'a>(&'a self, ray: &Ray, t_min: f64, t_max: f64, record: &mut HitRecord<'a>) -> bool {
+    let normal = self.axes.w;
+    let denominator = normal.dot(ray.direction);
+
+    if denominator.abs() < 1.0e-8 {
+      return false;
+    }
+
+    let t = normal.dot(self.point - ray.origin) / denominator;
+    if t <= t_min || t >= t_max {
+      return false;
+    }
+
+    let point = ray.at(t);
+    let planar = point - self.point;
+
+    record.set_t(t);
+    record.set_point(point);
+    record.set_uv(planar.dot(self.axes.u), planar.dot(self.axes.v));
+    record.set_face_normal(ray, normal);
+    record.set_material(self.material.as_ref());
+    true
+  }
+
+  fn bounding_box(&self, _time0: f64, _time1: f64, _output_box: &mut Aabb) -> bool {
+    false
+  }
+}
@@ -0,0 +1,418 @@
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+use crate::hittable::HitRecord;
+use crate::material::{Material, ScatterRecord};
+use crate::microfacet::{fresnel_dielectric, TrowbridgeReitz};
+use crate::onb::Onb;
+use crate::ray::Ray;
+use crate::sampler::{sample_1d, sample_2d};
+use crate::texture::{SolidColor, Texture};
+use crate::vec3::Vec3;
+
+// the clear coat is a fixed, fairly glossy layer of varnish over everything else
+const CLEARCOAT_ROUGHNESS: f64 = 0.1;
+
+// one material for most surfaces, after the Disney principled BSDF: a diffuse base with sheen,
+// a GGX specular layer that turns into colored metal with metallic, rough glass with
+// transmission and a clear coat on top. every parameter runs from 0 to 1, except the index of
+// refraction, and can be driven by a texture, whose channels are averaged for the scalar ones
+// like the original, the retro-reflection of rough diffuse surfaces gives back a little more
+// light than arrives at grazing angles
+#[derive(Debug)]
+pub struct Principled {
+  base_color: Arc<dyn Texture>,
+  metallic: Arc<dyn Texture>,
+  roughness: Arc<dyn Texture>,
+  specular: Arc<dyn Texture>,
+  specular_tint: Arc<dyn Texture>,
+  sheen: Arc<dyn Texture>,
+  clearcoat: Arc<dyn Texture>,
+  transmission: Arc<dyn Texture>,
+  ior: Arc<dyn Texture>,
+}
+
+fn constant(value: f64) -> Arc<dyn Texture> {
+  Arc::new(SolidColor::new(Vec3::fill(value)))
+}
+
+fn scalar(texture: &Arc<dyn Texture>, record: &HitRecord) -> f64 {
+  let color = texture.value(record.u, record.v, record.point);
+  (color.x() + color.y() + color.z()) / 3.0
+}
+
+fn schlick_weight(cosine: f64) -> f64 {
+  (1.0 - cosine.clamp(0.0, 1.0)).powi(5)
+}
+
+impl Principled {
+  // a plastic-like dielectric of medium roughness
+  pub fn new(base_color: Vec3) -> Principled {
+    Principled::from_texture(Arc::new(SolidColor::new(base_color)))
+  }
+  pub fn from_texture(base_color: Arc<dyn Texture>) -> Principled {
+    Principled {
+      base_color,
+      metallic: constant(0.0),
+      roughness: constant(0.5),
+      specular: constant(0.5),
+      specular_tint: constant(0.0),
+      sheen: constant(0.0),
+      clearcoat: constant(0.0),
+      transmission: constant(0.0),
+      ior: constant(1.5),
+    }
+  }
+
+  pub fn set_base_color(&mut self, base_color: Vec3) {
+    self.base_color = Arc::new(SolidColor::new(base_color));
+  }
+  pub fn set_base_color_texture(&mut self, texture: Arc<dyn Texture>) {
+    self.base_color = texture;
+  }
+  // 0 for a dielectric, 1 for a metal reflecting in the base color
+  pub fn set_metallic(&mut self, metallic: f64) {
+    self.metallic = constant(metallic);
+  }
+  pub fn set_metallic_texture(&mut self, texture: Arc<dyn Texture>) {
+    self.metallic = texture;
+  }
+  // 0 for a mirror finish, 1 for a fully matte one
+  pub fn set_roughness(&mut self, roughness: f64) {
+    self.roughness = constant(roughness);
+  }
+  pub fn set_roughness_texture(&mut self, texture: Arc<dyn Texture>) {
+    self.roughness = texture;
+  }
+  // scales the Fresnel reflectance of the dielectric, 0.5 reflects exactly as the index of refraction says
+  pub fn set_specular(&mut self, specular: f64) {
+    self.specular = constant(specular);
+  }
+  pub fn set_specular_texture(&mut self, texture: Arc<dyn Texture>) {
+    self.specular = texture;
+  }
+  // tints the dielectric reflection toward the hue of the base color
+  pub fn set_specular_tint(&mut self, specular_tint: f64) {
+    self.specular_tint = constant(specular_tint);
+  }
+  pub fn set_specular_tint_texture(&mut self, texture: Arc<dyn Texture>) {
+    self.specular_tint = texture;
+  }
+  // a soft glow at grazing angles, for cloth
+  pub fn set_sheen(&mut self, sheen: f64) {
+    self.sheen = constant(sheen);
+  }
+  pub fn set_sheen_texture(&mut self, texture: Arc<dyn Texture>) {
+    self.sheen = texture;
+  }
+  pub fn set_clearcoat(&mut self, clearcoat: f64) {
+    self.clearcoat = constant(clearcoat);
+  }
+  pub fn set_clearcoat_texture(&mut self, texture: Arc<dyn Texture>) {
+    self.clearcoat = texture;
+  }
+  // 0 for an opaque surface, 1 for glass tinted by the base color
+  pub fn set_transmission(&mut self, transmission: f64) {
+    self.transmission = constant(transmission);
+  }
+  pub fn set_transmission_texture(&mut self, texture: Arc<dyn Texture>) {
+    self.transmission = texture;
+  }
+  pub fn set_ior(&mut self, ior: f64) {
+    self.ior = constant(ior);
+  }
+  pub fn set_ior_texture(&mut self, texture: Arc<dyn Texture>) {
+    self.ior = texture;
+  }
+
+  fn lobes(&self, record: &HitRecord) -> Lobes {
+    let base_color = self.base_color.value(record.u, record.v, record.point);
+    let metallic = scalar(&self.metallic, record).clamp(0.0, 1.0);
+    let transmission = scalar(&self.transmission, record).clamp(0.0, 1.0);
+
+    // eta is the index of the side the light enters over the side it leaves
+    let ior = scalar(&self.ior, record).max(1.0e-3);
+    let roughness = scalar(&self.roughness, record).clamp(0.0, 1.0);
+    let eta = if record.front_face { ior } else { 1.0 / ior };
+
+    let tint = if base_color.luminance() > 0.0 {
+      base_color / base_color.luminance()
+    } else {
+      Vec3::fill(1.0)
+    };
+
+    Lobes {
+      base_color,
+      tint,
+      metallic,
+      specular: scalar(&self.specular, record).max(0.0),
+      specular_tint: scalar(&self.specular_tint, record).clamp(0.0, 1.0),
+      sheen: scalar(&self.sheen, record).max(0.0),
+      clearcoat: scalar(&self.clearcoat, record).clamp(0.0, 1.0),
+      diffuse_weight: (1.0 - metallic) * (1.0 - transmission),
+      transmission_weight: (1.0 - metallic) * transmission,
+      eta,
+      roughness,
+      distribution: TrowbridgeReitz::from_roughness(roughness),
+      coat: TrowbridgeReitz::from_roughness(CLEARCOAT_ROUGHNESS),
+    }
+  }
+}
+
+// the parameters at one point of the surface
+struct Lobes {
+  base_color: Vec3,
+  tint: Vec3,
+  metallic: f64,
+  specular: f64,
+  specular_tint: f64,
+  sheen: f64,
+  clearcoat: f64,
+  diffuse_weight: f64,
+  transmission_weight: f64,
+  eta: f64,
+  roughness: f64,
+  distribution: TrowbridgeReitz,
+  coat: TrowbridgeReitz,
+}
+
+impl Lobes {
+  // the share of the light the dielectric reflects, the rest is refracted or reaches the base
+  fn dielectric_reflectance(&self, cosine: f64) -> f64 {
+    (2.0 * self.specular * fresnel_dielectric(cosine, self.eta)).min(1.0)
+  }
+
+  fn specular_fresnel(&self, cosine: f64) -> Vec3 {
+    let metal = Vec3::lerp(schlick_weight(cosine), self.base_color, Vec3::fill(1.0));
+    let dielectric = Vec3::lerp(self.specular_tint, Vec3::fill(1.0), self.tint)
+      * self.dielectric_reflectance(cosine);
+    Vec3::lerp(self.metallic, dielectric, metal)
+  }
+
+  // chances of sampling the diffuse, specular, transmission and clear coat lobes, from wo alone
+  fn probabilities(&self, wo: Vec3) -> [f64; 4] {
+    let weights = [
+      self.diffuse_weight * self.base_color.luminance().max(0.0),
+      self.specular_fresnel(wo.z()).luminance().max(0.01),
+      self.transmission_weight * (1.0 - self.dielectric_reflectance(wo.z())),
+      0.25 * self.clearcoat * (0.04 + 0.96 * schlick_weight(wo.z())),
+    ];
+    let total: f64 = weights.iter().sum();
+    weights.map(|weight| weight / total)
+  }
+
+  // the half vector of a refraction from wo to wi, facing the outside, if the pair is possible
+  fn refraction_half_vector(&self, wo: Vec3, wi: Vec3) -> Option<Vec3> {
+    let wm = (wo + wi * self.eta).unit_vector();
+    let wm = if wm.z() < 0.0 { -wm } else { wm };
+    if wm.dot(wi) >= 0.0 || wm.dot(wo) <= 0.0 {
+      return None;
+    }
+    Some(wm)
+  }
+
+  // bsdf times the cosine, both directions in the local frame and wo above the surface
+  fn evaluate(&self, wo: Vec3, wi: Vec3) -> Vec3 {
+    if wi.z() < 0.0 {
+      return self.evaluate_transmission(wo, wi);
+    }
+    if wi.z() == 0.0 {
+      return Vec3::fill(0.0);
+    }
+
+    let wm = (wo + wi).unit_vector();
+    let cos_d = wi.dot(wm);
+
+    // the Disney diffuse gets darker at grazing angles when smooth and retro-reflects when rough
+    let fd90 = 0.5 + 2.0 * self.roughness * cos_d * cos_d;
+    let fresnel_o = 1.0 + (fd90 - 1.0) * schlick_weight(wo.z());
+    let fresnel_i = 1.0 + (fd90 - 1.0) * schlick_weight(wi.z());
+    let diffuse = self.base_color * (fresnel_o * fresnel_i / PI);
+    let sheen = Vec3::lerp(0.5, Vec3::fill(1.0), self.tint) * (self.sheen * schlick_weight(cos_d));
+
+    let specular = self.specular_fresnel(wo.dot(wm))
+      * (self.distribution.d(wm) * self.distribution.g(wo, wi) / (4.0 * wo.z() * wi.z()));
+
+    let coat_fresnel = 0.04 + 0.96 * schlick_weight(wo.dot(wm));
+    let clearcoat = 0.25 * self.clearcoat * coat_fresnel * self.coat.d(wm) * self.coat.g(wo, wi)
+      / (4.0 * wo.z() * wi.z());
+
+    ((diffuse + sheen) * self.diffuse_weight + specular + Vec3::fill(clearcoat)) * wi.z()
+  }
+
+  fn evaluate_transmission(&self, wo: Vec3, wi: Vec3) -> Vec3 {
+    if self.transmission_weight <= 0.0 {
+      return Vec3::fill(0.0);
+    }
+    let wm = match self.refraction_half_vector(wo, wi) {
+      Some(wm) => wm,
+      None => return Vec3::fill(0.0),
+    };
+
+    // like Dielectric, radiance is not rescaled by eta^2 on the way through
+    let denominator = (wi.dot(wm) + wo.dot(wm) / self.eta).powi(2);
+    let transmitted = (1.0 - self.dielectric_reflectance(wo.dot(wm)))
+      * self.distribution.d(wm)
+      * self.distribution.g(wo, wi)
+      * (wi.dot(wm) * wo.dot(wm) / (wo.z() * denominator)).abs();
+    self.base_color * (self.transmission_weight * transmitted)
+  }
+
+  // density over solid angle of sample picking wi, mixed over the lobes
+  fn pdf(&self, wo: Vec3, wi: Vec3) -> f64 {
+    let [diffuse, specular, transmission, clearcoat] = self.probabilities(wo);
+
+    if wi.z() < 0.0 {
+      if transmission <= 0.0 {
+        return 0.0;
+      }
+      return match self.refraction_half_vector(wo, wi) {
+        Some(wm) => {
+          // refraction squeezes the density of the half vector, like reflection does
+          let denominator = (wi.dot(wm) + wo.dot(wm) / self.eta).powi(2);
+          transmission * self.distribution.pdf_visible_normal(wo, wm) * wi.dot(wm).abs() / denominator
+        }
+        None => 0.0,
+      };
+    }
+    if wi.z() == 0.0 {
+      return 0.0;
+    }
+
+    let wm = (wo + wi).unit_vector();
+    let reflection = 4.0 * wo.dot(wm);
+    diffuse * wi.z() / PI
+      + specular * self.distribution.pdf_visible_normal(wo, wm) / reflection
+      + clearcoat * self.coat.pdf_visible_normal(wo, wm) / reflection
+  }
+
+  // picks one lobe, then a direction from it
+  fn sample(&self, wo: Vec3) -> Option<Vec3> {
+    let [diffuse, specular, transmission, _] = self.probabilities(wo);
+    let lobe = sample_1d();
+    let (u, v) = sample_2d();
+
+    let wi = if lobe < diffuse {
+      Vec3::cosine_from_square(u, v)
+    } else if lobe < diffuse + specular {
+      let wm = self.distribution.sample_visible_normal(wo, u, v);
+      2.0 * wo.dot(wm) * wm - wo
+    } else if lobe < diffuse + specular + transmission {
+      let wm = self.distribution.sample_visible_normal(wo, u, v);
+      let cos_o = wo.dot(wm);
+      let sin2_t = (1.0 - cos_o * cos_o) / (self.eta * self.eta);
+      if sin2_t >= 1.0 {
+        return None;
+      }
+      let cos_t = (1.0 - sin2_t).sqrt();
+      -wo / self.eta + (cos_o / self.eta - cos_t) * wm
+    } else {
+      let wm = self.coat.sample_visible_normal(wo, u, v);
+      2.0 * wo.dot(wm) * wm - wo
+    };
+
+    // a lobe only counts the directions on its own side of the surface
+    let reflects = lobe < diffuse + specular || lobe >= diffuse + specular + transmission;
+    if (wi.z() > 0.0) != reflects || wi.z() == 0.0 {
+      return None;
+    }
+    Some(wi)
+  }
+}
+
+impl Material for Principled {
+  fn scatter(&self, ray_in: &Ray, record: &HitRecord) -> Option<ScatterRecord> {
+    let frame = Onb::from_w(record.normal);
+    let wo = frame.to_local(-ray_in.direction.unit_vector());
+    if wo.z() <= 0.0 {
+      return None;
+    }
+
+    let lobes = self.lobes(record);
+    let wi = lobes.sample(wo)?;
+
+    // weighted by the whole mixture, so any lobe could have picked the direction
+    let pdf = lobes.pdf(wo, wi);
+    if pdf <= 0.0 {
+      return None;
+    }
+    let mut scattered = Ray::new(record.point, frame.local(wi));
+    scattered.time = ray_in.time;
+    Some(ScatterRecord::new(lobes.evaluate(wo, wi) / pdf, scattered))
+  }
+  // even the sharpest lobes keep a finite density, so light sampling always applies
+  fn is_diffuse(&self) -> bool {
+    true
+  }
+  fn evaluate(&self, ray_in: &Ray, record: &HitRecord, direction: Vec3) -> Vec3 {
+    let frame = Onb::from_w(record.normal);
+    let wo = frame.to_local(-ray_in.direction.unit_vector());
+    if wo.z() <= 0.0 {
+      return Vec3::fill(0.0);
+    }
+    self.lobes(record).evaluate(wo, frame.to_local(direction.unit_vector()))
+  }
+  fn scattering_pdf(&self, ray_in: &Ray, record: &HitRecord, scattered: &Ray) -> f64 {
+    let frame = Onb::from_w(record.normal);
+    let wo = frame.to_local(-ray_in.direction.unit_vector());
+    if wo.z() <= 0.0 {
+      return 0.0;
+    }
+    self.lobes(record).pdf(wo, frame.to_local(scattered.direction.unit_vector()))
+  }
+  fn albedo(&self, record: &HitRecord) -> Vec3 {
+    self.base_color.value(record.u, record.v, record.point)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+
+  use super::*;
+
+  #[test]
+  fn test_principled_sampling_matches_evaluate() {
+    let mut material = Principled::new(Vec3(0.8, 0.3, 0.2));
+    material.set_roughness(0.4);
+    material.set_sheen(0.5);
+    material.set_clearcoat(1.0);
+    material.set_transmission(0.5);
+
+    let mut record = HitRecord::new();
+    record.normal = Vec3(0.0, 1.0, 0.0);
+    record.front_face = true;
+    let ray_in = Ray::new(Vec3(-1.0, 1.0, 0.3), Vec3(1.0, -1.0, -0.3));
+
+    // the weight of every sampled direction is its bsdf * cosine over its density
+    let mut refracted = 0;
+    for _ in 0..200 {
+      let scatter = match material.scatter(&ray_in, &record) {
+        Some(scatter) => scatter,
+        None => continue,
+      };
+      if scatter.scattered.direction.y() < 0.0 {
+        refracted += 1;
+      }
+      let pdf = material.scattering_pdf(&ray_in, &record, &scatter.scattered);
+      let expected = material.evaluate(&ray_in, &record, scatter.scattered.direction) / pdf;
+      assert!((scatter.attenuation - expected).len() < 1.0e-6);
+    }
+    assert!(refracted > 0);
+
+    // the mixture density integrates to 1 over the sphere, less what refraction cannot reach
+    let lobes = material.lobes(&record);
+    let wo = Vec3(0.6, 0.0, 0.8);
+    let steps = 400;
+    let mut integral = 0.0;
+    for i in 0..steps {
+      for j in 0..steps {
+        let cos_theta = -1.0 + 2.0 * (i as f64 + 0.5) / steps as f64;
+        let phi = 2.0 * PI * (j as f64 + 0.5) / steps as f64;
+        let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+        let wi = Vec3(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta);
+        integral += lobes.pdf(wo, wi) * 4.0 * PI / (steps * steps) as f64;
+      }
+    }
+    assert!(integral > 0.95 && integral < 1.02);
+  }
+}
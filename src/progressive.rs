@@ -0,0 +1,258 @@
+use std::time::{Duration, Instant};
+
+use crate::camera::Camera;
+use crate::framebuffer::Framebuffer;
+use crate::hittable::Hittable;
+use crate::renderer::{self, Renderer};
+use crate::vec3::Vec3;
+
+// the error estimate needs a few passes before it can be trusted
+const MIN_PASSES: usize = 4;
+
+// dark pixels are measured against this luminance, so noise in the shadows doesn't dominate
+const MIN_LUMINANCE: f64 = 0.01;
+
+// renders an image one pass at a time and keeps the running average, so a preview can be
+// shown after every pass. each pass takes the renderer's samples per pixel, continuing every
+// pixel's sample sequence where the last pass stopped.
+// the spread of the pass averages gives a per-pixel estimate of the remaining error, and
+// rendering stops when it is small enough, the time budget is spent or max passes are done
+pub struct ProgressiveRenderer {
+  renderer: Renderer,
+  // sum of the pass averages of every pixel, row by row from the top like a framebuffer
+  sum: Vec<Vec3>,
+  // sum of the luminance of the pass averages and of its square
+  sum_luminance: Vec<f64>,
+  sum_squares: Vec<f64>,
+  passes: usize,
+  // rendering time of the passes since the last reset
+  elapsed: Duration,
+  max_passes: usize,
+  // 0 never stops on the error estimate
+  error_threshold: f64,
+  time_budget: Option<Duration>,
+}
+
+impl ProgressiveRenderer {
+  pub fn new(renderer: Renderer) -> ProgressiveRenderer {
+    let pixel_count = renderer.image_width * renderer.image_height;
+    ProgressiveRenderer {
+      renderer,
+      sum: vec![Vec3::fill(0.0); pixel_count],
+      sum_luminance: vec![0.0; pixel_count],
+      sum_squares: vec![0.0; pixel_count],
+      passes: 0,
+      elapsed: Duration::ZERO,
+      max_passes: 100,
+      error_threshold: 0.0,
+      time_budget: None,
+    }
+  }
+
+  pub fn set_max_passes(&mut self, max_passes: usize) {
+    self.max_passes = max_passes.max(1);
+  }
+
+  // stop once the mean relative standard error of the pixels drops below the threshold,
+  // e.g. 0.01 for about 1% noise. 0 turns it off
+  pub fn set_error_threshold(&mut self, error_threshold: f64) {
+    self.error_threshold = error_threshold;
+  }
+
+  // stop after the first pass that ends past the budget, counted since the last reset
+  pub fn set_time_budget(&mut self, time_budget: Option<Duration>) {
+    self.time_budget = time_budget;
+  }
+
+  pub fn renderer(&self) -> &Renderer {
+    &self.renderer
+  }
+
+  // changing the settings of the renderer should be followed by a reset
+  pub fn renderer_mut(&mut self) -> &mut Renderer {
+    &mut self.renderer
+  }
+
+  // starts over, e.g. after the camera or the scene has moved
+  pub fn reset(&mut self) {
+    let pixel_count = self.renderer.image_width * self.renderer.image_height;
+    self.sum = vec![Vec3::fill(0.0); pixel_count];
+    self.sum_luminance = vec![0.0; pixel_count];
+    self.sum_squares = vec![0.0; pixel_count];
+    self.passes = 0;
+    self.elapsed = Duration::ZERO;
+  }
+
+  pub fn passes(&self) -> usize {
+    self.passes
+  }
+
+  // with adaptive sampling pixels may have taken more
+  pub fn samples_per_pixel(&self) -> usize {
+    self.passes * self.renderer.samples_per_pixel()
+  }
+
+  // renders one more pass into the accumulation buffer
+  pub fn pass(&mut self, world: &dyn Hittable, camera: &dyn Camera) {
+    // the clock only runs with a budget, Instant panics on wasm
+    let start = self.time_budget.map(|_| Instant::now());
+    let framebuffer = self.renderer.render_pass(world, camera, self.passes);
+
+    for (index, color) in framebuffer.pixels.into_iter().enumerate() {
+      let y = color.luminance();
+      self.sum[index] = self.sum[index] + color;
+      self.sum_luminance[index] += y;
+      self.sum_squares[index] += y * y;
+    }
+
+    self.passes += 1;
+    if let Some(start) = start {
+      self.elapsed += start.elapsed();
+    }
+  }
+
+  // the average of all passes so far, black before the first one
+  pub fn image(&self) -> Framebuffer {
+    let mut framebuffer = Framebuffer::new(self.renderer.image_width, self.renderer.image_height);
+    if self.passes > 0 {
+      let n = self.passes as f64;
+      for (pixel, sum) in framebuffer.pixels.iter_mut().zip(self.sum.iter()) {
+        *pixel = *sum / n;
+      }
+    }
+    framebuffer
+  }
+
+  // standard error of the mean luminance of a pixel, relative to that luminance.
+  // infinite until there are two passes to compare
+  pub fn pixel_error(&self, index: usize) -> f64 {
+    renderer::relative_error(
+      self.passes,
+      self.sum_luminance[index],
+      self.sum_squares[index],
+      MIN_LUMINANCE,
+    )
+  }
+
+  // the mean relative error of all pixels
+  pub fn error(&self) -> f64 {
+    let count = self.sum.len();
+    if count == 0 {
+      return 0.0;
+    }
+    (0..count).map(|index| self.pixel_error(index)).sum::<f64>() / count as f64
+  }
+
+  pub fn is_converged(&self) -> bool {
+    self.error_threshold > 0.0 && self.passes >= MIN_PASSES && self.error() < self.error_threshold
+  }
+
+  pub fn is_done(&self) -> bool {
+    if self.passes >= self.max_passes || self.is_converged() {
+      return true;
+    }
+    match self.time_budget {
+      Some(budget) => self.elapsed >= budget,
+      None => false,
+    }
+  }
+
+  // renders passes until done and returns the averaged image
+  pub fn render(&mut self, world: &dyn Hittable, camera: &dyn Camera) -> Framebuffer {
+    while !self.is_done() {
+      self.pass(world, camera);
+    }
+    self.image()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+
+  use std::sync::Arc;
+
+  use super::*;
+  use crate::camera::LensCamera;
+  use crate::hittable_list::HittableList;
+  use crate::material::Lambertian;
+  use crate::sphere::Sphere;
+
+  fn scene() -> (HittableList, LensCamera) {
+    let mut sphere = Sphere::new(Vec3(0.0, 0.0, -1.0), 0.5);
+    sphere.set_material(Arc::new(Lambertian::new(Vec3(0.5, 0.5, 0.5))));
+    let mut world = HittableList::new();
+    world.add(Box::new(sphere));
+    let camera = LensCamera::new(
+      Vec3::fill(0.0),
+      Vec3(0.0, 0.0, -1.0),
+      Vec3(0.0, 1.0, 0.0),
+      60.0,
+      1.5,
+      0.0,
+      1.0,
+    );
+    (world, camera)
+  }
+
+  #[test]
+  fn test_passes_continue_the_samples() {
+    let (world, camera) = scene();
+    let renderer = || {
+      let mut renderer = Renderer::new(12, 8);
+      renderer.set_samples_per_pixel(2);
+      renderer.set_threads(1);
+      renderer
+    };
+
+    let mut progressive = ProgressiveRenderer::new(renderer());
+    progressive.set_max_passes(3);
+    progressive.pass(&world, &camera);
+
+    // the first pass is a plain render
+    let reference = renderer().render(&world, &camera);
+    let same = |a: &[Vec3], b: &[Vec3]| a.iter().zip(b.iter()).all(|(x, y)| x.eq(*y));
+    assert!(same(&progressive.image().pixels, &reference.pixels));
+
+    // later passes draw new samples instead of repeating the first ones
+    let image = progressive.render(&world, &camera);
+    assert_eq!(progressive.passes(), 3);
+    assert_eq!(progressive.samples_per_pixel(), 6);
+    assert!(!same(&image.pixels, &reference.pixels));
+  }
+
+  #[test]
+  fn test_dark_pixels_keep_their_own_error() {
+    let mut progressive = ProgressiveRenderer::new(Renderer::new(1, 1));
+    // pass averages of 0.04 and 0.06, a mean of 0.05 with a standard error of 0.01
+    progressive.passes = 2;
+    progressive.sum_luminance[0] = 0.1;
+    progressive.sum_squares[0] = 0.04 * 0.04 + 0.06 * 0.06;
+    assert!((progressive.pixel_error(0) - 0.2).abs() < 1.0e-9);
+
+    // only pixels darker than MIN_LUMINANCE are measured against it
+    progressive.sum_luminance[0] = 0.01;
+    progressive.sum_squares[0] = 0.004 * 0.004 + 0.006 * 0.006;
+    assert!((progressive.pixel_error(0) - 0.1).abs() < 1.0e-9);
+  }
+
+  #[test]
+  fn test_stops_when_converged() {
+    let (world, camera) = scene();
+
+    let mut renderer = Renderer::new(8, 6);
+    renderer.set_samples_per_pixel(4);
+    let mut progressive = ProgressiveRenderer::new(renderer);
+    progressive.set_max_passes(1000);
+    progressive.set_error_threshold(0.05);
+    progressive.render(&world, &camera);
+
+    assert!(progressive.is_converged());
+    assert!(progressive.passes() >= MIN_PASSES && progressive.passes() < 1000);
+    assert!(progressive.error() < 0.05);
+
+    progressive.reset();
+    assert_eq!(progressive.passes(), 0);
+    assert!(!progressive.is_done());
+    assert!(progressive.image().pixels.iter().all(|color| color.eq(Vec3::fill(0.0))));
+  }
+}
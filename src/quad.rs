@@ -0,0 +1,140 @@
+use std::sync::Arc;
+
+use crate::aabb::Aabb;
+use crate::hittable::{HitRecord, Hittable};
+use crate::material::{DefaultMaterial, Material};
+use crate::ray::Ray;
+use crate::sampler::sample_2d;
+use crate::vec3::Vec3;
+
+// same padding as triangles, a quad is flat along at least one axis when it is axis aligned
+const BOX_PADDING: f64 = 1.0e-4;
+
+// the parallelogram spanned by u and v from the corner q, facing along u x v.
+// (u, v) of a hit are its coordinates along the two edges, both in [0, 1]
+#[derive(Debug)]
+pub struct Quad {
+  pub q: Vec3,
+  pub u: Vec3,
+  pub v: Vec3,
+  pub material: Arc<dyn Material>,
+  normal: Vec3,
+  // the plane holds the points p with normal . p == d
+  d: f64,
+  // turns a point of the plane into its edge coordinates
+  w: Vec3,
+  area: f64,
+}
+
+impl Quad {
+  pub fn new(q: Vec3, u: Vec3, v: Vec3) -> Quad {
+    let n = u.cross(v);
+    let normal = n.unit_vector();
+
+    Quad {
+      q,
+      u,
+      v,
+      material: Arc::new(DefaultMaterial::new()),
+      normal,
+      d: normal.dot(q),
+      w: n / n.dot(n),
+      area: n.len(),
+    }
+  }
+
+  pub fn set_material(&mut self, material: Arc<dyn Material>) {
+    self.material = material;
+  }
+
+  pub fn normal(&self) -> Vec3 {
+    self.normal
+  }
+}
+
+impl Hittable for Quad {
+  fn hit<'a>(&'a self, ray: &Ray, t_min: f64, t_max: f64, record: &mut HitRecord<'a>) -> bool {
+    let denominator = self.normal.dot(ray.direction);
+
+    // parallel to the plane
+    if denominator.abs() < 1.0e-8 {
+      return false;
+    }
+
+    let t = (self.d - self.normal.dot(ray.origin)) / denominator;
+    if t <= t_min || t >= t_max {
+      return false;
+    }
+
+    let point = ray.at(t);
+    let planar = point - self.q;
+    let alpha = self.w.dot(planar.cross(self.v));
+    let beta = self.w.dot(self.u.cross(planar));
+
+    if !(0.0..=1.0).contains(&alpha) || !(0.0..=1.0).contains(&beta) {
+      return false;
+    }
+
+    record.set_t(t);
+    record.set_point(point);
+    record.set_uv(alpha, beta);
+    record.set_face_normal(ray, self.normal);
+    record.set_material(self.material.as_ref());
+    true
+  }
+
+  fn bounding_box(&self, _time0: f64, _time1: f64, output_box: &mut Aabb) -> bool {
+    let corners = [self.q, self.q + self.u, self.q + self.v, self.q + self.u + self.v];
+    let bounds = corners
+      .iter()
+      .fold(Aabb::empty(), |acc, corner| Aabb::surrounding_box(acc, Aabb::new(*corner, *corner)));
+    *output_box = bounds.padded(BOX_PADDING);
+    true
+  }
+
+  // uniform over the area, converted to solid angle at origin
+  fn pdf_value(&self, origin: Vec3, direction: Vec3) -> f64 {
+    let mut record = HitRecord::new();
+    if !self.hit(&Ray::new(origin, direction), 0.001, f64::INFINITY, &mut record) {
+      return 0.0;
+    }
+
+    let distance_squared = record.t * record.t * direction.length_squared();
+    let cosine = direction.dot(self.normal).abs() / direction.len();
+
+    distance_squared / (cosine * self.area)
+  }
+
+  fn random(&self, origin: Vec3) -> Vec3 {
+    let (s, t) = sample_2d();
+    self.q + s * self.u + t * self.v - origin
+  }
+}
+
+#[cfg(test)]
+mod tests {
+
+  use super::*;
+
+  #[test]
+  fn test_quad_hit_uv_and_normal() {
+    let quad = Quad::new(Vec3(-1.0, -1.0, -2.0), Vec3(2.0, 0.0, 0.0), Vec3(0.0, 4.0, 0.0));
+    let mut record = HitRecord::new();
+
+    let ray = Ray::new(Vec3(0.5, 2.0, 0.0), Vec3(0.0, 0.0, -1.0));
+    assert!(quad.hit(&ray, 0.001, f64::INFINITY, &mut record));
+    assert!((record.t - 2.0).abs() < 1.0e-9);
+    assert!((record.u - 0.75).abs() < 1.0e-9);
+    assert!((record.v - 0.75).abs() < 1.0e-9);
+    assert!((record.normal - Vec3(0.0, 0.0, 1.0)).len() < 1.0e-9);
+    assert!(record.front_face);
+
+    let outside = Ray::new(Vec3(1.5, 0.0, 0.0), Vec3(0.0, 0.0, -1.0));
+    assert!(!quad.hit(&outside, 0.001, f64::INFINITY, &mut record));
+
+    let mut bounds = Aabb::empty();
+    assert!(quad.bounding_box(0.0, 1.0, &mut bounds));
+    assert!((bounds.minimum - Vec3(-1.0, -1.0, -2.0 - 0.5e-4)).len() < 1.0e-9);
+    assert!((bounds.maximum - Vec3(1.0, 3.0, -2.0 + 0.5e-4)).len() < 1.0e-9);
+  }
+}
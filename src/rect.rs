@@ -0,0 +1,153 @@
+use std::sync::Arc;
+
+use crate::aabb::Aabb;
+use crate::hittable::{HitRecord, Hittable};
+use crate::material::Material;
+use crate::quad::Quad;
+use crate::ray::Ray;
+use crate::vec3::Vec3;
+
+// rectangles on the planes z = k, y = k and x = k, facing the positive axis.
+// (u, v) run along the two other axes in the order of the name
+
+#[derive(Debug)]
+pub struct XyRect {
+  quad: Quad,
+}
+
+impl XyRect {
+  pub fn new(x0: f64, x1: f64, y0: f64, y1: f64, k: f64) -> XyRect {
+    XyRect {
+      quad: Quad::new(Vec3(x0, y0, k), Vec3(x1 - x0, 0.0, 0.0), Vec3(0.0, y1 - y0, 0.0)),
+    }
+  }
+
+  pub fn set_material(&mut self, material: Arc<dyn Material>) {
+    self.quad.set_material(material);
+  }
+}
+
+impl Hittable for XyRect {
+  fn hit<'a>(&'a self, ray: &Ray, t_min: f64, t_max: f64, record: &mut HitRecord<'a>) -> bool {
+    self.quad.hit(ray, t_min, t_max, record)
+  }
+
+  fn bounding_box(&self, time0: f64, time1: f64, output_box: &mut Aabb) -> bool {
+    self.quad.bounding_box(time0, time1, output_box)
+  }
+
+  fn pdf_value(&self, origin: Vec3, direction: Vec3) -> f64 {
+    self.quad.pdf_value(origin, direction)
+  }
+
+  fn random(&self, origin: Vec3) -> Vec3 {
+    self.quad.random(origin)
+  }
+}
+
+#[derive(Debug)]
+pub struct XzRect {
+  quad: Quad,
+}
+
+impl XzRect {
+  pub fn new(x0: f64, x1: f64, z0: f64, z1: f64, k: f64) -> XzRect {
+    // z x x points up, so the edges are given in that order and (u, v) swapped back on a hit
+    XzRect {
+      quad: Quad::new(Vec3(x0, k, z0), Vec3(0.0, 0.0, z1 - z0), Vec3(x1 - x0, 0.0, 0.0)),
+    }
+  }
+
+  pub fn set_material(&mut self, material: Arc<dyn Material>) {
+    self.quad.set_material(material);
+  }
+}
+
+impl Hittable for XzRect {
+  fn hit<'a>(&'a self, ray: &Ray, t_min: f64, t_max: f64, record: &mut HitRecord<'a>) -> bool {
+    if !self.quad.hit(ray, t_min, t_max, record) {
+      return false;
+    }
+    let (z, x) = (record.u, record.v);
+    record.set_uv(x, z);
+    true
+  }
+
+  fn bounding_box(&self, time0: f64, time1: f64, output_box: &mut Aabb) -> bool {
+    self.quad.bounding_box(time0, time1, output_box)
+  }
+
+  fn pdf_value(&self, origin: Vec3, direction: Vec3) -> f64 {
+    self.quad.pdf_value(origin, direction)
+  }
+
+  fn random(&self, origin: Vec3) -> Vec3 {
+    self.quad.random(origin)
+  }
+}
+
+#[derive(Debug)]
+pub struct YzRect {
+  quad: Quad,
+}
+
+impl YzRect {
+  pub fn new(y0: f64, y1: f64, z0: f64, z1: f64, k: f64) -> YzRect {
+    YzRect {
+      quad: Quad::new(Vec3(k, y0, z0), Vec3(0.0, y1 - y0, 0.0), Vec3(0.0, 0.0, z1 - z0)),
+    }
+  }
+
+  pub fn set_material(&mut self, material: Arc<dyn Material>) {
+    self.quad.set_material(material);
+  }
+}
+
+impl Hittable for YzRect {
+  fn hit<'a>(&'a self, ray: &Ray, t_min: f64, t_max: f64, record: &mut HitRecord<'a>) -> bool {
+    self.quad.hit(ray, t_min, t_max, record)
+  }
+
+  fn bounding_box(&self, time0: f64, time1: f64, output_box: &mut Aabb) -> bool {
+    self.quad.bounding_box(time0, time1, output_box)
+  }
+
+  fn pdf_value(&self, origin: Vec3, direction: Vec3) -> f64 {
+    self.quad.pdf_value(origin, direction)
+  }
+
+  fn random(&self, origin: Vec3) -> Vec3 {
+    self.quad.random(origin)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+
+  use super::*;
+
+  #[test]
+  fn test_rects_face_their_axis() {
+    let mut record = HitRecord::new();
+
+    let xz = XzRect::new(0.0, 2.0, 0.0, 4.0, 1.0);
+    let down = Ray::new(Vec3(0.5, 3.0, 3.0), Vec3(0.0, -1.0, 0.0));
+    assert!(xz.hit(&down, 0.001, f64::INFINITY, &mut record));
+    assert!((record.normal - Vec3(0.0, 1.0, 0.0)).len() < 1.0e-9);
+    assert!(record.front_face);
+    assert!((record.u - 0.25).abs() < 1.0e-9);
+    assert!((record.v - 0.75).abs() < 1.0e-9);
+
+    let yz = YzRect::new(0.0, 1.0, 0.0, 1.0, -1.0);
+    let left = Ray::new(Vec3(0.0, 0.5, 0.5), Vec3(-1.0, 0.0, 0.0));
+    assert!(yz.hit(&left, 0.001, f64::INFINITY, &mut record));
+    assert!(record.front_face);
+    assert!((record.point - Vec3(-1.0, 0.5, 0.5)).len() < 1.0e-9);
+
+    let xy = XyRect::new(0.0, 1.0, 0.0, 1.0, 0.0);
+    let from_behind = Ray::new(Vec3(0.5, 0.5, -1.0), Vec3(0.0, 0.0, 1.0));
+    assert!(xy.hit(&from_behind, 0.001, f64::INFINITY, &mut record));
+    assert!(!record.front_face);
+    assert!((record.normal - Vec3(0.0, 0.0, -1.0)).len() < 1.0e-9);
+  }
+}
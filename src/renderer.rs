@@ -0,0 +1,647 @@
+use std::sync::Mutex;
+use std::thread;
+
+use crate::aov::{self, Aovs};
+use crate::background::Background;
+use crate::camera::Camera;
+use crate::denoise::FeatureBuffers;
+use crate::framebuffer::Framebuffer;
+use crate::hittable::{HitRecord, Hittable};
+use crate::integrator::{Integrator, PathTracer};
+use crate::material::Material;
+use crate::ray::Ray;
+use crate::sampler::{self, Sampler, SobolSampler};
+use crate::scheduler::TileScheduler;
+use crate::utils::seed_random;
+use crate::vec3::Vec3;
+
+// mirrors and glass the features look through before settling on a surface
+const FEATURE_BOUNCES: usize = 4;
+
+// what the camera sees first in one pixel, see Aovs
+struct AovPixel {
+  albedo: Vec3,
+  normal: Vec3,
+  depth: f64,
+  position: Vec3,
+  // addresses, numbered once the whole image is done
+  material: usize,
+  object: usize,
+}
+
+pub struct Renderer {
+  pub image_width: usize,
+  pub image_height: usize,
+  samples_per_pixel: usize,
+  // adaptive sampling goes up to this many samples, 0 picks 8 times samples_per_pixel
+  max_samples_per_pixel: usize,
+  // 0 turns adaptive sampling off
+  error_threshold: f64,
+  max_depth: u64,
+  background: Background,
+  antialias: bool,
+  // 0 runs one thread per core
+  threads: usize,
+  tile_size: usize,
+  seed: u64,
+  // every worker thread renders with its own copy
+  sampler: Box<dyn Sampler>,
+  integrator: Box<dyn Integrator>,
+}
+
+impl Renderer {
+  pub fn new(image_width: usize, image_height: usize) -> Renderer {
+    Renderer {
+      image_width,
+      image_height,
+      samples_per_pixel: 1,
+      max_samples_per_pixel: 0,
+      error_threshold: 0.0,
+      max_depth: 50,
+      background: Background::sky(),
+      antialias: true,
+      threads: 0,
+      tile_size: 16,
+      seed: 0,
+      sampler: Box::new(SobolSampler::new()),
+      integrator: Box::new(PathTracer::new()),
+    }
+  }
+
+  pub fn set_samples_per_pixel(&mut self, samples_per_pixel: usize) {
+    self.samples_per_pixel = samples_per_pixel.max(1);
+  }
+
+  // with adaptive sampling a pixel takes samples_per_pixel samples first, then keeps adding
+  // batches of as many until the relative standard error of its luminance drops below the
+  // threshold, e.g. 0.02 for 2% noise, or it reaches max samples per pixel
+  pub fn set_error_threshold(&mut self, error_threshold: f64) {
+    self.error_threshold = error_threshold.max(0.0);
+  }
+
+  pub fn set_max_samples_per_pixel(&mut self, max_samples_per_pixel: usize) {
+    self.max_samples_per_pixel = max_samples_per_pixel;
+  }
+
+  pub fn set_max_depth(&mut self, max_depth: u64) {
+    self.max_depth = max_depth;
+  }
+
+  pub fn set_background(&mut self, background: Background) {
+    self.background = background;
+  }
+
+  // without antialiasing every sample goes through the pixel center instead of a random point inside it
+  pub fn set_antialias(&mut self, antialias: bool) {
+    self.antialias = antialias;
+  }
+
+  // 0 picks one thread per core
+  pub fn set_threads(&mut self, threads: usize) {
+    self.threads = threads;
+  }
+
+  // width and height of the square buckets handed out to the threads
+  pub fn set_tile_size(&mut self, tile_size: usize) {
+    self.tile_size = tile_size.max(1);
+  }
+
+  pub fn thread_count(&self) -> usize {
+    if self.threads > 0 {
+      return self.threads;
+    }
+
+    // not available everywhere, e.g. on wasm
+    thread::available_parallelism()
+      .map(|count| count.get())
+      .unwrap_or(1)
+  }
+
+  // every pixel draws its samples from a sequence picked by the seed and its position,
+  // so an image only depends on the seed and the settings, never on the thread count
+  pub fn set_seed(&mut self, seed: u64) {
+    self.seed = seed;
+  }
+
+  // where the pixel, lens, time and scattering samples come from
+  pub fn set_sampler(&mut self, sampler: Box<dyn Sampler>) {
+    self.sampler = sampler;
+  }
+
+  pub fn set_integrator(&mut self, integrator: Box<dyn Integrator>) {
+    self.integrator = integrator;
+  }
+
+  pub fn samples_per_pixel(&self) -> usize {
+    self.samples_per_pixel
+  }
+
+  // the most samples a pixel takes in one pass
+  pub fn max_samples_per_pixel(&self) -> usize {
+    if self.error_threshold <= 0.0 {
+      return self.samples_per_pixel;
+    }
+    match self.max_samples_per_pixel {
+      0 => 8 * self.samples_per_pixel,
+      max => max.max(self.samples_per_pixel),
+    }
+  }
+
+  pub fn aspect_ratio(&self) -> f64 {
+    (self.image_width as f64) / (self.image_height as f64)
+  }
+
+  // the camera ray of one sample of pixel (i, j), where j counts rows from the bottom of the image.
+  // pixel i covers u from i / width to (i + 1) / width, without antialiasing the ray goes
+  // through its middle
+  fn pixel_ray(&self, camera: &dyn Camera, i: usize, j: usize) -> Ray {
+    let (du, dv) = if self.antialias {
+      sampler::sample_2d()
+    } else {
+      (0.5, 0.5)
+    };
+    let u = (i as f64 + du) / self.image_width as f64;
+    let v = (j as f64 + dv) / self.image_height as f64;
+    camera.get_ray(u, v)
+  }
+
+  // traces one camera sample through pixel (i, j), see pixel_ray
+  pub fn sample_pixel(&self, world: &dyn Hittable, camera: &dyn Camera, i: usize, j: usize) -> Vec3 {
+    let ray = self.pixel_ray(camera, i, j);
+
+    self
+      .integrator
+      .ray_color(&ray, world, &self.background, self.max_depth)
+  }
+
+  pub fn render_pixel(&self, world: &dyn Hittable, camera: &dyn Camera, i: usize, j: usize) -> Vec3 {
+    self.render_pixel_pass(world, camera, i, j, 0).0
+  }
+
+  // pass k takes samples k * max spp up to (k + 1) * max spp of every pixel, so the passes of
+  // a progressive render continue each pixel's sequence instead of repeating it.
+  // returns the average color and the number of samples it took
+  fn render_pixel_pass(
+    &self,
+    world: &dyn Hittable,
+    camera: &dyn Camera,
+    i: usize,
+    j: usize,
+    pass: usize,
+  ) -> (Vec3, usize) {
+    let mut color = Vec3::fill(0.0);
+
+    // stream 0 is left to scene generation
+    let pixel = (j * self.image_width + i) as u64 + 1;
+    let pass_seed = if pass == 0 {
+      self.seed
+    } else {
+      sampler::hash(self.seed, pass as u64)
+    };
+    seed_random(pass_seed, pixel);
+    let pixel_seed = sampler::hash(self.seed, pixel);
+
+    let samples = self.max_samples_per_pixel();
+    let first = pass * samples;
+    let mut count = 0;
+    let mut sum = 0.0;
+    let mut sum_squares = 0.0;
+
+    while count < samples {
+      let batch = (count + self.samples_per_pixel).min(samples);
+      for index in count..batch {
+        sampler::start_sample(pixel_seed, first + index, samples);
+        let sample = self.sample_pixel(world, camera, i, j);
+        let y = sample.luminance();
+        color = color + sample;
+        sum += y;
+        sum_squares += y * y;
+      }
+      count = batch;
+
+      if relative_error(count, sum, sum_squares, MIN_LUMINANCE) < self.error_threshold {
+        break;
+      }
+    }
+
+    (color / (count as f64), count)
+  }
+
+  pub fn render(&self, world: &dyn Hittable, camera: &dyn Camera) -> Framebuffer {
+    self.render_pass(world, camera, 0)
+  }
+
+  // renders the samples of the given pass, see render_pixel_pass. pass 0 is a plain render
+  pub fn render_pass(&self, world: &dyn Hittable, camera: &dyn Camera, pass: usize) -> Framebuffer {
+    self.render_with_sample_counts_pass(world, camera, pass).0
+  }
+
+  // also returns how many samples every pixel took, which varies with adaptive sampling
+  pub fn render_with_sample_counts(&self, world: &dyn Hittable, camera: &dyn Camera) -> (Framebuffer, SampleCounts) {
+    self.render_with_sample_counts_pass(world, camera, 0)
+  }
+
+  fn render_with_sample_counts_pass(
+    &self,
+    world: &dyn Hittable,
+    camera: &dyn Camera,
+    pass: usize,
+  ) -> (Framebuffer, SampleCounts) {
+    let pixels = self.render_pixels(|i, j| self.render_pixel_pass(world, camera, i, j, pass));
+
+    let mut framebuffer = Framebuffer::new(self.image_width, self.image_height);
+    let mut counts = SampleCounts::new(self.image_width, self.image_height);
+    for (index, (color, count)) in pixels.into_iter().enumerate() {
+      framebuffer.pixels[index] = color;
+      counts.counts[index] = count;
+    }
+    (framebuffer, counts)
+  }
+
+  // the albedo, normal and depth the camera sees in every pixel, averaged over the same pixel
+  // positions the color samples take. mirrors and glass are looked through to the first
+  // diffuse surface behind them, whose albedo is tinted by their color
+  pub fn render_features(&self, world: &dyn Hittable, camera: &dyn Camera) -> FeatureBuffers {
+    let pixels = self.render_pixels(|i, j| self.pixel_features(world, camera, i, j));
+
+    let mut features = FeatureBuffers::new(self.image_width, self.image_height);
+    for (index, (albedo, normal, depth)) in pixels.into_iter().enumerate() {
+      features.albedo.pixels[index] = albedo;
+      features.normal.pixels[index] = normal;
+      features.depth[index] = depth;
+    }
+    features
+  }
+
+  fn pixel_features(&self, world: &dyn Hittable, camera: &dyn Camera, i: usize, j: usize) -> (Vec3, Vec3, f64) {
+    let pixel = (j * self.image_width + i) as u64 + 1;
+    seed_random(self.seed, pixel);
+    let pixel_seed = sampler::hash(self.seed, pixel);
+
+    let mut albedo = Vec3::fill(0.0);
+    let mut normal = Vec3::fill(0.0);
+    let mut depth = 0.0;
+    let mut hits = 0;
+
+    for index in 0..self.samples_per_pixel {
+      sampler::start_sample(pixel_seed, index, self.samples_per_pixel);
+      let mut ray = self.pixel_ray(camera, i, j);
+      let mut tint = Vec3::fill(1.0);
+
+      for bounce in 0..FEATURE_BOUNCES {
+        let mut record = HitRecord::new();
+        if !world.hit(&ray, 0.001, f64::INFINITY, &mut record) {
+          albedo = albedo + tint * self.background.value(&ray);
+          break;
+        }
+
+        if bounce == 0 {
+          depth += record.t;
+          hits += 1;
+        }
+
+        let material = record.material;
+        let scatter = if material.is_diffuse() {
+          None
+        } else {
+          material.scatter(&ray, &record)
+        };
+        match scatter {
+          Some(scatter) if bounce + 1 < FEATURE_BOUNCES => {
+            tint = tint * scatter.attenuation;
+            ray = scatter.scattered;
+          }
+          _ => {
+            albedo = albedo + tint * material.albedo(&record);
+            normal = normal + record.normal;
+            break;
+          }
+        }
+      }
+    }
+
+    let n = self.samples_per_pixel as f64;
+    let depth = if hits > 0 { depth / hits as f64 } else { f64::INFINITY };
+    (albedo / n, normal / n, depth)
+  }
+
+  // the auxiliary buffers of the first hit in every pixel, averaged over the same pixel positions
+  // the color samples take
+  pub fn render_aovs(&self, world: &dyn Hittable, camera: &dyn Camera) -> Aovs {
+    let pixels = self.render_pixels(|i, j| self.pixel_aovs(world, camera, i, j));
+
+    let mut aovs = Aovs::new(self.image_width, self.image_height);
+    let mut materials = Vec::with_capacity(pixels.len());
+    let mut objects = Vec::with_capacity(pixels.len());
+    for (index, pixel) in pixels.into_iter().enumerate() {
+      aovs.albedo.pixels[index] = pixel.albedo;
+      aovs.normal.pixels[index] = pixel.normal;
+      aovs.depth[index] = pixel.depth;
+      aovs.position.pixels[index] = pixel.position;
+      materials.push(pixel.material);
+      objects.push(pixel.object);
+    }
+    aovs.material_id = aov::number_ids(&materials);
+    aovs.object_id = aov::number_ids(&objects);
+    aovs
+  }
+
+  fn pixel_aovs(&self, world: &dyn Hittable, camera: &dyn Camera, i: usize, j: usize) -> AovPixel {
+    let pixel = (j * self.image_width + i) as u64 + 1;
+    seed_random(self.seed, pixel);
+    let pixel_seed = sampler::hash(self.seed, pixel);
+
+    let mut albedo = Vec3::fill(0.0);
+    let mut normal = Vec3::fill(0.0);
+    let mut depth = 0.0;
+    let mut position = Vec3::fill(0.0);
+    let mut hits = 0;
+
+    for index in 0..self.samples_per_pixel {
+      sampler::start_sample(pixel_seed, index, self.samples_per_pixel);
+      let ray = self.pixel_ray(camera, i, j);
+
+      let mut record = HitRecord::new();
+      if world.hit(&ray, 0.001, f64::INFINITY, &mut record) {
+        albedo = albedo + record.material.albedo(&record);
+        normal = normal + record.normal;
+        depth += record.t;
+        position = position + record.point;
+        hits += 1;
+      } else {
+        albedo = albedo + self.background.value(&ray);
+      }
+    }
+
+    let u = (i as f64 + 0.5) / self.image_width as f64;
+    let v = (j as f64 + 0.5) / self.image_height as f64;
+    let ray = camera.get_ray(u, v);
+    let mut record = HitRecord::new();
+    let (material, object) = if world.hit(&ray, 0.001, f64::INFINITY, &mut record) {
+      (record.material as *const dyn Material as *const () as usize, record.object_id)
+    } else {
+      (0, 0)
+    };
+
+    let n = self.samples_per_pixel as f64;
+    let (depth, position) = if hits > 0 {
+      (depth / hits as f64, position / hits as f64)
+    } else {
+      (f64::INFINITY, position)
+    };
+    let normal = if normal.length_squared() > 0.0 {
+      normal.unit_vector()
+    } else {
+      normal
+    };
+    AovPixel {
+      albedo: albedo / n,
+      normal,
+      depth,
+      position,
+      material,
+      object,
+    }
+  }
+
+  // runs pixel(i, j) for every pixel on the worker threads, a tile at a time, and returns the
+  // results row by row from the top. j counts rows from the bottom like in sample_pixel
+  fn render_pixels<T, F>(&self, pixel: F) -> Vec<T>
+  where
+    T: Send,
+    F: Fn(usize, usize) -> T + Sync,
+  {
+    let tile_count = TileScheduler::tiles(self.image_width, self.image_height, self.tile_size).len();
+    let threads = self.thread_count().min(tile_count).max(1);
+    let scheduler = TileScheduler::new(self.image_width, self.image_height, self.tile_size, threads);
+    let output: Mutex<Vec<Option<T>>> =
+      Mutex::new((0..self.image_width * self.image_height).map(|_| None).collect());
+
+    // each worker renders into one reused tile buffer and copies it out when the tile is done
+    let work = |worker: usize| {
+      sampler::set_thread_sampler(Some(self.sampler.box_clone()));
+      let mut buffer: Vec<T> = Vec::with_capacity(self.tile_size * self.tile_size);
+
+      while let Some(tile) = scheduler.next(worker) {
+        for y in tile.y..tile.y + tile.height {
+          let j = self.image_height - 1 - y;
+          for i in tile.x..tile.x + tile.width {
+            buffer.push(pixel(i, j));
+          }
+        }
+
+        let mut output = output.lock().unwrap();
+        for (index, value) in buffer.drain(..).enumerate() {
+          let (x, y) = (tile.x + index % tile.width, tile.y + index / tile.width);
+          output[y * self.image_width + x] = Some(value);
+        }
+      }
+
+      sampler::set_thread_sampler(None);
+    };
+
+    if threads == 1 {
+      work(0);
+    } else {
+      thread::scope(|scope| {
+        for worker in 0..threads {
+          let work = &work;
+          scope.spawn(move || work(worker));
+        }
+      });
+    }
+
+    output
+      .into_inner()
+      .unwrap()
+      .into_iter()
+      .map(|value| value.expect("Expected every pixel to be rendered"))
+      .collect()
+  }
+}
+
+// the number of samples every pixel took, row by row from the top like a framebuffer
+#[derive(Debug, Clone)]
+pub struct SampleCounts {
+  pub width: usize,
+  pub height: usize,
+  pub counts: Vec<usize>,
+}
+
+impl SampleCounts {
+  pub fn new(width: usize, height: usize) -> SampleCounts {
+    SampleCounts {
+      width,
+      height,
+      counts: vec![0; width * height],
+    }
+  }
+
+  pub fn mean(&self) -> f64 {
+    if self.counts.is_empty() {
+      return 0.0;
+    }
+    self.counts.iter().sum::<usize>() as f64 / self.counts.len() as f64
+  }
+
+  // shows where the samples went, from dark blue for the fewest over cyan and yellow to red
+  // for the most. the colors are meant to be saved without gamma correction
+  pub fn heatmap(&self) -> Framebuffer {
+    const COLORS: [Vec3; 4] = [
+      Vec3(0.05, 0.05, 0.4),
+      Vec3(0.0, 0.7, 0.9),
+      Vec3(1.0, 0.9, 0.1),
+      Vec3(0.9, 0.1, 0.05),
+    ];
+
+    let min = self.counts.iter().copied().min().unwrap_or(0);
+    let max = self.counts.iter().copied().max().unwrap_or(0);
+    let range = (max - min).max(1) as f64;
+
+    let mut framebuffer = Framebuffer::new(self.width, self.height);
+    for (pixel, count) in framebuffer.pixels.iter_mut().zip(self.counts.iter()) {
+      let t = (count - min) as f64 / range * (COLORS.len() - 1) as f64;
+      let index = (t as usize).min(COLORS.len() - 2);
+      *pixel = Vec3::lerp(t - index as f64, COLORS[index], COLORS[index + 1]);
+    }
+    framebuffer
+  }
+}
+
+// pixels darker than a dark grey are measured against its luminance, so the noise in deep
+// shadows doesn't draw all the samples. single samples are much noisier than the pass
+// averages of ProgressiveRenderer, so the floor is higher than its own
+const MIN_LUMINANCE: f64 = 0.1;
+
+// standard error of a mean luminance from its sum and sum of squares over n samples,
+// relative to that luminance or min_luminance if darker. infinite while there are fewer
+// than two samples
+pub(crate) fn relative_error(n: usize, sum: f64, sum_squares: f64, min_luminance: f64) -> f64 {
+  if n < 2 {
+    return f64::INFINITY;
+  }
+
+  let n = n as f64;
+  let mean = sum / n;
+  let variance = ((sum_squares - n * mean * mean) / (n - 1.0)).max(0.0);
+  (variance / n).sqrt() / mean.max(min_luminance)
+}
+
+#[cfg(test)]
+mod tests {
+
+  use std::sync::Arc;
+
+  use super::*;
+  use crate::camera::{LensCamera, ViewportCamera};
+  use crate::hittable_list::HittableList;
+  use crate::material::Lambertian;
+  use crate::sphere::Sphere;
+
+  #[test]
+  fn test_render_empty_world_shows_background() {
+    let world = HittableList::new();
+    let camera = ViewportCamera::new(
+      Vec3::fill(0.0),
+      Vec3(-0.5, -0.5, -1.0),
+      Vec3(1.0, 0.0, 0.0),
+      Vec3(0.0, 1.0, 0.0),
+    );
+    let background = Vec3(0.2, 0.4, 0.6);
+
+    let mut renderer = Renderer::new(8, 6);
+    renderer.set_background(Background::Color(background));
+    renderer.set_samples_per_pixel(4);
+    renderer.set_threads(3);
+
+    let framebuffer = renderer.render(&world, &camera);
+
+    assert_eq!(framebuffer.pixels.len(), 8 * 6);
+    for color in framebuffer.pixels {
+      assert!((color - background).len() < 1.0e-9);
+    }
+  }
+
+  #[test]
+  fn test_single_pixel_sees_the_middle_of_the_view() {
+    let mut sphere = Sphere::new(Vec3(0.0, 0.0, -1.0), 0.5);
+    sphere.set_material(Arc::new(Lambertian::new(Vec3(0.5, 0.5, 0.5))));
+    let mut world = HittableList::new();
+    world.add(Box::new(sphere));
+    let camera = ViewportCamera::new(
+      Vec3::fill(0.0),
+      Vec3(-0.5, -0.5, -1.0),
+      Vec3(1.0, 0.0, 0.0),
+      Vec3(0.0, 1.0, 0.0),
+    );
+
+    let mut renderer = Renderer::new(1, 1);
+    renderer.set_background(Background::Color(Vec3::fill(1.0)));
+    renderer.set_antialias(false);
+    let aovs = renderer.render_aovs(&world, &camera);
+    assert!((aovs.depth[0] - 0.5).abs() < 1.0e-9);
+    assert!(renderer.render(&world, &camera).pixels[0].len().is_finite());
+  }
+
+  #[test]
+  fn test_render_is_deterministic() {
+    let mut sphere = Sphere::new(Vec3(0.0, 0.0, -1.0), 0.5);
+    sphere.set_material(Arc::new(Lambertian::new(Vec3(0.5, 0.5, 0.5))));
+    let mut world = HittableList::new();
+    world.add(Box::new(sphere));
+    let camera = LensCamera::new(
+      Vec3::fill(0.0),
+      Vec3(0.0, 0.0, -1.0),
+      Vec3(0.0, 1.0, 0.0),
+      60.0,
+      1.5,
+      0.1,
+      1.0,
+    );
+
+    let render = |threads: usize, seed: u64| {
+      let mut renderer = Renderer::new(12, 8);
+      renderer.set_samples_per_pixel(4);
+      renderer.set_threads(threads);
+      renderer.set_tile_size(5);
+      renderer.set_seed(seed);
+      renderer.render(&world, &camera).pixels
+    };
+
+    let same = |a: &[Vec3], b: &[Vec3]| a.iter().zip(b.iter()).all(|(x, y)| x.eq(*y));
+    let reference = render(1, 7);
+    assert!(same(&reference, &render(3, 7)));
+    assert!(same(&reference, &render(1, 7)));
+    assert!(!same(&reference, &render(1, 8)));
+  }
+
+  #[test]
+  fn test_adaptive_sampling_spends_samples_on_noise() {
+    let mut sphere = Sphere::new(Vec3(0.0, 0.0, -1.0), 0.5);
+    sphere.set_material(Arc::new(Lambertian::new(Vec3(0.5, 0.5, 0.5))));
+    let mut world = HittableList::new();
+    world.add(Box::new(sphere));
+    let camera = LensCamera::new(
+      Vec3::fill(0.0),
+      Vec3(0.0, 0.0, -1.0),
+      Vec3(0.0, 1.0, 0.0),
+      60.0,
+      1.5,
+      0.0,
+      1.0,
+    );
+
+    let mut renderer = Renderer::new(12, 8);
+    renderer.set_samples_per_pixel(4);
+    renderer.set_max_samples_per_pixel(64);
+    renderer.set_error_threshold(0.02);
+    renderer.set_antialias(false);
+    let (_, counts) = renderer.render_with_sample_counts(&world, &camera);
+
+    // without antialiasing the sky is the same for every sample, the sphere in the middle is not
+    assert_eq!(counts.counts[0], 4);
+    assert!(counts.counts[4 * 12 + 6] > 4);
+    assert!(counts.counts.iter().all(|count| (4..=64).contains(count)));
+    assert_eq!(counts.heatmap().pixels.len(), 12 * 8);
+  }
+}
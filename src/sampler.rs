@@ -0,0 +1,469 @@
+use std::cell::RefCell;
+
+use crate::utils::random;
+
+// hands out the numbers a camera sample consumes, one dimension after another:
+// the pixel position, the lens, the shutter time, then the scattering at every bounce.
+// a sampler sees all samples of a pixel, so it can spread them over the domain
+// instead of drawing each one independently
+pub trait Sampler: Send + Sync {
+  // restarts the dimensions for sample `index` of the `count` taken in one pixel,
+  // `seed` is different for every pixel and render seed
+  fn start_sample(&mut self, seed: u64, index: usize, count: usize);
+
+  fn get_1d(&mut self) -> f64;
+
+  fn get_2d(&mut self) -> (f64, f64);
+
+  fn box_clone(&self) -> Box<dyn Sampler>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SamplerKind {
+  Independent,
+  Stratified,
+  Halton,
+  Sobol,
+}
+
+impl SamplerKind {
+  pub fn from_name(name: &str) -> Option<SamplerKind> {
+    match name {
+      "independent" => Some(SamplerKind::Independent),
+      "stratified" => Some(SamplerKind::Stratified),
+      "halton" => Some(SamplerKind::Halton),
+      "sobol" => Some(SamplerKind::Sobol),
+      _ => None,
+    }
+  }
+
+  pub fn name(&self) -> &'static str {
+    match self {
+      SamplerKind::Independent => "independent",
+      SamplerKind::Stratified => "stratified",
+      SamplerKind::Halton => "halton",
+      SamplerKind::Sobol => "sobol",
+    }
+  }
+
+  pub fn build(&self) -> Box<dyn Sampler> {
+    match self {
+      SamplerKind::Independent => Box::new(IndependentSampler::new()),
+      SamplerKind::Stratified => Box::new(StratifiedSampler::new()),
+      SamplerKind::Halton => Box::new(HaltonSampler::new()),
+      SamplerKind::Sobol => Box::new(SobolSampler::new()),
+    }
+  }
+}
+
+// uniform random numbers, what every sample used before samplers existed
+#[derive(Debug, Clone, Default)]
+pub struct IndependentSampler {}
+
+impl IndependentSampler {
+  pub fn new() -> IndependentSampler {
+    IndependentSampler {}
+  }
+}
+
+impl Sampler for IndependentSampler {
+  fn start_sample(&mut self, _seed: u64, _index: usize, _count: usize) {}
+
+  fn get_1d(&mut self) -> f64 {
+    random()
+  }
+
+  fn get_2d(&mut self) -> (f64, f64) {
+    (random(), random())
+  }
+
+  fn box_clone(&self) -> Box<dyn Sampler> {
+    Box::new(self.clone())
+  }
+}
+
+// jittered strata: each dimension is cut into `count` intervals, or a square grid of
+// cells for 2D samples, and every sample of a pixel lands in a different one.
+// the strata are visited in a shuffled order per dimension so dimensions stay uncorrelated
+#[derive(Debug, Clone, Default)]
+pub struct StratifiedSampler {
+  seed: u64,
+  index: usize,
+  count: usize,
+  dimension: u64,
+}
+
+impl StratifiedSampler {
+  pub fn new() -> StratifiedSampler {
+    StratifiedSampler::default()
+  }
+
+  fn stratum(&mut self, strata: usize) -> usize {
+    let seed = hash(self.seed, self.dimension) as u32;
+    self.dimension += 1;
+    permute((self.index % strata) as u32, strata as u32, seed) as usize
+  }
+}
+
+impl Sampler for StratifiedSampler {
+  fn start_sample(&mut self, seed: u64, index: usize, count: usize) {
+    self.seed = seed;
+    self.index = index;
+    self.count = count.max(1);
+    self.dimension = 0;
+  }
+
+  fn get_1d(&mut self) -> f64 {
+    let stratum = self.stratum(self.count);
+    (stratum as f64 + random()) / self.count as f64
+  }
+
+  fn get_2d(&mut self) -> (f64, f64) {
+    // sample counts that are not a square leave some cells of the grid empty
+    let side = (self.count as f64).sqrt().ceil() as usize;
+    let cell = self.stratum(side * side);
+    let x = (cell % side) as f64 + random();
+    let y = (cell / side) as f64 + random();
+    (x / side as f64, y / side as f64)
+  }
+
+  fn box_clone(&self) -> Box<dyn Sampler> {
+    Box::new(self.clone())
+  }
+}
+
+static PRIMES: [u64; 32] = [
+  2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89, 97, 101, 103,
+  107, 109, 113, 127, 131,
+];
+
+// the Halton sequence, dimension d is the radical inverse of the sample index in the d-th prime.
+// the digits are Owen scrambled per pixel, which keeps the stratification and breaks up the
+// correlated lines the large bases form at low sample counts.
+// dimensions past the table of primes fall back to random numbers
+#[derive(Debug, Clone, Default)]
+pub struct HaltonSampler {
+  seed: u64,
+  index: u64,
+  count: u64,
+  dimension: usize,
+}
+
+impl HaltonSampler {
+  pub fn new() -> HaltonSampler {
+    HaltonSampler::default()
+  }
+
+  fn next(&mut self) -> f64 {
+    let dimension = self.dimension;
+    self.dimension += 1;
+
+    if dimension >= PRIMES.len() {
+      return random();
+    }
+
+    let base = PRIMES[dimension];
+    let seed = hash(self.seed, dimension as u64);
+    scrambled_radical_inverse(base, self.index, seed, digit_count(base, self.count.saturating_sub(1)))
+  }
+}
+
+impl Sampler for HaltonSampler {
+  fn start_sample(&mut self, seed: u64, index: usize, count: usize) {
+    self.seed = seed;
+    self.index = index as u64;
+    self.count = count as u64;
+    self.dimension = 0;
+  }
+
+  fn get_1d(&mut self) -> f64 {
+    self.next()
+  }
+
+  fn get_2d(&mut self) -> (f64, f64) {
+    (self.next(), self.next())
+  }
+
+  fn box_clone(&self) -> Box<dyn Sampler> {
+    Box::new(self.clone())
+  }
+}
+
+// the first two dimensions of the Sobol sequence with the hash based Owen scrambling of
+// Burley, "Practical Hash-based Owen Scrambling" (2020). every request gets freshly
+// shuffled and scrambled points, so any prefix of a power of two samples stays stratified
+// in each request without the correlation of higher Sobol dimensions
+#[derive(Debug, Clone, Default)]
+pub struct SobolSampler {
+  seed: u64,
+  index: u32,
+  dimension: u64,
+}
+
+impl SobolSampler {
+  pub fn new() -> SobolSampler {
+    SobolSampler::default()
+  }
+
+  // a shuffled sample index and the seed to scramble its coordinates with
+  fn next(&mut self) -> (u32, u32) {
+    let seed = hash(self.seed, self.dimension) as u32;
+    self.dimension += 1;
+    (nested_uniform_scramble(self.index, seed), seed)
+  }
+}
+
+impl Sampler for SobolSampler {
+  fn start_sample(&mut self, seed: u64, index: usize, _count: usize) {
+    self.seed = seed;
+    self.index = index as u32;
+    self.dimension = 0;
+  }
+
+  fn get_1d(&mut self) -> f64 {
+    let (index, seed) = self.next();
+    to_unit(nested_uniform_scramble(index.reverse_bits(), hash_u32(seed)))
+  }
+
+  fn get_2d(&mut self) -> (f64, f64) {
+    let (index, seed) = self.next();
+    let x = nested_uniform_scramble(index.reverse_bits(), hash_u32(seed));
+    let y = nested_uniform_scramble(sobol_second_dimension(index), hash_u32(seed ^ 1));
+    (to_unit(x), to_unit(y))
+  }
+
+  fn box_clone(&self) -> Box<dyn Sampler> {
+    Box::new(self.clone())
+  }
+}
+
+// mirrors the digits of index around the radix point, each digit permuted depending on the
+// digits before it. the zeros up to min_digits are permuted too, so the first base^min_digits
+// indices land in different strata. the permuted zeros past that are as good as a uniform
+// number picked by the digits before them, so that is what they are replaced with
+fn scrambled_radical_inverse(base: u64, mut index: u64, seed: u64, min_digits: u32) -> f64 {
+  let inverse_base = 1.0 / base as f64;
+  let mut scale = 1.0;
+  let mut value = 0.0;
+  let mut prefix = seed;
+  let mut digits = 0;
+
+  while index > 0 || digits < min_digits {
+    scale *= inverse_base;
+    let digit = permute((index % base) as u32, base as u32, prefix as u32) as u64;
+    value += digit as f64 * scale;
+    prefix = hash(prefix, digit);
+    index /= base;
+    digits += 1;
+  }
+
+  (value + scale * to_unit(prefix as u32)).min(1.0 - f64::EPSILON)
+}
+
+// the number of digits of n in base
+fn digit_count(base: u64, mut n: u64) -> u32 {
+  let mut digits = 0;
+  while n > 0 {
+    n /= base;
+    digits += 1;
+  }
+  digits
+}
+
+// the generator matrix of the second Sobol dimension is Pascal's triangle mod 2
+fn sobol_second_dimension(mut index: u32) -> u32 {
+  let mut direction = 1u32 << 31;
+  let mut value = 0;
+
+  while index != 0 {
+    if index & 1 != 0 {
+      value ^= direction;
+    }
+    index >>= 1;
+    direction ^= direction >> 1;
+  }
+
+  value
+}
+
+// Burley's variant of the Laine-Karras permutation, each bit only depends on the bits below it
+fn laine_karras_permutation(mut x: u32, seed: u32) -> u32 {
+  x ^= x.wrapping_mul(0x3d20_adea);
+  x = x.wrapping_add(seed);
+  x = x.wrapping_mul((seed >> 16) | 1);
+  x ^= x.wrapping_mul(0x0552_6c56);
+  x ^= x.wrapping_mul(0x53a2_2864);
+  x
+}
+
+// an Owen scramble: flips each bit depending on the bits above it
+fn nested_uniform_scramble(x: u32, seed: u32) -> u32 {
+  laine_karras_permutation(x.reverse_bits(), seed).reverse_bits()
+}
+
+// Kensler's hashed permutation of 0..length, "Correlated Multi-Jittered Sampling" (2013)
+fn permute(mut i: u32, length: u32, seed: u32) -> u32 {
+  let mut mask = length - 1;
+  mask |= mask >> 1;
+  mask |= mask >> 2;
+  mask |= mask >> 4;
+  mask |= mask >> 8;
+  mask |= mask >> 16;
+
+  // cycle walking, values outside 0..length are permuted again
+  loop {
+    i ^= seed;
+    i = i.wrapping_mul(0xe170_893d);
+    i ^= seed >> 16;
+    i ^= (i & mask) >> 4;
+    i ^= seed >> 8;
+    i = i.wrapping_mul(0x0929_eb3f);
+    i ^= seed >> 23;
+    i ^= (i & mask) >> 1;
+    i = i.wrapping_mul(1 | seed >> 27);
+    i = i.wrapping_mul(0x6935_fa69);
+    i ^= (i & mask) >> 11;
+    i = i.wrapping_mul(0x74dc_b303);
+    i ^= (i & mask) >> 2;
+    i = i.wrapping_mul(0x9e50_1cc3);
+    i ^= (i & mask) >> 2;
+    i = i.wrapping_mul(0xc860_a3df);
+    i &= mask;
+    i ^= i >> 5;
+    if i < length {
+      break;
+    }
+  }
+
+  ((i as u64 + seed as u64) % length as u64) as u32
+}
+
+// the splitmix64 finalizer
+fn mix(mut x: u64) -> u64 {
+  x ^= x >> 30;
+  x = x.wrapping_mul(0xbf58_476d_1ce4_e5b9);
+  x ^= x >> 27;
+  x = x.wrapping_mul(0x94d0_49bb_1331_11eb);
+  x ^ (x >> 31)
+}
+
+pub fn hash(a: u64, b: u64) -> u64 {
+  mix(a ^ mix(b).wrapping_add(0x9e37_79b9_7f4a_7c15))
+}
+
+fn hash_u32(x: u32) -> u32 {
+  mix(x as u64) as u32
+}
+
+fn to_unit(x: u32) -> f64 {
+  x as f64 / 4_294_967_296.0
+}
+
+thread_local! {
+  // the sampler of the pixel the current thread is working on, None outside of a render
+  static ACTIVE: RefCell<Option<Box<dyn Sampler>>> = const { RefCell::new(None) };
+}
+
+// installs the sampler that sample_1d and sample_2d draw from on the current thread
+pub fn set_thread_sampler(sampler: Option<Box<dyn Sampler>>) {
+  ACTIVE.with(|active| *active.borrow_mut() = sampler);
+}
+
+pub fn start_sample(seed: u64, index: usize, count: usize) {
+  ACTIVE.with(|active| {
+    if let Some(sampler) = active.borrow_mut().as_mut() {
+      sampler.start_sample(seed, index, count);
+    }
+  });
+}
+
+// uniform in [0, 1), from the thread's sampler or random() without one
+pub fn sample_1d() -> f64 {
+  ACTIVE.with(|active| match active.borrow_mut().as_mut() {
+    Some(sampler) => sampler.get_1d(),
+    None => random(),
+  })
+}
+
+pub fn sample_2d() -> (f64, f64) {
+  ACTIVE.with(|active| match active.borrow_mut().as_mut() {
+    Some(sampler) => sampler.get_2d(),
+    None => (random(), random()),
+  })
+}
+
+#[cfg(test)]
+mod tests {
+
+  use super::*;
+
+  // every sample of the pixel in its own cell of a side x side grid
+  fn covers_grid(sampler: &mut dyn Sampler, side: usize) -> bool {
+    let count = side * side;
+    let mut cells = vec![false; count];
+
+    for index in 0..count {
+      sampler.start_sample(42, index, count);
+      let (x, y) = sampler.get_2d();
+      cells[(y * side as f64) as usize * side + (x * side as f64) as usize] = true;
+    }
+
+    cells.iter().all(|cell| *cell)
+  }
+
+  #[test]
+  fn test_samplers_stratify_2d_samples() {
+    assert!(covers_grid(&mut StratifiedSampler::new(), 4));
+    assert!(covers_grid(&mut SobolSampler::new(), 4));
+    assert!(covers_grid(&mut SobolSampler::new(), 8));
+  }
+
+  #[test]
+  fn test_sobol_stratifies_every_dimension() {
+    let mut sampler = SobolSampler::new();
+    let mut strata = [[false; 16]; 3];
+
+    for index in 0..16 {
+      sampler.start_sample(7, index, 16);
+      for stratum in strata.iter_mut() {
+        let value = sampler.get_1d();
+        assert!((0.0..1.0).contains(&value));
+        stratum[(value * 16.0) as usize] = true;
+      }
+    }
+
+    assert!(strata.iter().all(|stratum| stratum.iter().all(|hit| *hit)));
+  }
+
+  #[test]
+  fn test_halton_stratifies_every_dimension() {
+    let mut sampler = HaltonSampler::new();
+
+    // base^2 samples put one value in each of base^2 strata, scrambled or not
+    for (dimension, base) in PRIMES.iter().take(4).enumerate() {
+      let count = (base * base) as usize;
+      let mut stratum = vec![false; count];
+
+      for index in 0..count {
+        sampler.start_sample(7, index, count);
+        for _ in 0..dimension {
+          sampler.get_1d();
+        }
+        let value = sampler.get_1d();
+        assert!((0.0..1.0).contains(&value));
+        stratum[(value * count as f64) as usize] = true;
+      }
+
+      assert!(stratum.iter().all(|hit| *hit), "dimension {} in base {}", dimension, base);
+    }
+  }
+
+  #[test]
+  fn test_permute_is_a_permutation() {
+    for length in [1, 5, 16, 49] {
+      let mut seen: Vec<u32> = (0..length).map(|i| permute(i, length, 1234)).collect();
+      seen.sort();
+      assert_eq!(seen, (0..length).collect::<Vec<u32>>());
+    }
+  }
+}
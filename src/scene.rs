@@ -0,0 +1,1393 @@
+// a line based text format describing everything needed to render an image:
+//
+//   # comments run to the end of the line
+//   render width=400 height=225 samples=50 depth=50 threads=0 antialias=true sampler=sobol bvh=true
+//   background gradient bottom=1,1,1 top=0.5,0.7,1
+//   background environment file=assets/sky.hdr rotation=90 intensity=1
+//   camera exposure from=13,2,3 at=0,0,0 up=0,1,0 fov=20 aperture=0.1 focus=10 time0=0 time1=1
+//   texture checks checker odd=0.2,0.3,0.1 even=0.9,0.9,0.9 scale=10
+//   material ground lambertian texture=checks
+//   material gold metal albedo=0.8,0.6,0.2 fuzz=0.1
+//   material copper conductor preset=copper roughness=0.3
+//   material frosted rough_dielectric ior=1.5 roughness=0.2
+//   material paint principled base=0.8,0.1,0.1 roughness=0.4 clearcoat=1 metallic=rust_mask
+//   sphere center=0,-1000,0 radius=1000 material=ground
+//   mesh file=assets/icosphere.obj material=gold
+//   quad corner=-1,0,-1 u=2,0,0 v=0,0,2 material=ground
+//   box min=0,0,0 max=1,2,1 material=gold
+//   plane point=0,0,0 normal=0,1,0 material=ground
+//
+// textures and materials must be defined before they are used, values never contain spaces.
+// the numbers of a principled material may also name a texture
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::aabb::Aabb;
+use crate::background::Background;
+use crate::box_shape::BoxShape;
+use crate::bvh::{BvhNode, SplitHeuristic};
+use crate::camera::{Camera, ExposureCamera, LensCamera, PositionalCamera};
+use crate::environment::EnvironmentMap;
+use crate::hittable_list::HittableList;
+use crate::integrator::MisPathTracer;
+use crate::material::{
+  Conductor, Dielectric, DiffuseLight, Lambertian, Material, Metal, RoughDielectric, CONDUCTORS,
+};
+use crate::obj;
+use crate::plane::Plane;
+use crate::principled::Principled;
+use crate::quad::Quad;
+use crate::renderer::Renderer;
+use crate::sampler::SamplerKind;
+use crate::sphere::{MovingSphere, Sphere};
+use crate::texture::{CheckerTexture, ImageTexture, NoiseTexture, SolidColor, Texture};
+use crate::triangle::Triangle;
+use crate::vec3::Vec3;
+
+#[derive(Debug, Clone)]
+pub struct RenderSettings {
+  pub width: usize,
+  pub height: usize,
+  pub samples_per_pixel: usize,
+  pub max_depth: u64,
+  // 0 runs one thread per core
+  pub threads: usize,
+  pub antialias: bool,
+  // picks the random sequences of the renderer and of noise textures
+  pub seed: u64,
+  pub sampler: SamplerKind,
+  // wraps every primitive in one BvhNode
+  pub bvh: bool,
+}
+
+impl Default for RenderSettings {
+  fn default() -> Self {
+    RenderSettings {
+      width: 400,
+      height: 225,
+      samples_per_pixel: 100,
+      max_depth: 50,
+      threads: 0,
+      antialias: true,
+      seed: 0,
+      sampler: SamplerKind::Sobol,
+      bvh: false,
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CameraKind {
+  Positional,
+  Lens,
+  Exposure,
+}
+
+// lens settings are only used by Lens and Exposure cameras, times only by Exposure cameras
+#[derive(Debug, Clone)]
+pub struct CameraDescription {
+  pub kind: CameraKind,
+  pub look_from: Vec3,
+  pub look_at: Vec3,
+  pub vup: Vec3,
+  pub vfov: f64,
+  pub aperture: f64,
+  pub focus_dist: f64,
+  pub time0: f64,
+  pub time1: f64,
+}
+
+impl Default for CameraDescription {
+  fn default() -> Self {
+    CameraDescription {
+      kind: CameraKind::Positional,
+      look_from: Vec3(0.0, 0.0, 0.0),
+      look_at: Vec3(0.0, 0.0, -1.0),
+      vup: Vec3(0.0, 1.0, 0.0),
+      vfov: 90.0,
+      aperture: 0.0,
+      focus_dist: 1.0,
+      time0: 0.0,
+      time1: 0.0,
+    }
+  }
+}
+
+// environment maps are only loaded by build_renderer
+#[derive(Debug, Clone)]
+pub enum BackgroundDescription {
+  Color(Vec3),
+  Gradient(Vec3, Vec3),
+  Environment {
+    file: String,
+    rotation: f64,
+    intensity: f64,
+  },
+}
+
+#[derive(Debug, Clone)]
+pub enum TextureDescription {
+  Solid(Vec3),
+  Checker { odd: Vec3, even: Vec3, scale: f64 },
+  Image { file: String },
+  Noise { scale: f64, turbulence_depth: usize },
+}
+
+// a material color is either a constant or the name of a texture
+#[derive(Debug, Clone)]
+pub enum ColorSource {
+  Color(Vec3),
+  Texture(String),
+}
+
+// a material number is either a constant or the name of a texture
+#[derive(Debug, Clone)]
+pub enum ScalarSource {
+  Value(f64),
+  Texture(String),
+}
+
+#[derive(Debug, Clone)]
+pub enum MaterialDescription {
+  Lambertian { albedo: ColorSource },
+  Metal { albedo: ColorSource, fuzz: f64 },
+  Dielectric { refract_index: f64 },
+  DiffuseLight { emit: ColorSource },
+  Conductor { eta: Vec3, k: Vec3, roughness: f64 },
+  RoughDielectric { refract_index: f64, roughness: f64 },
+  Principled {
+    base_color: ColorSource,
+    metallic: ScalarSource,
+    roughness: ScalarSource,
+    specular: ScalarSource,
+    specular_tint: ScalarSource,
+    sheen: ScalarSource,
+    clearcoat: ScalarSource,
+    transmission: ScalarSource,
+    ior: ScalarSource,
+  },
+}
+
+// shapes without a material keep the DefaultMaterial
+#[derive(Debug, Clone)]
+pub enum ShapeDescription {
+  Sphere {
+    center: Vec3,
+    radius: f64,
+    material: Option<String>,
+  },
+  MovingSphere {
+    center0: Vec3,
+    center1: Vec3,
+    time0: f64,
+    time1: f64,
+    radius: f64,
+    material: Option<String>,
+  },
+  Triangle {
+    v0: Vec3,
+    v1: Vec3,
+    v2: Vec3,
+    material: Option<String>,
+  },
+  Mesh {
+    file: String,
+    group: Option<String>,
+    material: Option<String>,
+  },
+  Quad {
+    corner: Vec3,
+    u: Vec3,
+    v: Vec3,
+    material: Option<String>,
+  },
+  Box {
+    min: Vec3,
+    max: Vec3,
+    material: Option<String>,
+  },
+  Plane {
+    point: Vec3,
+    normal: Vec3,
+    material: Option<String>,
+  },
+}
+
+#[derive(Debug, Clone)]
+pub struct Scene {
+  pub settings: RenderSettings,
+  pub background: BackgroundDescription,
+  pub camera: CameraDescription,
+  // kept in definition order so a saved scene reads like the original
+  pub textures: Vec<(String, TextureDescription)>,
+  pub materials: Vec<(String, MaterialDescription)>,
+  pub shapes: Vec<ShapeDescription>,
+}
+
+impl Default for Scene {
+  fn default() -> Self {
+    Scene::new()
+  }
+}
+
+impl Scene {
+  pub fn new() -> Scene {
+    Scene {
+      settings: RenderSettings::default(),
+      background: BackgroundDescription::Gradient(Vec3(1.0, 1.0, 1.0), Vec3(0.5, 0.7, 1.0)),
+      camera: CameraDescription::default(),
+      textures: vec![],
+      materials: vec![],
+      shapes: vec![],
+    }
+  }
+
+  pub fn add_texture(&mut self, name: &str, texture: TextureDescription) {
+    self.textures.push((name.to_string(), texture));
+  }
+
+  pub fn add_material(&mut self, name: &str, material: MaterialDescription) {
+    self.materials.push((name.to_string(), material));
+  }
+
+  pub fn add_shape(&mut self, shape: ShapeDescription) {
+    self.shapes.push(shape);
+  }
+
+  pub fn aspect_ratio(&self) -> f64 {
+    self.settings.width as f64 / self.settings.height as f64
+  }
+
+  // loads the environment map of the background, if there is one
+  pub fn build_background(&self) -> io::Result<Background> {
+    let background = match &self.background {
+      BackgroundDescription::Color(color) => Background::Color(*color),
+      BackgroundDescription::Gradient(bottom, top) => Background::Gradient(*bottom, *top),
+      BackgroundDescription::Environment {
+        file,
+        rotation,
+        intensity,
+      } => {
+        let mut environment = EnvironmentMap::load(file)?;
+        environment.set_rotation(*rotation);
+        environment.set_intensity(*intensity);
+        Background::Environment(Arc::new(environment))
+      }
+    };
+    Ok(background)
+  }
+
+  pub fn build_renderer(&self) -> io::Result<Renderer> {
+    let settings = &self.settings;
+    let mut renderer = Renderer::new(settings.width, settings.height);
+    renderer.set_samples_per_pixel(settings.samples_per_pixel);
+    renderer.set_max_depth(settings.max_depth);
+    renderer.set_threads(settings.threads);
+    renderer.set_antialias(settings.antialias);
+    renderer.set_seed(settings.seed);
+    renderer.set_sampler(settings.sampler.build());
+    let background = self.build_background()?;
+
+    // scenes with lights that can be sampled converge faster with shadow rays toward them
+    let lights = self.build_lights();
+    if !lights.objects.is_empty() || background.is_sampled() {
+      renderer.set_integrator(Box::new(MisPathTracer::new(lights)));
+    }
+    renderer.set_background(background);
+
+    Ok(renderer)
+  }
+
+  // the spheres, triangles and quads made of a diffuse_light material, for the integrator to sample
+  pub fn build_lights(&self) -> HittableList {
+    let is_light = |material: &Option<String>| {
+      self.materials.iter().any(|(name, description)| {
+        material.as_deref() == Some(name.as_str())
+          && matches!(description, MaterialDescription::DiffuseLight { .. })
+      })
+    };
+
+    let mut lights = HittableList::new();
+    for shape in self.shapes.iter() {
+      match shape {
+        ShapeDescription::Sphere {
+          center,
+          radius,
+          material,
+        } if is_light(material) => lights.add(Box::new(Sphere::new(*center, *radius))),
+        ShapeDescription::Triangle { v0, v1, v2, material } if is_light(material) => {
+          lights.add(Box::new(Triangle::new(*v0, *v1, *v2)))
+        }
+        ShapeDescription::Quad { corner, u, v, material } if is_light(material) => {
+          lights.add(Box::new(Quad::new(*corner, *u, *v)))
+        }
+        _ => {}
+      }
+    }
+
+    lights
+  }
+
+  pub fn build_camera(&self) -> Box<dyn Camera> {
+    let CameraDescription {
+      kind,
+      look_from,
+      look_at,
+      vup,
+      vfov,
+      aperture,
+      focus_dist,
+      time0,
+      time1,
+    } = self.camera;
+    let aspect_ratio = self.aspect_ratio();
+
+    match kind {
+      CameraKind::Positional => Box::new(PositionalCamera::new(look_from, look_at, vup, vfov, aspect_ratio)),
+      CameraKind::Lens => Box::new(LensCamera::new(
+        look_from,
+        look_at,
+        vup,
+        vfov,
+        aspect_ratio,
+        aperture,
+        focus_dist,
+      )),
+      CameraKind::Exposure => Box::new(ExposureCamera::new(
+        look_from,
+        look_at,
+        vup,
+        vfov,
+        aspect_ratio,
+        aperture,
+        focus_dist,
+        time0,
+        time1,
+      )),
+    }
+  }
+
+  // loads the image and mesh files referenced by the scene, names were checked by the parser
+  pub fn build_world(&self) -> io::Result<HittableList> {
+    let mut textures: HashMap<&str, Arc<dyn Texture>> = HashMap::new();
+    for (name, texture) in self.textures.iter() {
+      let texture: Arc<dyn Texture> = match texture {
+        TextureDescription::Solid(color) => Arc::new(SolidColor::new(*color)),
+        TextureDescription::Checker { odd, even, scale } => {
+          let mut checker = CheckerTexture::from_colors(*odd, *even);
+          checker.set_scale(*scale);
+          Arc::new(checker)
+        }
+        TextureDescription::Image { file } => Arc::new(ImageTexture::load(file)?),
+        TextureDescription::Noise {
+          scale,
+          turbulence_depth,
+        } => {
+          let mut noise = NoiseTexture::new(*scale);
+          noise.set_turbulence_depth(*turbulence_depth);
+          Arc::new(noise)
+        }
+      };
+      textures.insert(name, texture);
+    }
+
+    let color_texture = |source: &ColorSource| -> Arc<dyn Texture> {
+      match source {
+        ColorSource::Color(color) => Arc::new(SolidColor::new(*color)),
+        ColorSource::Texture(name) => Arc::clone(&textures[name.as_str()]),
+      }
+    };
+    let scalar_texture = |source: &ScalarSource| -> Arc<dyn Texture> {
+      match source {
+        ScalarSource::Value(value) => Arc::new(SolidColor::new(Vec3::fill(*value))),
+        ScalarSource::Texture(name) => Arc::clone(&textures[name.as_str()]),
+      }
+    };
+
+    // every shape naming a material shares the one built here
+    let mut materials: HashMap<&str, Arc<dyn Material>> = HashMap::new();
+    for (name, material) in self.materials.iter() {
+      let material: Arc<dyn Material> = match material {
+        MaterialDescription::Lambertian { albedo } => Arc::new(Lambertian::from_texture(color_texture(albedo))),
+        MaterialDescription::Metal { albedo, fuzz } => {
+          Arc::new(Metal::from_texture(color_texture(albedo)).set_fuzz(*fuzz))
+        }
+        MaterialDescription::Dielectric { refract_index } => Arc::new(Dielectric::new(*refract_index)),
+        MaterialDescription::DiffuseLight { emit } => Arc::new(DiffuseLight::from_texture(color_texture(emit))),
+        MaterialDescription::Conductor { eta, k, roughness } => Arc::new(Conductor::new(*eta, *k, *roughness)),
+        MaterialDescription::RoughDielectric {
+          refract_index,
+          roughness,
+        } => Arc::new(RoughDielectric::new(*refract_index, *roughness)),
+        MaterialDescription::Principled {
+          base_color,
+          metallic,
+          roughness,
+          specular,
+          specular_tint,
+          sheen,
+          clearcoat,
+          transmission,
+          ior,
+        } => {
+          let mut principled = Principled::from_texture(color_texture(base_color));
+          principled.set_metallic_texture(scalar_texture(metallic));
+          principled.set_roughness_texture(scalar_texture(roughness));
+          principled.set_specular_texture(scalar_texture(specular));
+          principled.set_specular_tint_texture(scalar_texture(specular_tint));
+          principled.set_sheen_texture(scalar_texture(sheen));
+          principled.set_clearcoat_texture(scalar_texture(clearcoat));
+          principled.set_transmission_texture(scalar_texture(transmission));
+          principled.set_ior_texture(scalar_texture(ior));
+          Arc::new(principled)
+        }
+      };
+      materials.insert(name, material);
+    }
+
+    let material_of = |name: &Option<String>| -> Option<Arc<dyn Material>> {
+      name.as_ref().map(|name| Arc::clone(&materials[name.as_str()]))
+    };
+
+    let mut world = HittableList::new();
+    for shape in self.shapes.iter() {
+      match shape {
+        ShapeDescription::Sphere {
+          center,
+          radius,
+          material,
+        } => {
+          let mut sphere = Sphere::new(*center, *radius);
+          if let Some(material) = material_of(material) {
+            sphere.set_material(material);
+          }
+          world.add(Box::new(sphere));
+        }
+        ShapeDescription::MovingSphere {
+          center0,
+          center1,
+          time0,
+          time1,
+          radius,
+          material,
+        } => {
+          let mut sphere = MovingSphere::new(*center0, *center1, *time0, *time1, *radius);
+          if let Some(material) = material_of(material) {
+            sphere.set_material(material);
+          }
+          world.add(Box::new(sphere));
+        }
+        ShapeDescription::Triangle { v0, v1, v2, material } => {
+          let mut triangle = Triangle::new(*v0, *v1, *v2);
+          if let Some(material) = material_of(material) {
+            triangle.set_material(material);
+          }
+          world.add(Box::new(triangle));
+        }
+        ShapeDescription::Mesh { file, group, material } => {
+          let model = obj::load(file)?;
+          let mut mesh = match group {
+            Some(group) => model.group_mesh(group).ok_or_else(|| {
+              io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Expected group {} in {}", group, file),
+              )
+            })?,
+            None => model.to_mesh(),
+          };
+          if let Some(material) = material_of(material) {
+            mesh.set_material(material);
+          }
+          world.add(Box::new(mesh));
+        }
+        ShapeDescription::Quad { corner, u, v, material } => {
+          let mut quad = Quad::new(*corner, *u, *v);
+          if let Some(material) = material_of(material) {
+            quad.set_material(material);
+          }
+          world.add(Box::new(quad));
+        }
+        ShapeDescription::Box { min, max, material } => {
+          let mut shape = BoxShape::new(*min, *max);
+          if let Some(material) = material_of(material) {
+            shape.set_material(material);
+          }
+          world.add(Box::new(shape));
+        }
+        ShapeDescription::Plane { point, normal, material } => {
+          let mut plane = Plane::new(*point, *normal);
+          if let Some(material) = material_of(material) {
+            plane.set_material(material);
+          }
+          world.add(Box::new(plane));
+        }
+      }
+    }
+
+    if !self.settings.bvh || world.objects.is_empty() {
+      return Ok(world);
+    }
+
+    // planes have no bounding box and stay next to the BVH
+    let (time0, time1) = (self.camera.time0, self.camera.time1);
+    let (bounded, unbounded): (Vec<_>, Vec<_>) = world
+      .objects
+      .into_iter()
+      .partition(|object| object.bounding_box(time0, time1, &mut Aabb::empty()));
+
+    let mut bvh_world = HittableList { objects: unbounded };
+    if !bounded.is_empty() {
+      let bounded = HittableList { objects: bounded };
+      bvh_world.add(Box::new(BvhNode::new(bounded, time0, time1, SplitHeuristic::Sah)));
+    }
+    Ok(bvh_world)
+  }
+
+  // writes the scene in the format read by parse, so parse(scene.serialize()) rebuilds it
+  pub fn serialize(&self) -> String {
+    let mut out = String::new();
+    let settings = &self.settings;
+
+    // writing into a String cannot fail
+    writeln!(
+      out,
+      "render width={} height={} samples={} depth={} threads={} antialias={} seed={} sampler={} bvh={}",
+      settings.width,
+      settings.height,
+      settings.samples_per_pixel,
+      settings.max_depth,
+      settings.threads,
+      settings.antialias,
+      settings.seed,
+      settings.sampler.name(),
+      settings.bvh
+    )
+    .unwrap();
+
+    match &self.background {
+      BackgroundDescription::Color(color) => writeln!(out, "background color value={}", vector(*color)),
+      BackgroundDescription::Gradient(bottom, top) => writeln!(
+        out,
+        "background gradient bottom={} top={}",
+        vector(*bottom),
+        vector(*top)
+      ),
+      BackgroundDescription::Environment {
+        file,
+        rotation,
+        intensity,
+      } => writeln!(
+        out,
+        "background environment file={} rotation={} intensity={}",
+        file, rotation, intensity
+      ),
+    }
+    .unwrap();
+
+    let camera = &self.camera;
+    let kind = match camera.kind {
+      CameraKind::Positional => "positional",
+      CameraKind::Lens => "lens",
+      CameraKind::Exposure => "exposure",
+    };
+    write!(
+      out,
+      "camera {} from={} at={} up={} fov={}",
+      kind,
+      vector(camera.look_from),
+      vector(camera.look_at),
+      vector(camera.vup),
+      camera.vfov
+    )
+    .unwrap();
+    if camera.kind != CameraKind::Positional {
+      write!(out, " aperture={} focus={}", camera.aperture, camera.focus_dist).unwrap();
+    }
+    if camera.kind == CameraKind::Exposure {
+      write!(out, " time0={} time1={}", camera.time0, camera.time1).unwrap();
+    }
+    writeln!(out).unwrap();
+
+    for (name, texture) in self.textures.iter() {
+      match texture {
+        TextureDescription::Solid(color) => writeln!(out, "texture {} solid color={}", name, vector(*color)),
+        TextureDescription::Checker { odd, even, scale } => writeln!(
+          out,
+          "texture {} checker odd={} even={} scale={}",
+          name,
+          vector(*odd),
+          vector(*even),
+          scale
+        ),
+        TextureDescription::Image { file } => writeln!(out, "texture {} image file={}", name, file),
+        TextureDescription::Noise {
+          scale,
+          turbulence_depth,
+        } => writeln!(
+          out,
+          "texture {} noise scale={} turbulence={}",
+          name, scale, turbulence_depth
+        ),
+      }
+      .unwrap();
+    }
+
+    for (name, material) in self.materials.iter() {
+      match material {
+        MaterialDescription::Lambertian { albedo } => {
+          writeln!(out, "material {} lambertian {}", name, color_source("albedo", albedo))
+        }
+        MaterialDescription::Metal { albedo, fuzz } => writeln!(
+          out,
+          "material {} metal {} fuzz={}",
+          name,
+          color_source("albedo", albedo),
+          fuzz
+        ),
+        MaterialDescription::Dielectric { refract_index } => {
+          writeln!(out, "material {} dielectric ior={}", name, refract_index)
+        }
+        MaterialDescription::DiffuseLight { emit } => {
+          writeln!(out, "material {} diffuse_light {}", name, color_source("emit", emit))
+        }
+        MaterialDescription::Conductor { eta, k, roughness } => writeln!(
+          out,
+          "material {} conductor eta={} k={} roughness={}",
+          name,
+          vector(*eta),
+          vector(*k),
+          roughness
+        ),
+        MaterialDescription::RoughDielectric {
+          refract_index,
+          roughness,
+        } => writeln!(
+          out,
+          "material {} rough_dielectric ior={} roughness={}",
+          name, refract_index, roughness
+        ),
+        MaterialDescription::Principled {
+          base_color,
+          metallic,
+          roughness,
+          specular,
+          specular_tint,
+          sheen,
+          clearcoat,
+          transmission,
+          ior,
+        } => writeln!(
+          out,
+          "material {} principled {} {} {} {} {} {} {} {} {}",
+          name,
+          color_source("base", base_color),
+          scalar_source("metallic", metallic),
+          scalar_source("roughness", roughness),
+          scalar_source("specular", specular),
+          scalar_source("specular_tint", specular_tint),
+          scalar_source("sheen", sheen),
+          scalar_source("clearcoat", clearcoat),
+          scalar_source("transmission", transmission),
+          scalar_source("ior", ior)
+        ),
+      }
+      .unwrap();
+    }
+
+    for shape in self.shapes.iter() {
+      let material = match shape {
+        ShapeDescription::Sphere {
+          center,
+          radius,
+          material,
+        } => {
+          write!(out, "sphere center={} radius={}", vector(*center), radius).unwrap();
+          material
+        }
+        ShapeDescription::MovingSphere {
+          center0,
+          center1,
+          time0,
+          time1,
+          radius,
+          material,
+        } => {
+          write!(
+            out,
+            "moving_sphere center0={} center1={} time0={} time1={} radius={}",
+            vector(*center0),
+            vector(*center1),
+            time0,
+            time1,
+            radius
+          )
+          .unwrap();
+          material
+        }
+        ShapeDescription::Triangle { v0, v1, v2, material } => {
+          write!(
+            out,
+            "triangle v0={} v1={} v2={}",
+            vector(*v0),
+            vector(*v1),
+            vector(*v2)
+          )
+          .unwrap();
+          material
+        }
+        ShapeDescription::Mesh { file, group, material } => {
+          write!(out, "mesh file={}", file).unwrap();
+          if let Some(group) = group {
+            write!(out, " group={}", group).unwrap();
+          }
+          material
+        }
+        ShapeDescription::Quad { corner, u, v, material } => {
+          write!(
+            out,
+            "quad corner={} u={} v={}",
+            vector(*corner),
+            vector(*u),
+            vector(*v)
+          )
+          .unwrap();
+          material
+        }
+        ShapeDescription::Box { min, max, material } => {
+          write!(out, "box min={} max={}", vector(*min), vector(*max)).unwrap();
+          material
+        }
+        ShapeDescription::Plane { point, normal, material } => {
+          write!(out, "plane point={} normal={}", vector(*point), vector(*normal)).unwrap();
+          material
+        }
+      };
+      if let Some(material) = material {
+        write!(out, " material={}", material).unwrap();
+      }
+      writeln!(out).unwrap();
+    }
+
+    out
+  }
+
+  pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+    fs::write(path, self.serialize())
+  }
+}
+
+// f64 Display prints the shortest text that parses back to the same value
+fn vector(v: Vec3) -> String {
+  format!("{},{},{}", v.0, v.1, v.2)
+}
+
+fn color_source(key: &str, source: &ColorSource) -> String {
+  match source {
+    ColorSource::Color(color) => format!("{}={}", key, vector(*color)),
+    ColorSource::Texture(name) => format!("texture={}", name),
+  }
+}
+
+fn scalar_source(key: &str, source: &ScalarSource) -> String {
+  match source {
+    ScalarSource::Value(value) => format!("{}={}", key, value),
+    ScalarSource::Texture(name) => format!("{}={}", key, name),
+  }
+}
+
+fn parse_error(line: usize, column: usize, message: String) -> io::Error {
+  io::Error::new(
+    io::ErrorKind::InvalidData,
+    format!("line {}, column {}: {}", line, column, message),
+  )
+}
+
+// a whitespace separated word and its 1-based column
+#[derive(Clone, Copy)]
+struct Token<'a> {
+  text: &'a str,
+  column: usize,
+}
+
+fn tokenize(line: &str) -> Vec<Token<'_>> {
+  let mut tokens = vec![];
+  let mut start: Option<usize> = None;
+
+  for (index, c) in line.char_indices().chain(std::iter::once((line.len(), ' '))) {
+    match (c.is_whitespace(), start) {
+      (true, Some(begin)) => {
+        tokens.push(Token {
+          text: &line[begin..index],
+          column: line[..begin].chars().count() + 1,
+        });
+        start = None;
+      }
+      (false, None) => start = Some(index),
+      _ => {}
+    }
+  }
+
+  tokens
+}
+
+// the key=value pairs of one statement, every key has to be consumed exactly once
+struct Properties<'a> {
+  line: usize,
+  // column of the statement keyword, for missing properties
+  column: usize,
+  entries: Vec<(&'a str, Token<'a>, bool)>,
+}
+
+impl<'a> Properties<'a> {
+  fn new(line: usize, column: usize, tokens: &[Token<'a>]) -> io::Result<Properties<'a>> {
+    let mut entries: Vec<(&'a str, Token<'a>, bool)> = vec![];
+
+    for token in tokens {
+      let (key, value) = match token.text.find('=') {
+        Some(split) => (&token.text[..split], &token.text[split + 1..]),
+        None => {
+          return Err(parse_error(
+            line,
+            token.column,
+            format!("Expected key=value, but received {}", token.text),
+          ))
+        }
+      };
+
+      if entries.iter().any(|(existing, _, _)| *existing == key) {
+        return Err(parse_error(line, token.column, format!("Duplicate property {}", key)));
+      }
+
+      let value = Token {
+        text: value,
+        column: token.column + key.chars().count() + 1,
+      };
+      entries.push((key, value, false));
+    }
+
+    Ok(Properties {
+      line,
+      column,
+      entries,
+    })
+  }
+
+  fn take(&mut self, key: &str) -> Option<Token<'a>> {
+    self
+      .entries
+      .iter_mut()
+      .find(|(existing, _, _)| *existing == key)
+      .map(|(_, value, used)| {
+        *used = true;
+        *value
+      })
+  }
+
+  fn required(&mut self, key: &str) -> io::Result<Token<'a>> {
+    let (line, column) = (self.line, self.column);
+    self
+      .take(key)
+      .ok_or_else(|| parse_error(line, column, format!("Expected property {}", key)))
+  }
+
+  fn number(&mut self, key: &str, default: Option<f64>) -> io::Result<f64> {
+    match (self.take(key), default) {
+      (Some(token), _) => parse_number(self.line, token),
+      (None, Some(default)) => Ok(default),
+      (None, None) => parse_number(self.line, self.required(key)?),
+    }
+  }
+
+  // parses into the type of the setting, so a u64 seed does not go through a 32-bit usize on wasm
+  fn integer<T: std::str::FromStr>(&mut self, key: &str, default: T) -> io::Result<T> {
+    match self.take(key) {
+      Some(token) => token.text.parse::<T>().map_err(|_| {
+        parse_error(
+          self.line,
+          token.column,
+          format!("Expected a non-negative integer, but received {}", token.text),
+        )
+      }),
+      None => Ok(default),
+    }
+  }
+
+  fn boolean(&mut self, key: &str, default: bool) -> io::Result<bool> {
+    match self.take(key) {
+      Some(token) => token.text.parse::<bool>().map_err(|_| {
+        parse_error(
+          self.line,
+          token.column,
+          format!("Expected true or false, but received {}", token.text),
+        )
+      }),
+      None => Ok(default),
+    }
+  }
+
+  fn vector(&mut self, key: &str, default: Option<Vec3>) -> io::Result<Vec3> {
+    match (self.take(key), default) {
+      (Some(token), _) => parse_vector(self.line, token),
+      (None, Some(default)) => Ok(default),
+      (None, None) => parse_vector(self.line, self.required(key)?),
+    }
+  }
+
+  fn name(&mut self, key: &str, defined: &[&str]) -> io::Result<Option<String>> {
+    match self.take(key) {
+      Some(token) if defined.contains(&token.text) => Ok(Some(token.text.to_string())),
+      Some(token) => Err(parse_error(
+        self.line,
+        token.column,
+        format!("Unknown {} {}", key, token.text),
+      )),
+      None => Ok(None),
+    }
+  }
+
+  // a constant color under `key`, or a texture defined earlier
+  fn color_source(&mut self, key: &str, textures: &[&str]) -> io::Result<ColorSource> {
+    match self.name("texture", textures)? {
+      Some(name) => Ok(ColorSource::Texture(name)),
+      None => Ok(ColorSource::Color(self.vector(key, None)?)),
+    }
+  }
+
+  // a number under `key`, or the name of a texture defined earlier
+  fn scalar_source(&mut self, key: &str, default: f64, textures: &[&str]) -> io::Result<ScalarSource> {
+    match self.take(key) {
+      Some(token) if textures.contains(&token.text) => Ok(ScalarSource::Texture(token.text.to_string())),
+      Some(token) => Ok(ScalarSource::Value(parse_number(self.line, token)?)),
+      None => Ok(ScalarSource::Value(default)),
+    }
+  }
+
+  fn finish(self) -> io::Result<()> {
+    match self.entries.iter().find(|(_, _, used)| !used) {
+      Some((key, value, _)) => Err(parse_error(
+        self.line,
+        value.column - key.chars().count() - 1,
+        format!("Unknown property {}", key),
+      )),
+      None => Ok(()),
+    }
+  }
+}
+
+fn parse_number(line: usize, token: Token) -> io::Result<f64> {
+  token.text.parse::<f64>().map_err(|_| {
+    parse_error(
+      line,
+      token.column,
+      format!("Expected a number, but received {}", token.text),
+    )
+  })
+}
+
+fn parse_vector(line: usize, token: Token) -> io::Result<Vec3> {
+  let parts: Vec<&str> = token.text.split(',').collect();
+  let numbers: Vec<f64> = parts.iter().filter_map(|part| part.parse::<f64>().ok()).collect();
+
+  if parts.len() != 3 || numbers.len() != 3 {
+    return Err(parse_error(
+      line,
+      token.column,
+      format!("Expected a vector x,y,z, but received {}", token.text),
+    ));
+  }
+
+  Ok(Vec3(numbers[0], numbers[1], numbers[2]))
+}
+
+fn expect_word<'a>(line: usize, tokens: &[Token<'a>], index: usize, what: &str) -> io::Result<Token<'a>> {
+  match tokens.get(index) {
+    Some(token) if !token.text.contains('=') => Ok(*token),
+    Some(token) => Err(parse_error(line, token.column, format!("Expected {}", what))),
+    None => {
+      let last = tokens[tokens.len() - 1];
+      let column = last.column + last.text.chars().count();
+      Err(parse_error(line, column, format!("Expected {}", what)))
+    }
+  }
+}
+
+pub fn parse(source: &str) -> io::Result<Scene> {
+  let mut scene = Scene::new();
+
+  for (number, line) in source.lines().enumerate() {
+    let number = number + 1;
+    let line = match line.find('#') {
+      Some(comment) => &line[..comment],
+      None => line,
+    };
+    let tokens = tokenize(line);
+
+    if tokens.is_empty() {
+      continue;
+    }
+
+    let keyword = tokens[0];
+    let texture_names: Vec<String> = scene.textures.iter().map(|(name, _)| name.clone()).collect();
+    let material_names: Vec<String> = scene.materials.iter().map(|(name, _)| name.clone()).collect();
+    let texture_names: Vec<&str> = texture_names.iter().map(|name| name.as_str()).collect();
+    let material_names: Vec<&str> = material_names.iter().map(|name| name.as_str()).collect();
+
+    match keyword.text {
+      "render" => {
+        let mut props = Properties::new(number, keyword.column, &tokens[1..])?;
+        let defaults = RenderSettings::default();
+        let sampler = props
+          .name("sampler", &["independent", "stratified", "halton", "sobol"])?
+          .and_then(|name| SamplerKind::from_name(&name))
+          .unwrap_or(defaults.sampler);
+        scene.settings = RenderSettings {
+          width: props.integer("width", defaults.width)?,
+          height: props.integer("height", defaults.height)?,
+          samples_per_pixel: props.integer("samples", defaults.samples_per_pixel)?,
+          max_depth: props.integer("depth", defaults.max_depth)?,
+          threads: props.integer("threads", defaults.threads)?,
+          antialias: props.boolean("antialias", defaults.antialias)?,
+          seed: props.integer("seed", defaults.seed)?,
+          sampler,
+          bvh: props.boolean("bvh", defaults.bvh)?,
+        };
+        props.finish()?;
+
+        if scene.settings.width == 0 || scene.settings.height == 0 {
+          return Err(parse_error(
+            number,
+            keyword.column,
+            "Expected a width and height above 0".to_string(),
+          ));
+        }
+      }
+      "background" => {
+        let kind = expect_word(number, &tokens, 1, "a background kind")?;
+        let mut props = Properties::new(number, keyword.column, &tokens[2..])?;
+        scene.background = match kind.text {
+          "color" => BackgroundDescription::Color(props.vector("value", None)?),
+          "gradient" => BackgroundDescription::Gradient(props.vector("bottom", None)?, props.vector("top", None)?),
+          "sky" => BackgroundDescription::Gradient(Vec3(1.0, 1.0, 1.0), Vec3(0.5, 0.7, 1.0)),
+          "environment" => BackgroundDescription::Environment {
+            file: props.required("file")?.text.to_string(),
+            rotation: props.number("rotation", Some(0.0))?,
+            intensity: props.number("intensity", Some(1.0))?,
+          },
+          other => {
+            return Err(parse_error(
+              number,
+              kind.column,
+              format!("Unknown background {}", other),
+            ))
+          }
+        };
+        props.finish()?;
+      }
+      "camera" => {
+        let kind = expect_word(number, &tokens, 1, "a camera kind")?;
+        let mut props = Properties::new(number, keyword.column, &tokens[2..])?;
+        let kind = match kind.text {
+          "positional" => CameraKind::Positional,
+          "lens" => CameraKind::Lens,
+          "exposure" => CameraKind::Exposure,
+          other => return Err(parse_error(number, kind.column, format!("Unknown camera {}", other))),
+        };
+        let mut camera = CameraDescription {
+          kind,
+          look_from: props.vector("from", None)?,
+          look_at: props.vector("at", None)?,
+          vup: props.vector("up", Some(Vec3(0.0, 1.0, 0.0)))?,
+          vfov: props.number("fov", None)?,
+          ..CameraDescription::default()
+        };
+        if kind != CameraKind::Positional {
+          camera.aperture = props.number("aperture", None)?;
+          camera.focus_dist = props.number("focus", None)?;
+        }
+        if kind == CameraKind::Exposure {
+          camera.time0 = props.number("time0", None)?;
+          camera.time1 = props.number("time1", None)?;
+        }
+        props.finish()?;
+        scene.camera = camera;
+      }
+      "texture" => {
+        let name = expect_word(number, &tokens, 1, "a texture name")?;
+        let kind = expect_word(number, &tokens, 2, "a texture kind")?;
+        let mut props = Properties::new(number, keyword.column, &tokens[3..])?;
+        let texture = match kind.text {
+          "solid" => TextureDescription::Solid(props.vector("color", None)?),
+          "checker" => TextureDescription::Checker {
+            odd: props.vector("odd", None)?,
+            even: props.vector("even", None)?,
+            scale: props.number("scale", Some(10.0))?,
+          },
+          "image" => TextureDescription::Image {
+            file: props.required("file")?.text.to_string(),
+          },
+          "noise" => TextureDescription::Noise {
+            scale: props.number("scale", Some(1.0))?,
+            turbulence_depth: props.integer("turbulence", 7)?,
+          },
+          other => return Err(parse_error(number, kind.column, format!("Unknown texture {}", other))),
+        };
+        props.finish()?;
+
+        if texture_names.contains(&name.text) {
+          return Err(parse_error(number, name.column, format!("Duplicate texture {}", name.text)));
+        }
+        scene.add_texture(name.text, texture);
+      }
+      "material" => {
+        let name = expect_word(number, &tokens, 1, "a material name")?;
+        let kind = expect_word(number, &tokens, 2, "a material kind")?;
+        let mut props = Properties::new(number, keyword.column, &tokens[3..])?;
+        let material = match kind.text {
+          "lambertian" => MaterialDescription::Lambertian {
+            albedo: props.color_source("albedo", &texture_names)?,
+          },
+          "metal" => MaterialDescription::Metal {
+            albedo: props.color_source("albedo", &texture_names)?,
+            fuzz: props.number("fuzz", Some(0.0))?,
+          },
+          "dielectric" => MaterialDescription::Dielectric {
+            refract_index: props.number("ior", None)?,
+          },
+          "diffuse_light" => MaterialDescription::DiffuseLight {
+            emit: props.color_source("emit", &texture_names)?,
+          },
+          "conductor" => {
+            let presets: Vec<&str> = CONDUCTORS.iter().map(|(name, _, _)| *name).collect();
+            // a preset stands in for eta and k
+            let (eta, k) = match props.name("preset", &presets)? {
+              Some(preset) => {
+                let (_, eta, k) = CONDUCTORS.iter().find(|(name, _, _)| *name == preset).unwrap();
+                (*eta, *k)
+              }
+              None => (props.vector("eta", None)?, props.vector("k", None)?),
+            };
+            MaterialDescription::Conductor {
+              eta,
+              k,
+              roughness: props.number("roughness", Some(0.0))?,
+            }
+          }
+          "rough_dielectric" => MaterialDescription::RoughDielectric {
+            refract_index: props.number("ior", None)?,
+            roughness: props.number("roughness", Some(0.0))?,
+          },
+          "principled" => MaterialDescription::Principled {
+            base_color: props.color_source("base", &texture_names)?,
+            metallic: props.scalar_source("metallic", 0.0, &texture_names)?,
+            roughness: props.scalar_source("roughness", 0.5, &texture_names)?,
+            specular: props.scalar_source("specular", 0.5, &texture_names)?,
+            specular_tint: props.scalar_source("specular_tint", 0.0, &texture_names)?,
+            sheen: props.scalar_source("sheen", 0.0, &texture_names)?,
+            clearcoat: props.scalar_source("clearcoat", 0.0, &texture_names)?,
+            transmission: props.scalar_source("transmission", 0.0, &texture_names)?,
+            ior: props.scalar_source("ior", 1.5, &texture_names)?,
+          },
+          other => return Err(parse_error(number, kind.column, format!("Unknown material {}", other))),
+        };
+        props.finish()?;
+
+        if material_names.contains(&name.text) {
+          return Err(parse_error(number, name.column, format!("Duplicate material {}", name.text)));
+        }
+        scene.add_material(name.text, material);
+      }
+      "sphere" | "moving_sphere" | "triangle" | "mesh" | "quad" | "box" | "plane" => {
+        let mut props = Properties::new(number, keyword.column, &tokens[1..])?;
+        let shape = match keyword.text {
+          "sphere" => ShapeDescription::Sphere {
+            center: props.vector("center", None)?,
+            radius: props.number("radius", None)?,
+            material: props.name("material", &material_names)?,
+          },
+          "moving_sphere" => ShapeDescription::MovingSphere {
+            center0: props.vector("center0", None)?,
+            center1: props.vector("center1", None)?,
+            time0: props.number("time0", None)?,
+            time1: props.number("time1", None)?,
+            radius: props.number("radius", None)?,
+            material: props.name("material", &material_names)?,
+          },
+          "triangle" => ShapeDescription::Triangle {
+            v0: props.vector("v0", None)?,
+            v1: props.vector("v1", None)?,
+            v2: props.vector("v2", None)?,
+            material: props.name("material", &material_names)?,
+          },
+          "mesh" => ShapeDescription::Mesh {
+            file: props.required("file")?.text.to_string(),
+            group: props.take("group").map(|token| token.text.to_string()),
+            material: props.name("material", &material_names)?,
+          },
+          "quad" => ShapeDescription::Quad {
+            corner: props.vector("corner", None)?,
+            u: props.vector("u", None)?,
+            v: props.vector("v", None)?,
+            material: props.name("material", &material_names)?,
+          },
+          "box" => ShapeDescription::Box {
+            min: props.vector("min", None)?,
+            max: props.vector("max", None)?,
+            material: props.name("material", &material_names)?,
+          },
+          _ => ShapeDescription::Plane {
+            point: props.vector("point", None)?,
+            normal: props.vector("normal", None)?,
+            material: props.name("material", &material_names)?,
+          },
+        };
+        props.finish()?;
+        scene.add_shape(shape);
+      }
+      other => {
+        return Err(parse_error(
+          number,
+          keyword.column,
+          format!("Unknown statement {}", other),
+        ))
+      }
+    }
+  }
+
+  Ok(scene)
+}
+
+pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Scene> {
+  parse(&fs::read_to_string(path)?)
+}
+
+#[cfg(test)]
+mod tests {
+
+  use super::*;
+  use crate::framebuffer::Framebuffer;
+  use crate::hittable::{HitRecord, Hittable};
+  use crate::ray::Ray;
+
+  static SCENE: &str = "
+# two spheres on a checker floor
+render width=40 height=20 samples=4 depth=8 threads=2 sampler=halton bvh=true
+background color value=0,0,0
+camera lens from=0,1,5 at=0,0,0 fov=30 aperture=0.1 focus=5
+texture floor checker odd=0.2,0.3,0.1 even=0.9,0.9,0.9 scale=5
+material ground lambertian texture=floor
+material light diffuse_light emit=4,4,4
+material glass dielectric ior=1.5
+material copper conductor preset=copper roughness=0.3
+material frosted rough_dielectric ior=1.5 roughness=0.2
+material paint principled base=0.8,0.1,0.1 roughness=floor clearcoat=1 transmission=0.2
+sphere center=0,-1000,0 radius=1000 material=ground
+moving_sphere center0=0,1,0 center1=0,1.5,0 time0=0 time1=1 radius=0.5 material=glass
+triangle v0=-1,0,-2 v1=1,0,-2 v2=0,2,-2 material=light
+quad corner=-1,3,-1 u=2,0,0 v=0,0,2 material=light
+box min=2,0,0 max=3,1,1 material=ground
+plane point=0,-5,0 normal=0,1,0
+";
+
+  #[test]
+  fn test_parse_builds_scene() {
+    let scene = parse(SCENE).unwrap();
+    assert_eq!(scene.settings.width, 40);
+    assert_eq!(scene.settings.samples_per_pixel, 4);
+    assert_eq!(scene.settings.sampler, SamplerKind::Halton);
+    assert_eq!(scene.camera.kind, CameraKind::Lens);
+    assert_eq!(scene.textures.len(), 1);
+    assert_eq!(scene.materials.len(), 6);
+    assert_eq!(scene.shapes.len(), 6);
+    assert_eq!(scene.build_lights().objects.len(), 2);
+
+    let world = scene.build_world().unwrap();
+    let mut record = HitRecord::new();
+    let ray = Ray::new(Vec3(0.0, 0.5, 5.0), Vec3(0.0, 0.0, -1.0));
+    assert!(world.hit(&ray, 0.001, f64::INFINITY, &mut record));
+
+    // the plane stays next to the BVH
+    assert_eq!(world.objects.len(), 2);
+    let down = Ray::new(Vec3(2000.0, 0.0, 0.0), Vec3(0.0, -1.0, 0.0));
+    assert!(world.hit(&down, 0.001, f64::INFINITY, &mut record));
+    assert!((record.t - 5.0).abs() < 1.0e-9);
+  }
+
+  #[test]
+  fn test_serialize_round_trips() {
+    let scene = parse(SCENE).unwrap();
+    let text = scene.serialize();
+    let reparsed = parse(&text).unwrap();
+    assert_eq!(reparsed.serialize(), text);
+    assert_eq!(reparsed.shapes.len(), scene.shapes.len());
+  }
+
+  #[test]
+  fn test_seed_is_read_as_u64() {
+    let scene = parse("render seed=18446744073709551615\n").unwrap();
+    assert_eq!(scene.settings.seed, u64::MAX);
+    assert_eq!(parse(&scene.serialize()).unwrap().settings.seed, u64::MAX);
+  }
+
+  #[test]
+  fn test_bundled_scene_files_load() {
+    let scenes = concat!(env!("CARGO_MANIFEST_DIR"), "/scenes");
+
+    let cornell = load(format!("{}/cornell.scene", scenes)).unwrap();
+    assert_eq!(cornell.shapes.len(), 6 + 2);
+    assert_eq!(cornell.build_lights().objects.len(), 1);
+    let world = cornell.build_world().unwrap();
+    let mut record = HitRecord::new();
+    let ray = Ray::new(Vec3(278.0, 278.0, -800.0), Vec3(0.0, 0.0, 1.0));
+    assert!(world.hit(&ray, 0.001, f64::INFINITY, &mut record));
+
+    // demo15's random_scene: the ground, three big spheres and the small ones
+    let random = load(format!("{}/random.scene", scenes)).unwrap();
+    assert_eq!(random.shapes.len(), 486);
+    assert_eq!(random.materials.len(), 455);
+    let world = random.build_world().unwrap();
+    let ray = Ray::new(Vec3(13.0, 2.0, 3.0), Vec3(-13.0, -2.0, -3.0));
+    assert!(world.hit(&ray, 0.001, f64::INFINITY, &mut record));
+  }
+
+  #[test]
+  fn test_environment_background_loads_map() {
+    let path = std::env::temp_dir().join("ray_tracing_scene_environment.pfm");
+    let mut image = Framebuffer::new(4, 2);
+    for pixel in image.pixels.iter_mut() {
+      *pixel = Vec3::fill(2.0);
+    }
+    image.save(&path, false).unwrap();
+
+    let source = format!(
+      "background environment file={} rotation=45 intensity=0.5\n",
+      path.display()
+    );
+    let scene = parse(&source).unwrap();
+    assert_eq!(parse(&scene.serialize()).unwrap().serialize(), scene.serialize());
+
+    let background = scene.build_background().unwrap();
+    assert!(background.is_sampled());
+    let ray = Ray::new(Vec3::fill(0.0), Vec3(0.3, 0.2, -1.0));
+    assert!((background.value(&ray) - Vec3::fill(1.0)).len() < 1.0e-6);
+  }
+
+  #[test]
+  fn test_errors_report_line_and_column() {
+    let err = parse("material a lambertian albedo=1,1\n").unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    assert!(err.to_string().starts_with("line 1, column 30:"));
+
+    let err = parse("render width=10\n\nsphere center=0,0,0 radius=1 material=missing\n").unwrap_err();
+    assert!(err.to_string().starts_with("line 3, column 39:"));
+
+    let err = parse("  sphere center=0,0,0\n").unwrap_err();
+    assert!(err.to_string().starts_with("line 1, column 3: Expected property radius"));
+
+    let err = parse("sphere center=0,0,0 radius=1 color=1,0,0\n").unwrap_err();
+    assert!(err.to_string().starts_with("line 1, column 30: Unknown property color"));
+  }
+}
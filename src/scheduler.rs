@@ -0,0 +1,102 @@
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+// a rectangle of pixels, rows counted from the top of the image
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tile {
+  pub x: usize,
+  pub y: usize,
+  pub width: usize,
+  pub height: usize,
+}
+
+// splits the image into tiles and deals a contiguous run of them to each worker,
+// a worker whose queue runs dry steals from the back of the other queues
+pub struct TileScheduler {
+  queues: Vec<Mutex<VecDeque<Tile>>>,
+}
+
+impl TileScheduler {
+  pub fn new(width: usize, height: usize, tile_size: usize, workers: usize) -> TileScheduler {
+    let tiles = TileScheduler::tiles(width, height, tile_size);
+    let workers = workers.max(1);
+    let count = tiles.len();
+
+    let mut queues: Vec<Mutex<VecDeque<Tile>>> = vec![];
+    let mut tiles = tiles.into_iter();
+    for worker in 0..workers {
+      let share = (worker + 1) * count / workers - worker * count / workers;
+      queues.push(Mutex::new(tiles.by_ref().take(share).collect()));
+    }
+
+    TileScheduler { queues }
+  }
+
+  // row by row from the top-left, tiles on the right and bottom edges may be smaller
+  pub fn tiles(width: usize, height: usize, tile_size: usize) -> Vec<Tile> {
+    let tile_size = tile_size.max(1);
+    let mut tiles = vec![];
+
+    for y in (0..height).step_by(tile_size) {
+      for x in (0..width).step_by(tile_size) {
+        tiles.push(Tile {
+          x,
+          y,
+          width: tile_size.min(width - x),
+          height: tile_size.min(height - y),
+        });
+      }
+    }
+
+    tiles
+  }
+
+  pub fn workers(&self) -> usize {
+    self.queues.len()
+  }
+
+  // the next tile for a worker, None once every queue is empty
+  pub fn next(&self, worker: usize) -> Option<Tile> {
+    let workers = self.queues.len();
+
+    if let Some(tile) = self.queues[worker % workers].lock().unwrap().pop_front() {
+      return Some(tile);
+    }
+
+    // the back of a queue is the work its owner would reach last
+    (1..workers).find_map(|offset| {
+      let victim = (worker + offset) % workers;
+      self.queues[victim].lock().unwrap().pop_back()
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+
+  use super::*;
+
+  #[test]
+  fn test_tiles_cover_the_image() {
+    let tiles = TileScheduler::tiles(37, 20, 16);
+    assert_eq!(tiles.len(), 3 * 2);
+    assert_eq!(tiles[2], Tile { x: 32, y: 0, width: 5, height: 16 });
+    assert_eq!(tiles.iter().map(|tile| tile.width * tile.height).sum::<usize>(), 37 * 20);
+  }
+
+  #[test]
+  fn test_idle_worker_steals_remaining_tiles() {
+    let scheduler = TileScheduler::new(64, 64, 16, 4);
+    let mut seen = vec![];
+
+    // worker 0 drains its own queue first, then steals everything the others left
+    while let Some(tile) = scheduler.next(0) {
+      seen.push((tile.x, tile.y));
+    }
+
+    seen.sort();
+    seen.dedup();
+    assert_eq!(seen.len(), 16);
+    assert!(scheduler.next(3).is_none());
+  }
+}
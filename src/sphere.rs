@@ -1,29 +1,44 @@
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+use crate::aabb::Aabb;
 use crate::hittable::{HitRecord, Hittable};
 use crate::material::{DefaultMaterial, Material};
+use crate::onb::Onb;
 use crate::ray::Ray;
+use crate::sampler::sample_2d;
 use crate::vec3::Vec3;
 
 #[derive(Debug)]
 pub struct Sphere {
   pub center: Vec3,
   pub radius: f64,
-  pub material: Box<dyn Material>,
+  pub material: Arc<dyn Material>,
 }
 
 impl Sphere {
   pub fn new(center: Vec3, radius: f64) -> Sphere {
-    let material = Box::new(DefaultMaterial::new());
+    let material = Arc::new(DefaultMaterial::new());
     Sphere {
       center,
       radius,
       material,
     }
   }
-  pub fn set_material(&mut self, material: Box<dyn Material>) {
-    self.material = material.box_clone();
+  pub fn set_material(&mut self, material: Arc<dyn Material>) {
+    self.material = material;
   }
 }
 
+// maps a point on the unit sphere to (u, v), with u going around the y axis from -x
+// and v going from the bottom pole to the top pole
+pub fn get_sphere_uv(point: Vec3) -> (f64, f64) {
+  let theta = (-point.y()).acos();
+  let phi = (-point.z()).atan2(point.x()) + PI;
+
+  (phi / (2.0 * PI), theta / PI)
+}
+
 fn in_min_max(t: f64, min: f64, max: f64) -> bool {
   if min > max {
     panic!(
@@ -35,7 +50,7 @@ fn in_min_max(t: f64, min: f64, max: f64) -> bool {
 }
 
 impl Hittable for Sphere {
-  fn hit(&self, ray: &Ray, t_min: f64, t_max: f64, record: &mut HitRecord) -> bool {
+  fn hit<'a>(&'a self, ray: &Ray, t_min: f64, t_max: f64, record: &mut HitRecord<'a>) -> bool {
     let Sphere {
       center,
       radius,
@@ -55,10 +70,13 @@ impl Hittable for Sphere {
         let point = ray.at(temp);
         let outward_normal = (point - center) / radius;
 
+        let (u, v) = get_sphere_uv(outward_normal);
+
         record.set_t(temp);
         record.set_point(point);
-        record.set_face_normal(&ray, outward_normal);
-        record.set_material(material.box_clone());
+        record.set_uv(u, v);
+        record.set_face_normal(ray, outward_normal);
+        record.set_material(material.as_ref());
       };
 
       let temp = (-half_b - root) / a;
@@ -76,7 +94,44 @@ impl Hittable for Sphere {
       }
     }
 
-    return false;
+    false
+  }
+
+  fn bounding_box(&self, _time0: f64, _time1: f64, output_box: &mut Aabb) -> bool {
+    let offset = Vec3::fill(self.radius.abs());
+    *output_box = Aabb::new(self.center - offset, self.center + offset);
+    true
+  }
+
+  // uniform over the cone of directions the sphere covers, which cannot be sampled from inside
+  fn pdf_value(&self, origin: Vec3, direction: Vec3) -> f64 {
+    let mut record = HitRecord::new();
+    if !self.hit(&Ray::new(origin, direction), 0.001, f64::INFINITY, &mut record) {
+      return 0.0;
+    }
+
+    let distance_squared = (self.center - origin).length_squared();
+    let radius_squared = self.radius * self.radius;
+    if distance_squared <= radius_squared {
+      return 0.0;
+    }
+
+    let cos_theta_max = (1.0 - radius_squared / distance_squared).sqrt();
+    1.0 / (2.0 * PI * (1.0 - cos_theta_max))
+  }
+
+  fn random(&self, origin: Vec3) -> Vec3 {
+    let direction = self.center - origin;
+    let distance_squared = direction.length_squared();
+    let cos_theta_max = (1.0 - self.radius * self.radius / distance_squared).max(0.0).sqrt();
+
+    let (u, v) = sample_2d();
+    let z = 1.0 + u * (cos_theta_max - 1.0);
+    let phi = 2.0 * PI * v;
+    let sin_theta = (1.0 - z * z).max(0.0).sqrt();
+
+    // around the direction to the center
+    Onb::from_w(direction).local(Vec3(phi.cos() * sin_theta, phi.sin() * sin_theta, z))
   }
 }
 
@@ -86,12 +141,12 @@ pub struct MovingSphere {
   radius: f64,
   time1: f64,
   time2: f64,
-  material: Box<dyn Material>,
+  material: Arc<dyn Material>,
 }
 
 impl MovingSphere {
   pub fn new(center1: Vec3, center2: Vec3, time1: f64, time2: f64, radius: f64) -> MovingSphere {
-    let material = Box::new(DefaultMaterial::new());
+    let material = Arc::new(DefaultMaterial::new());
 
     MovingSphere {
       center1,
@@ -103,7 +158,7 @@ impl MovingSphere {
     }
   }
 
-  pub fn set_material(&mut self, material: Box<dyn Material>) {
+  pub fn set_material(&mut self, material: Arc<dyn Material>) {
     self.material = material;
   }
 }
@@ -113,7 +168,7 @@ fn get_center_by_time(center1: Vec3, center2: Vec3, time1: f64, time2: f64, time
 }
 
 impl Hittable for MovingSphere {
-  fn hit(&self, ray: &Ray, t_min: f64, t_max: f64, record: &mut HitRecord) -> bool {
+  fn hit<'a>(&'a self, ray: &Ray, t_min: f64, t_max: f64, record: &mut HitRecord<'a>) -> bool {
     let MovingSphere {
       center1,
       center2,
@@ -136,10 +191,13 @@ impl Hittable for MovingSphere {
         let point = ray.at(temp);
         let outward_normal = (point - center) / *radius;
 
+        let (u, v) = get_sphere_uv(outward_normal);
+
         record.set_t(temp);
         record.set_point(point);
-        record.set_face_normal(&ray, outward_normal);
-        record.set_material(material.box_clone());
+        record.set_uv(u, v);
+        record.set_face_normal(ray, outward_normal);
+        record.set_material(material.as_ref());
       };
 
       let temp = (-half_b - root) / a;
@@ -157,6 +215,27 @@ impl Hittable for MovingSphere {
       }
     }
 
-    return false;
+    false
+  }
+
+  fn bounding_box(&self, time0: f64, time1: f64, output_box: &mut Aabb) -> bool {
+    let MovingSphere {
+      center1,
+      center2,
+      radius,
+      time1: start,
+      time2: end,
+      ..
+    } = *self;
+
+    // the sphere sweeps between the two centers, so the union of both end boxes covers it
+    let offset = Vec3::fill(radius.abs());
+    let center0 = get_center_by_time(center1, center2, start, end, time0);
+    let center1 = get_center_by_time(center1, center2, start, end, time1);
+    let box0 = Aabb::new(center0 - offset, center0 + offset);
+    let box1 = Aabb::new(center1 - offset, center1 + offset);
+
+    *output_box = Aabb::surrounding_box(box0, box1);
+    true
   }
 }
@@ -0,0 +1,183 @@
+use std::fmt::Debug;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::framebuffer::Framebuffer;
+use crate::image::{self, ImageFormat};
+use crate::perlin::Perlin;
+use crate::utils::clamp;
+use crate::vec3::Vec3;
+
+pub trait Texture: Debug + Send + Sync {
+  fn value(&self, u: f64, v: f64, point: Vec3) -> Vec3;
+}
+
+#[derive(Debug)]
+pub struct SolidColor {
+  color: Vec3,
+}
+
+impl SolidColor {
+  pub fn new(color: Vec3) -> SolidColor {
+    SolidColor { color }
+  }
+}
+
+impl Texture for SolidColor {
+  fn value(&self, _u: f64, _v: f64, _point: Vec3) -> Vec3 {
+    self.color
+  }
+}
+
+// a 3D checker pattern, so it tiles any surface without relying on (u, v)
+#[derive(Debug)]
+pub struct CheckerTexture {
+  odd: Arc<dyn Texture>,
+  even: Arc<dyn Texture>,
+  scale: f64,
+}
+
+impl CheckerTexture {
+  pub fn new(odd: Arc<dyn Texture>, even: Arc<dyn Texture>) -> CheckerTexture {
+    CheckerTexture {
+      odd,
+      even,
+      scale: 10.0,
+    }
+  }
+
+  pub fn from_colors(odd: Vec3, even: Vec3) -> CheckerTexture {
+    CheckerTexture::new(Arc::new(SolidColor::new(odd)), Arc::new(SolidColor::new(even)))
+  }
+
+  // number of checker cells per world unit
+  pub fn set_scale(&mut self, scale: f64) {
+    self.scale = scale;
+  }
+}
+
+impl Texture for CheckerTexture {
+  fn value(&self, u: f64, v: f64, point: Vec3) -> Vec3 {
+    let scale = self.scale;
+    let sines = (scale * point.x()).sin() * (scale * point.y()).sin() * (scale * point.z()).sin();
+
+    if sines < 0.0 {
+      self.odd.value(u, v, point)
+    } else {
+      self.even.value(u, v, point)
+    }
+  }
+}
+
+#[derive(Debug)]
+pub struct ImageTexture {
+  image: Framebuffer,
+}
+
+impl ImageTexture {
+  // expects colors already in linear space
+  pub fn new(image: Framebuffer) -> ImageTexture {
+    ImageTexture { image }
+  }
+
+  // loads a .ppm or .png file, whose colors are gamma encoded like the images we write,
+  // or a linear .hdr or .pfm file
+  pub fn load<P: AsRef<Path>>(path: P) -> io::Result<ImageTexture> {
+    let path = path.as_ref();
+    let mut image = image::read(path)?;
+
+    // undo the gamma=2.0 encoding so the texture multiplies light linearly
+    let linear = ImageFormat::from_path(path).is_some_and(|format| format.is_high_dynamic_range());
+    if !linear {
+      for color in image.pixels.iter_mut() {
+        *color = *color * *color;
+      }
+    }
+
+    Ok(ImageTexture::new(image))
+  }
+}
+
+impl Texture for ImageTexture {
+  fn value(&self, u: f64, v: f64, _point: Vec3) -> Vec3 {
+    let Framebuffer { width, height, .. } = self.image;
+
+    if width == 0 || height == 0 {
+      // debugging aid for a missing texture
+      return Vec3(0.0, 1.0, 1.0);
+    }
+
+    let u = clamp(u, 0.0, 1.0);
+    // image rows start at the top, while v grows upwards
+    let v = 1.0 - clamp(v, 0.0, 1.0);
+
+    let i = ((u * width as f64) as usize).min(width - 1);
+    let j = ((v * height as f64) as usize).min(height - 1);
+
+    self.image.get(i, j)
+  }
+}
+
+// marble-like stripes whose phase is disturbed by turbulence
+#[derive(Debug)]
+pub struct NoiseTexture {
+  noise: Perlin,
+  scale: f64,
+  turbulence_depth: usize,
+}
+
+impl NoiseTexture {
+  pub fn new(scale: f64) -> NoiseTexture {
+    NoiseTexture {
+      noise: Perlin::new(),
+      scale,
+      turbulence_depth: 7,
+    }
+  }
+
+  pub fn set_turbulence_depth(&mut self, turbulence_depth: usize) {
+    self.turbulence_depth = turbulence_depth;
+  }
+}
+
+impl Texture for NoiseTexture {
+  fn value(&self, _u: f64, _v: f64, point: Vec3) -> Vec3 {
+    let turbulence = self.noise.turb(point, self.turbulence_depth);
+    let phase = self.scale * point.z() + 10.0 * turbulence;
+    Vec3::fill(1.0) * 0.5 * (1.0 + phase.sin())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+
+  use super::*;
+
+  #[test]
+  fn test_image_texture_lookup() {
+    let mut image = Framebuffer::new(2, 2);
+    image.set(0, 0, Vec3(1.0, 0.0, 0.0));
+    image.set(1, 0, Vec3(0.0, 1.0, 0.0));
+    image.set(0, 1, Vec3(0.0, 0.0, 1.0));
+    image.set(1, 1, Vec3(1.0, 1.0, 1.0));
+    let texture = ImageTexture::new(image);
+    let point = Vec3::fill(0.0);
+
+    // v = 1 is the top row of the image
+    assert!(texture.value(0.0, 1.0, point).eq(Vec3(1.0, 0.0, 0.0)));
+    assert!(texture.value(1.0, 1.0, point).eq(Vec3(0.0, 1.0, 0.0)));
+    assert!(texture.value(0.0, 0.0, point).eq(Vec3(0.0, 0.0, 1.0)));
+    assert!(texture.value(0.9, 0.1, point).eq(Vec3(1.0, 1.0, 1.0)));
+  }
+
+  #[test]
+  fn test_noise_texture_stays_in_range() {
+    let texture = NoiseTexture::new(4.0);
+
+    for _ in 0..1000 {
+      let color = texture.value(0.0, 0.0, Vec3::random_in(-10.0, 10.0));
+      assert!(color.x() >= 0.0 && color.x() <= 1.0);
+    }
+  }
+}
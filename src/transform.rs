@@ -0,0 +1,379 @@
+use std::sync::Arc;
+
+use crate::aabb::Aabb;
+use crate::hittable::{self, HitRecord, Hittable};
+use crate::material::Material;
+use crate::ray::Ray;
+use crate::vec3::Vec3;
+
+type Matrix = [[f64; 4]; 4];
+
+const IDENTITY: Matrix = [
+  [1.0, 0.0, 0.0, 0.0],
+  [0.0, 1.0, 0.0, 0.0],
+  [0.0, 0.0, 1.0, 0.0],
+  [0.0, 0.0, 0.0, 1.0],
+];
+
+fn multiply(a: &Matrix, b: &Matrix) -> Matrix {
+  let mut product = [[0.0; 4]; 4];
+  for (row, product_row) in product.iter_mut().enumerate() {
+    for (column, value) in product_row.iter_mut().enumerate() {
+      *value = (0..4).map(|k| a[row][k] * b[k][column]).sum();
+    }
+  }
+  product
+}
+
+// Gauss-Jordan elimination with partial pivoting, None for a singular matrix
+fn invert(matrix: &Matrix) -> Option<Matrix> {
+  let mut left = *matrix;
+  let mut right = IDENTITY;
+
+  for column in 0..4 {
+    let pivot = (column..4)
+      .max_by(|a, b| left[*a][column].abs().total_cmp(&left[*b][column].abs()))
+      .unwrap();
+    if left[pivot][column].abs() < 1.0e-12 {
+      return None;
+    }
+    left.swap(column, pivot);
+    right.swap(column, pivot);
+
+    let scale = 1.0 / left[column][column];
+    for k in 0..4 {
+      left[column][k] *= scale;
+      right[column][k] *= scale;
+    }
+
+    for row in 0..4 {
+      if row == column {
+        continue;
+      }
+      let factor = left[row][column];
+      for k in 0..4 {
+        left[row][k] -= factor * left[column][k];
+        right[row][k] -= factor * right[column][k];
+      }
+    }
+  }
+
+  Some(right)
+}
+
+// an affine transform, kept together with its inverse so neither has to be recomputed per ray.
+// transforms are built from the constructors below and chained with `then`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transform {
+  matrix: Matrix,
+  inverse: Matrix,
+}
+
+impl Default for Transform {
+  fn default() -> Self {
+    Transform::identity()
+  }
+}
+
+impl Transform {
+  pub fn identity() -> Transform {
+    Transform {
+      matrix: IDENTITY,
+      inverse: IDENTITY,
+    }
+  }
+
+  // row major, the last row is expected to be 0, 0, 0, 1. None when the matrix has no inverse
+  pub fn from_matrix(matrix: [[f64; 4]; 4]) -> Option<Transform> {
+    invert(&matrix).map(|inverse| Transform { matrix, inverse })
+  }
+
+  pub fn translate(offset: Vec3) -> Transform {
+    let Vec3(x, y, z) = offset;
+    Transform {
+      matrix: [
+        [1.0, 0.0, 0.0, x],
+        [0.0, 1.0, 0.0, y],
+        [0.0, 0.0, 1.0, z],
+        [0.0, 0.0, 0.0, 1.0],
+      ],
+      inverse: [
+        [1.0, 0.0, 0.0, -x],
+        [0.0, 1.0, 0.0, -y],
+        [0.0, 0.0, 1.0, -z],
+        [0.0, 0.0, 0.0, 1.0],
+      ],
+    }
+  }
+
+  // scales each axis by its own factor. None when a factor is 0, like from_matrix
+  pub fn scale(factors: Vec3) -> Option<Transform> {
+    let Vec3(x, y, z) = factors;
+    if x == 0.0 || y == 0.0 || z == 0.0 {
+      return None;
+    }
+
+    Some(Transform {
+      matrix: [
+        [x, 0.0, 0.0, 0.0],
+        [0.0, y, 0.0, 0.0],
+        [0.0, 0.0, z, 0.0],
+        [0.0, 0.0, 0.0, 1.0],
+      ],
+      inverse: [
+        [1.0 / x, 0.0, 0.0, 0.0],
+        [0.0, 1.0 / y, 0.0, 0.0],
+        [0.0, 0.0, 1.0 / z, 0.0],
+        [0.0, 0.0, 0.0, 1.0],
+      ],
+    })
+  }
+
+  // counter-clockwise by degrees around axis when looking down at it from its tip
+  pub fn rotate(axis: Vec3, degrees: f64) -> Transform {
+    let Vec3(x, y, z) = axis.unit_vector();
+    let (sin, cos) = degrees.to_radians().sin_cos();
+    let t = 1.0 - cos;
+
+    let matrix = [
+      [t * x * x + cos, t * x * y - sin * z, t * x * z + sin * y, 0.0],
+      [t * x * y + sin * z, t * y * y + cos, t * y * z - sin * x, 0.0],
+      [t * x * z - sin * y, t * y * z + sin * x, t * z * z + cos, 0.0],
+      [0.0, 0.0, 0.0, 1.0],
+    ];
+
+    // a rotation is undone by its transpose
+    let mut inverse = matrix;
+    for (row, inverse_row) in inverse.iter_mut().enumerate() {
+      for (column, value) in inverse_row.iter_mut().enumerate() {
+        *value = matrix[column][row];
+      }
+    }
+
+    Transform { matrix, inverse }
+  }
+
+  pub fn rotate_x(degrees: f64) -> Transform {
+    Transform::rotate(Vec3(1.0, 0.0, 0.0), degrees)
+  }
+
+  pub fn rotate_y(degrees: f64) -> Transform {
+    Transform::rotate(Vec3(0.0, 1.0, 0.0), degrees)
+  }
+
+  pub fn rotate_z(degrees: f64) -> Transform {
+    Transform::rotate(Vec3(0.0, 0.0, 1.0), degrees)
+  }
+
+  // this transform followed by next
+  pub fn then(&self, next: Transform) -> Transform {
+    Transform {
+      matrix: multiply(&next.matrix, &self.matrix),
+      inverse: multiply(&self.inverse, &next.inverse),
+    }
+  }
+
+  pub fn inverse(&self) -> Transform {
+    Transform {
+      matrix: self.inverse,
+      inverse: self.matrix,
+    }
+  }
+
+  pub fn matrix(&self) -> [[f64; 4]; 4] {
+    self.matrix
+  }
+
+  pub fn point(&self, p: Vec3) -> Vec3 {
+    let m = &self.matrix;
+    Vec3(
+      m[0][0] * p.x() + m[0][1] * p.y() + m[0][2] * p.z() + m[0][3],
+      m[1][0] * p.x() + m[1][1] * p.y() + m[1][2] * p.z() + m[1][3],
+      m[2][0] * p.x() + m[2][1] * p.y() + m[2][2] * p.z() + m[2][3],
+    )
+  }
+
+  // directions ignore the translation
+  pub fn vector(&self, v: Vec3) -> Vec3 {
+    let m = &self.matrix;
+    Vec3(
+      m[0][0] * v.x() + m[0][1] * v.y() + m[0][2] * v.z(),
+      m[1][0] * v.x() + m[1][1] * v.y() + m[1][2] * v.z(),
+      m[2][0] * v.x() + m[2][1] * v.y() + m[2][2] * v.z(),
+    )
+  }
+
+  // normals go through the inverse transpose to stay perpendicular to the surface, the result is not normalized
+  pub fn normal(&self, n: Vec3) -> Vec3 {
+    let m = &self.inverse;
+    Vec3(
+      m[0][0] * n.x() + m[1][0] * n.y() + m[2][0] * n.z(),
+      m[0][1] * n.x() + m[1][1] * n.y() + m[2][1] * n.z(),
+      m[0][2] * n.x() + m[1][2] * n.y() + m[2][2] * n.z(),
+    )
+  }
+
+  // how much the transform scales volumes, negative when it mirrors
+  pub fn determinant(&self) -> f64 {
+    let m = &self.matrix;
+    m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1]) - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+      + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+  }
+
+  // the box around the eight transformed corners of bounds
+  pub fn bounding_box(&self, bounds: Aabb) -> Aabb {
+    let mut result = Aabb::empty();
+    for corner in 0..8 {
+      let pick = |bit: usize, axis: usize| {
+        if corner & bit == 0 {
+          bounds.minimum[axis]
+        } else {
+          bounds.maximum[axis]
+        }
+      };
+      let point = self.point(Vec3(pick(1, 0), pick(2, 1), pick(4, 2)));
+      result = Aabb::surrounding_box(result, Aabb::new(point, point));
+    }
+    result
+  }
+}
+
+// places a shared object with a transform, rays are taken into the object's space and the hit
+// is brought back out. instances of one object share its geometry and acceleration structure,
+// and can each replace its material
+pub struct TransformedHittable {
+  object: Arc<dyn Hittable>,
+  transform: Transform,
+  to_object: Transform,
+  material: Option<Arc<dyn Material>>,
+}
+
+impl TransformedHittable {
+  pub fn new(object: Arc<dyn Hittable>, transform: Transform) -> TransformedHittable {
+    TransformedHittable {
+      object,
+      transform,
+      to_object: transform.inverse(),
+      material: None,
+    }
+  }
+
+  // overrides the material of every hit on this instance
+  pub fn set_material(&mut self, material: Arc<dyn Material>) {
+    self.material = Some(material);
+  }
+
+  pub fn transform(&self) -> Transform {
+    self.transform
+  }
+}
+
+impl Hittable for TransformedHittable {
+  fn hit<'a>(&'a self, ray: &Ray, t_min: f64, t_max: f64, record: &mut HitRecord<'a>) -> bool {
+    // the direction is not normalized, so t means the same in both spaces
+    let mut object_ray = Ray::new(self.to_object.point(ray.origin), self.to_object.vector(ray.direction));
+    object_ray.time = ray.time;
+
+    if !self.object.hit(&object_ray, t_min, t_max, record) {
+      return false;
+    }
+
+    // the normal keeps its side of the surface, so front_face stays valid
+    record.set_point(self.transform.point(record.point));
+    record.normal = self.transform.normal(record.normal).unit_vector();
+    if let Some(material) = &self.material {
+      record.set_material(material.as_ref());
+    }
+    // instances of one shared object are told apart
+    record.object_id = hittable::object_id(self);
+    true
+  }
+
+  fn bounding_box(&self, time0: f64, time1: f64, output_box: &mut Aabb) -> bool {
+    let mut object_box = Aabb::empty();
+    if !self.object.bounding_box(time0, time1, &mut object_box) {
+      return false;
+    }
+    *output_box = self.transform.bounding_box(object_box);
+    true
+  }
+
+  // the object's density for the direction in its own space, times the change of solid angle
+  // the transform causes, |det| / |d|^3 for a unit direction mapped to d
+  fn pdf_value(&self, origin: Vec3, direction: Vec3) -> f64 {
+    let object_direction = self.to_object.vector(direction.unit_vector());
+    let pdf = self
+      .object
+      .pdf_value(self.to_object.point(origin), object_direction);
+    if pdf == 0.0 {
+      return 0.0;
+    }
+
+    pdf * self.to_object.determinant().abs() / object_direction.len().powi(3)
+  }
+
+  fn random(&self, origin: Vec3) -> Vec3 {
+    let direction = self.object.random(self.to_object.point(origin));
+    self.transform.vector(direction)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+
+  use super::*;
+  use crate::sphere::Sphere;
+
+  #[test]
+  fn test_transform_composition_and_inverse() {
+    let transform = Transform::scale(Vec3(2.0, 1.0, 0.5))
+      .unwrap()
+      .then(Transform::rotate_y(90.0))
+      .then(Transform::translate(Vec3(1.0, 2.0, 3.0)));
+
+    // x is doubled, then turned onto -z, then moved
+    let p = transform.point(Vec3(1.0, 0.0, 0.0));
+    assert!((p - Vec3(1.0, 2.0, 1.0)).len() < 1.0e-9);
+    assert!((transform.inverse().point(p) - Vec3(1.0, 0.0, 0.0)).len() < 1.0e-9);
+    assert!((transform.determinant() - 1.0).abs() < 1.0e-9);
+
+    let general = Transform::from_matrix(transform.matrix()).unwrap();
+    assert!((general.inverse().point(p) - Vec3(1.0, 0.0, 0.0)).len() < 1.0e-9);
+    assert!(Transform::from_matrix([[0.0; 4]; 4]).is_none());
+    assert!(Transform::scale(Vec3(1.0, 0.0, 1.0)).is_none());
+
+    // normals stay perpendicular to transformed tangents under non-uniform scale
+    let squash = Transform::scale(Vec3(1.0, 4.0, 1.0)).unwrap();
+    let tangent = squash.vector(Vec3(1.0, -1.0, 0.0));
+    let normal = squash.normal(Vec3(1.0, 1.0, 0.0));
+    assert!(tangent.dot(normal).abs() < 1.0e-9);
+  }
+
+  #[test]
+  fn test_instances_share_one_object() {
+    let sphere: Arc<dyn Hittable> = Arc::new(Sphere::new(Vec3::fill(0.0), 1.0));
+    let moved = TransformedHittable::new(Arc::clone(&sphere), Transform::translate(Vec3(0.0, 0.0, -5.0)));
+    let stretched = TransformedHittable::new(Arc::clone(&sphere), Transform::scale(Vec3(1.0, 3.0, 1.0)).unwrap());
+    let mut record = HitRecord::new();
+
+    let ray = Ray::new(Vec3::fill(0.0), Vec3(0.0, 0.0, -1.0));
+    assert!(moved.hit(&ray, 0.001, f64::INFINITY, &mut record));
+    assert!((record.t - 4.0).abs() < 1.0e-9);
+    assert!((record.normal - Vec3(0.0, 0.0, 1.0)).len() < 1.0e-9);
+
+    let down = Ray::new(Vec3(0.0, 10.0, 0.0), Vec3(0.0, -1.0, 0.0));
+    assert!(stretched.hit(&down, 0.001, f64::INFINITY, &mut record));
+    assert!((record.point - Vec3(0.0, 3.0, 0.0)).len() < 1.0e-9);
+
+    let mut bounds = Aabb::empty();
+    assert!(stretched.bounding_box(0.0, 0.0, &mut bounds));
+    assert!((bounds.maximum - Vec3(1.0, 3.0, 1.0)).len() < 1.0e-9);
+
+    // a sphere scaled up by 2 is sampled like a sphere of radius 2
+    let grown = TransformedHittable::new(Arc::clone(&sphere), Transform::scale(Vec3::fill(2.0)).unwrap());
+    let big = Sphere::new(Vec3::fill(0.0), 2.0);
+    let origin = Vec3(0.0, 0.0, 6.0);
+    let direction = Vec3(0.1, 0.2, -1.0);
+    assert!((grown.pdf_value(origin, direction) - big.pdf_value(origin, direction)).abs() < 1.0e-9);
+  }
+}
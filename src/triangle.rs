@@ -0,0 +1,192 @@
+use std::sync::Arc;
+
+use crate::aabb::Aabb;
+use crate::hittable::{HitRecord, Hittable};
+use crate::material::{DefaultMaterial, Material};
+use crate::ray::Ray;
+use crate::sampler::sample_2d;
+use crate::vec3::Vec3;
+
+// padding for the boxes of axis aligned triangles, which are flat along one axis
+const BOX_PADDING: f64 = 1.0e-4;
+
+#[derive(Debug)]
+pub struct Triangle {
+  pub v0: Vec3,
+  pub v1: Vec3,
+  pub v2: Vec3,
+  pub material: Arc<dyn Material>,
+}
+
+impl Triangle {
+  pub fn new(v0: Vec3, v1: Vec3, v2: Vec3) -> Triangle {
+    Triangle {
+      v0,
+      v1,
+      v2,
+      material: Arc::new(DefaultMaterial::new()),
+    }
+  }
+
+  pub fn set_material(&mut self, material: Arc<dyn Material>) {
+    self.material = material;
+  }
+}
+
+pub(crate) fn triangle_box(p0: Vec3, p1: Vec3, p2: Vec3) -> Aabb {
+  let bounds = Aabb::surrounding_box(Aabb::new(p0, p0), Aabb::new(p1, p1));
+  Aabb::surrounding_box(bounds, Aabb::new(p2, p2)).padded(BOX_PADDING)
+}
+
+// the watertight test of Woop, Benthin and Wald, rays through a shared edge or vertex
+// always hit one of the adjacent triangles, returns t and the barycentric weights of p0, p1, p2
+pub(crate) fn intersect_triangle(
+  ray: &Ray,
+  p0: Vec3,
+  p1: Vec3,
+  p2: Vec3,
+  t_min: f64,
+  t_max: f64,
+) -> Option<(f64, f64, f64, f64)> {
+  let direction = ray.direction;
+
+  // permute the axes so the largest direction component becomes z
+  let abs = Vec3(direction.x().abs(), direction.y().abs(), direction.z().abs());
+  let kz = if abs.x() > abs.y() && abs.x() > abs.z() {
+    0
+  } else if abs.y() > abs.z() {
+    1
+  } else {
+    2
+  };
+  let mut kx = (kz + 1) % 3;
+  let mut ky = (kx + 1) % 3;
+  if direction[kz] < 0.0 {
+    std::mem::swap(&mut kx, &mut ky);
+  }
+
+  // shear the vertices so the ray points along +z from the origin
+  let sx = direction[kx] / direction[kz];
+  let sy = direction[ky] / direction[kz];
+  let sz = 1.0 / direction[kz];
+
+  let a = p0 - ray.origin;
+  let b = p1 - ray.origin;
+  let c = p2 - ray.origin;
+
+  let ax = a[kx] - sx * a[kz];
+  let ay = a[ky] - sy * a[kz];
+  let bx = b[kx] - sx * b[kz];
+  let by = b[ky] - sy * b[kz];
+  let cx = c[kx] - sx * c[kz];
+  let cy = c[ky] - sy * c[kz];
+
+  // scaled barycentric coordinates from 2D edge functions
+  let u = cx * by - cy * bx;
+  let v = ax * cy - ay * cx;
+  let w = bx * ay - by * ax;
+
+  if (u < 0.0 || v < 0.0 || w < 0.0) && (u > 0.0 || v > 0.0 || w > 0.0) {
+    return None;
+  }
+
+  let det = u + v + w;
+  if det == 0.0 {
+    return None;
+  }
+
+  let az = sz * a[kz];
+  let bz = sz * b[kz];
+  let cz = sz * c[kz];
+  let t = (u * az + v * bz + w * cz) / det;
+
+  if t <= t_min || t >= t_max {
+    return None;
+  }
+
+  Some((t, u / det, v / det, w / det))
+}
+
+impl Hittable for Triangle {
+  fn hit<'a>(&'a self, ray: &Ray, t_min: f64, t_max: f64, record: &mut HitRecord<'a>) -> bool {
+    let (t, _b0, b1, b2) = match intersect_triangle(ray, self.v0, self.v1, self.v2, t_min, t_max) {
+      Some(hit) => hit,
+      None => return false,
+    };
+
+    // counter-clockwise vertices face the viewer
+    let outward_normal = (self.v1 - self.v0).cross(self.v2 - self.v0).unit_vector();
+
+    record.set_t(t);
+    record.set_point(ray.at(t));
+    record.set_uv(b1, b2);
+    record.set_face_normal(ray, outward_normal);
+    record.set_material(self.material.as_ref());
+    true
+  }
+
+  fn bounding_box(&self, _time0: f64, _time1: f64, output_box: &mut Aabb) -> bool {
+    *output_box = triangle_box(self.v0, self.v1, self.v2);
+    true
+  }
+
+  // uniform over the area, converted to solid angle at origin
+  fn pdf_value(&self, origin: Vec3, direction: Vec3) -> f64 {
+    let ray = Ray::new(origin, direction);
+    let t = match intersect_triangle(&ray, self.v0, self.v1, self.v2, 0.001, f64::INFINITY) {
+      Some((t, _, _, _)) => t,
+      None => return 0.0,
+    };
+
+    let normal = (self.v1 - self.v0).cross(self.v2 - self.v0);
+    let area = 0.5 * normal.len();
+    let distance_squared = t * t * direction.length_squared();
+    let cosine = direction.dot(normal).abs() / (direction.len() * normal.len());
+
+    distance_squared / (cosine * area)
+  }
+
+  fn random(&self, origin: Vec3) -> Vec3 {
+    let (u, v) = sample_2d();
+    let su = u.sqrt();
+    let point = (1.0 - su) * self.v0 + (su * (1.0 - v)) * self.v1 + (su * v) * self.v2;
+    point - origin
+  }
+}
+
+#[cfg(test)]
+mod tests {
+
+  use super::*;
+
+  #[test]
+  fn test_triangle_hit_and_miss() {
+    let triangle = Triangle::new(Vec3(-1.0, -1.0, -2.0), Vec3(1.0, -1.0, -2.0), Vec3(0.0, 1.0, -2.0));
+    let mut record = HitRecord::new();
+
+    let ray = Ray::new(Vec3::fill(0.0), Vec3(0.0, 0.0, -1.0));
+    assert!(triangle.hit(&ray, 0.001, f64::INFINITY, &mut record));
+    assert!((record.t - 2.0).abs() < 1.0e-9);
+    assert!(record.front_face);
+    assert!(record.normal.eq(Vec3(0.0, 0.0, 1.0)));
+
+    let ray = Ray::new(Vec3::fill(0.0), Vec3(1.0, 1.0, -1.0));
+    assert!(!triangle.hit(&ray, 0.001, f64::INFINITY, &mut record));
+  }
+
+  #[test]
+  fn test_shared_edge_is_watertight() {
+    // two triangles forming a square, rays through the diagonal must hit one of them
+    let a = Triangle::new(Vec3(0.0, 0.0, 0.0), Vec3(1.0, 0.0, 0.0), Vec3(1.0, 1.0, 0.0));
+    let b = Triangle::new(Vec3(0.0, 0.0, 0.0), Vec3(1.0, 1.0, 0.0), Vec3(0.0, 1.0, 0.0));
+    let mut record = HitRecord::new();
+
+    for i in 1..100 {
+      let s = i as f64 / 100.0;
+      let ray = Ray::new(Vec3(s, s, 1.0), Vec3(0.0, 0.0, -1.0));
+      let hit_a = a.hit(&ray, 0.001, f64::INFINITY, &mut record);
+      let hit_b = b.hit(&ray, 0.001, f64::INFINITY, &mut record);
+      assert!(hit_a || hit_b);
+    }
+  }
+}
@@ -1,13 +1,71 @@
-use rand;
+use std::cell::Cell;
+
+// the PCG-XSH-RR generator of O'Neill, 64 bits of state and 32 bits of output
+#[derive(Debug, Clone, Copy)]
+pub struct Pcg32 {
+  state: u64,
+  increment: u64,
+}
+
+impl Pcg32 {
+  // generators with the same seed but different streams produce unrelated sequences
+  pub fn new(seed: u64, stream: u64) -> Pcg32 {
+    let mut rng = Pcg32 {
+      state: 0,
+      increment: (stream << 1) | 1,
+    };
+    rng.next_u32();
+    rng.state = rng.state.wrapping_add(seed);
+    rng.next_u32();
+    rng
+  }
+
+  pub fn next_u32(&mut self) -> u32 {
+    let old = self.state;
+    self.state = old
+      .wrapping_mul(6_364_136_223_846_793_005)
+      .wrapping_add(self.increment);
+    let xorshifted = (((old >> 18) ^ old) >> 27) as u32;
+    xorshifted.rotate_right((old >> 59) as u32)
+  }
+
+  // uniform in [0, 1), built from 53 random bits
+  pub fn next_f64(&mut self) -> f64 {
+    let high = (self.next_u32() as u64) << 21;
+    let low = (self.next_u32() >> 11) as u64;
+    (high | low) as f64 / (1u64 << 53) as f64
+  }
+}
+
+thread_local! {
+  // every thread starts from the same state, the renderer reseeds it for each pixel
+  static RNG: Cell<Pcg32> = Cell::new(Pcg32::new(0, 0));
+}
+
+// restarts the current thread's random sequence, see Pcg32::new
+pub fn seed_random(seed: u64, stream: u64) {
+  RNG.with(|rng| rng.set(Pcg32::new(seed, stream)));
+}
 
 pub fn random() -> f64 {
-  rand::random::<f64>()
+  RNG.with(|rng| {
+    let mut state = rng.get();
+    let value = state.next_f64();
+    rng.set(state);
+    value
+  })
 }
 
 pub fn random_in(min: f64, max: f64) -> f64 {
   min + (max - min) * random()
 }
 
+// random integer in [min, max]
+pub fn random_int(min: i64, max: i64) -> i64 {
+  let n = random_in(min as f64, (max + 1) as f64).floor() as i64;
+  n.min(max)
+}
+
 pub fn clamp(num: f64, min: f64, max: f64) -> f64 {
   if num < min {
     min
@@ -1,3 +1,4 @@
+use crate::sampler::{sample_1d, sample_2d};
 use crate::utils::{clamp, random, random_in};
 use std::ops;
 
@@ -71,6 +72,12 @@ impl Vec3 {
     *self / self.len()
   }
 
+  // the brightness of a linear color, with the Rec. 709 weights
+  pub fn luminance(&self) -> f64 {
+    0.2126 * self.0 + 0.7152 * self.1 + 0.0722 * self.2
+  }
+
+  #[allow(clippy::should_implement_trait)]
   pub fn eq(&self, b: Vec3) -> bool {
     let a = self;
     a.0 == b.0 && a.1 == b.1 && a.2 == b.2
@@ -92,22 +99,48 @@ impl Vec3 {
     )
   }
 
-  pub fn random_in_unit_sphere() -> Vec3 {
-    loop {
-      let point = Vec3::random_in(-1.0, 1.0);
-      if point.length_squared() >= 1.0 {
-        continue;
-      } else {
-        return point;
-      }
+  // the samplers hand out points of the unit square, these map them onto other domains
+  // without rejecting any, so stratified samples stay stratified
+
+  // concentric mapping of Shirley and Chiu, keeps neighbouring points of the square close on the disk
+  pub fn disk_from_square(u: f64, v: f64) -> Vec3 {
+    let (x, y) = (2.0 * u - 1.0, 2.0 * v - 1.0);
+    if x == 0.0 && y == 0.0 {
+      return Vec3(0.0, 0.0, 0.0);
     }
+
+    let quarter_pi = std::f64::consts::FRAC_PI_4;
+    let (r, theta) = if x.abs() > y.abs() {
+      (x, quarter_pi * (y / x))
+    } else {
+      (y, 2.0 * quarter_pi - quarter_pi * (x / y))
+    };
+    Vec3(r * theta.cos(), r * theta.sin(), 0.0)
+  }
+
+  // uniform on the surface of the unit sphere
+  pub fn sphere_from_square(u: f64, v: f64) -> Vec3 {
+    let z = 1.0 - 2.0 * u;
+    let r = (1.0 - z * z).max(0.0).sqrt();
+    let phi = 2.0 * std::f64::consts::PI * v;
+    Vec3(r * phi.cos(), r * phi.sin(), z)
+  }
+
+  // cosine weighted on the hemisphere around +z, points of the disk lifted up onto it (Malley's method)
+  pub fn cosine_from_square(u: f64, v: f64) -> Vec3 {
+    let disk = Vec3::disk_from_square(u, v);
+    let z = (1.0 - disk.length_squared()).max(0.0).sqrt();
+    Vec3(disk.x(), disk.y(), z)
+  }
+
+  pub fn random_in_unit_sphere() -> Vec3 {
+    let (u, v) = sample_2d();
+    Vec3::sphere_from_square(u, v) * sample_1d().cbrt()
   }
 
   pub fn random_unit_vector() -> Vec3 {
-    let a = random_in(0.0, std::f64::consts::PI);
-    let z = random_in(-1.0, 1.0);
-    let r = (1.0 - z.powi(2)).sqrt();
-    Vec3(r * a.cos(), r * a.sin(), z)
+    let (u, v) = sample_2d();
+    Vec3::sphere_from_square(u, v)
   }
 
   pub fn random_in_hemisphere(normal: Vec3) -> Vec3 {
@@ -120,14 +153,13 @@ impl Vec3 {
   }
 
   pub fn random_in_unit_disk() -> Vec3 {
-    loop {
-      let point = Vec3(random_in(-1.0, 1.0), random_in(-1.0, 1.0), 0.0);
-      if point.length_squared() >= 1.0 {
-        continue
-      } else {
-        return point
-      }
-    }
+    let (u, v) = sample_2d();
+    Vec3::disk_from_square(u, v)
+  }
+
+  pub fn random_cosine_direction() -> Vec3 {
+    let (u, v) = sample_2d();
+    Vec3::cosine_from_square(u, v)
   }
 
   pub fn reflect(v: Vec3, n: Vec3) -> Vec3 {
@@ -204,6 +236,32 @@ impl ops::Neg for Vec3 {
   }
 }
 
+// Vec3[usize]
+impl ops::Index<usize> for Vec3 {
+  type Output = f64;
+
+  fn index(&self, axis: usize) -> &f64 {
+    match axis {
+      0 => &self.0,
+      1 => &self.1,
+      2 => &self.2,
+      _ => panic!("Expected axis in 0..3, but received {}", axis),
+    }
+  }
+}
+
+// Vec3[usize] = f64
+impl ops::IndexMut<usize> for Vec3 {
+  fn index_mut(&mut self, axis: usize) -> &mut f64 {
+    match axis {
+      0 => &mut self.0,
+      1 => &mut self.1,
+      2 => &mut self.2,
+      _ => panic!("Expected axis in 0..3, but received {}", axis),
+    }
+  }
+}
+
 #[cfg(test)]
 mod tests {
 